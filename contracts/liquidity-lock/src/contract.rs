@@ -0,0 +1,613 @@
+use atl_snip20_reference_impl::msg::{HandleMsg as Snip20HandleMsg, ResponseStatus};
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    InitResponse, Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use lock_utils::lists::{page_ids, push_id};
+use lock_utils::registry::{register_token, registered_code_hash};
+
+use crate::msg::{HandleAnswer, HandleMsg, InitMsg, LockInfo, QueryAnswer, QueryMsg, ReceiveMsg};
+use crate::state::{
+    lock_count, lock_count_read, locked, locked_read, locks, locks_read, Lock,
+    BENEFICIARY_LOCKS_KEY, TOKEN_LOCKS_KEY,
+};
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    _deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    _msg: InitMsg,
+) -> StdResult<InitResponse> {
+    Ok(InitResponse::default())
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        HandleMsg::RegisterToken {
+            address, code_hash, ..
+        } => try_register_token(deps, env, address, code_hash),
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => try_receive(deps, env, from, amount, msg),
+        HandleMsg::ExtendLock {
+            lock_id,
+            unlock_height,
+            ..
+        } => try_extend_lock(deps, env, lock_id, unlock_height),
+        HandleMsg::Withdraw { lock_id, .. } => try_withdraw(deps, env, lock_id),
+    }
+}
+
+/// Anyone can register a token, once.
+pub fn try_register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    let register_receive = register_token(deps, &env, &address, code_hash)?;
+
+    Ok(HandleResponse {
+        messages: vec![register_receive],
+        log: vec![log("token", address)],
+        data: Some(to_binary(&HandleAnswer::RegisterToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let token = deps.api.canonical_address(&env.message.sender)?;
+    registered_code_hash(&deps.storage, &token)?;
+
+    let msg = msg.ok_or_else(|| StdError::generic_err("Missing lock message"))?;
+    let (beneficiary, unlock_height) = match from_binary(&msg)? {
+        ReceiveMsg::Lock {
+            beneficiary,
+            unlock_height,
+        } => (beneficiary, unlock_height),
+    };
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot lock zero tokens"));
+    }
+    if unlock_height <= env.block.height {
+        return Err(StdError::generic_err("Unlock height must be in the future"));
+    }
+
+    let beneficiary = beneficiary.unwrap_or(from);
+    let beneficiary_raw = deps.api.canonical_address(&beneficiary)?;
+    let lock_id = lock_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    lock_count(&mut deps.storage).save(&lock_id)?;
+    locks(&mut deps.storage).save(
+        &lock_id.to_be_bytes(),
+        &Lock {
+            token: token.clone(),
+            beneficiary: beneficiary_raw.clone(),
+            amount,
+            locked_at: env.block.height,
+            unlock_height,
+            withdrawn: false,
+        },
+    )?;
+    push_id(
+        &mut deps.storage,
+        TOKEN_LOCKS_KEY,
+        token.as_slice(),
+        lock_id,
+    )?;
+    push_id(
+        &mut deps.storage,
+        BENEFICIARY_LOCKS_KEY,
+        beneficiary_raw.as_slice(),
+        lock_id,
+    )?;
+    let total = locked_read(&deps.storage)
+        .may_load(token.as_slice())?
+        .unwrap_or_default();
+    locked(&mut deps.storage).save(token.as_slice(), &Uint128(total.u128() + amount.u128()))?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("lock_id", lock_id),
+            log("token", env.message.sender),
+            log("beneficiary", beneficiary),
+            log("amount", amount),
+            log("unlock_height", unlock_height),
+        ],
+        data: Some(to_binary(&HandleAnswer::Receive { lock_id })?),
+    })
+}
+
+pub fn try_extend_lock<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    lock_id: u64,
+    unlock_height: u64,
+) -> StdResult<HandleResponse> {
+    let mut lock = load_beneficiary_lock(deps, &env, lock_id)?;
+    if lock.withdrawn {
+        return Err(StdError::generic_err("Lock was already withdrawn"));
+    }
+    if unlock_height <= lock.unlock_height {
+        return Err(StdError::generic_err(format!(
+            "Locks can only be extended past their unlock height: unlock_height={}",
+            lock.unlock_height
+        )));
+    }
+
+    lock.unlock_height = unlock_height;
+    locks(&mut deps.storage).save(&lock_id.to_be_bytes(), &lock)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("lock_id", lock_id), log("unlock_height", unlock_height)],
+        data: Some(to_binary(&HandleAnswer::ExtendLock {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_withdraw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    lock_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut lock = load_beneficiary_lock(deps, &env, lock_id)?;
+    if lock.withdrawn {
+        return Err(StdError::generic_err("Lock was already withdrawn"));
+    }
+    if env.block.height < lock.unlock_height {
+        return Err(StdError::generic_err(format!(
+            "Lock is still locked until height {}",
+            lock.unlock_height
+        )));
+    }
+
+    lock.withdrawn = true;
+    locks(&mut deps.storage).save(&lock_id.to_be_bytes(), &lock)?;
+    let total = locked_read(&deps.storage).load(lock.token.as_slice())?;
+    locked(&mut deps.storage).save(lock.token.as_slice(), &(total - lock.amount)?)?;
+    let code_hash = registered_code_hash(&deps.storage, &lock.token)?;
+
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: deps.api.human_address(&lock.token)?,
+            callback_code_hash: code_hash,
+            msg: to_binary(&Snip20HandleMsg::Transfer {
+                recipient: env.message.sender,
+                amount: lock.amount,
+                memo: None,
+                padding: None,
+            })?,
+            send: vec![],
+        })],
+        log: vec![log("lock_id", lock_id), log("amount", lock.amount)],
+        data: Some(to_binary(&HandleAnswer::Withdraw {
+            amount: lock.amount,
+        })?),
+    })
+}
+
+fn load_beneficiary_lock<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    lock_id: u64,
+) -> StdResult<Lock> {
+    let lock = load_lock(&deps.storage, lock_id)?;
+    if lock.beneficiary != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the beneficiary of a lock can change it",
+        ));
+    }
+
+    Ok(lock)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Lock { lock_id } => query_lock(deps, lock_id),
+        QueryMsg::TokenLocks {
+            token,
+            page,
+            page_size,
+        } => query_token_locks(deps, &token, page.unwrap_or(0), page_size),
+        QueryMsg::BeneficiaryLocks {
+            beneficiary,
+            page,
+            page_size,
+        } => query_beneficiary_locks(deps, &beneficiary, page.unwrap_or(0), page_size),
+    }
+}
+
+fn query_lock<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    lock_id: u64,
+) -> StdResult<Binary> {
+    let lock = load_lock(&deps.storage, lock_id)?;
+
+    to_binary(&QueryAnswer::Lock {
+        lock: lock_info(deps, lock_id, lock)?,
+    })
+}
+
+fn query_token_locks<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let token = deps.api.canonical_address(token)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        TOKEN_LOCKS_KEY,
+        token.as_slice(),
+        page,
+        page_size,
+    )?;
+    let locks = lock_infos(deps, ids)?;
+    let locked = locked_read(&deps.storage)
+        .may_load(token.as_slice())?
+        .unwrap_or_default();
+
+    to_binary(&QueryAnswer::TokenLocks {
+        locks,
+        total,
+        locked,
+    })
+}
+
+fn query_beneficiary_locks<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    beneficiary: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let beneficiary = deps.api.canonical_address(beneficiary)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        BENEFICIARY_LOCKS_KEY,
+        beneficiary.as_slice(),
+        page,
+        page_size,
+    )?;
+    let locks = lock_infos(deps, ids)?;
+
+    to_binary(&QueryAnswer::BeneficiaryLocks { locks, total })
+}
+
+fn lock_infos<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    ids: Vec<u64>,
+) -> StdResult<Vec<LockInfo>> {
+    ids.into_iter()
+        .map(|lock_id| lock_info(deps, lock_id, load_lock(&deps.storage, lock_id)?))
+        .collect()
+}
+
+fn lock_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    lock_id: u64,
+    lock: Lock,
+) -> StdResult<LockInfo> {
+    Ok(LockInfo {
+        id: lock_id,
+        token: deps.api.human_address(&lock.token)?,
+        beneficiary: deps.api.human_address(&lock.beneficiary)?,
+        amount: lock.amount,
+        locked_at: lock.locked_at,
+        unlock_height: lock.unlock_height,
+        withdrawn: lock.withdrawn,
+    })
+}
+
+fn load_lock<S: Storage>(storage: &S, lock_id: u64) -> StdResult<Lock> {
+    locks_read(storage)
+        .may_load(&lock_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("Lock {} not found", lock_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+
+    fn init_lock() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("admin", &[]), InitMsg {}).unwrap();
+        handle(
+            &mut deps,
+            mock_env("anyone", &[]),
+            HandleMsg::RegisterToken {
+                address: HumanAddr("lp_token".to_string()),
+                code_hash: "lp_hash".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        deps
+    }
+
+    fn env_at(sender: &str, height: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.height = height;
+        env
+    }
+
+    fn lock_msg(
+        from: &str,
+        amount: u128,
+        beneficiary: Option<&str>,
+        unlock_height: u64,
+    ) -> HandleMsg {
+        HandleMsg::Receive {
+            sender: HumanAddr(from.to_string()),
+            from: HumanAddr(from.to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            msg: Some(
+                to_binary(&ReceiveMsg::Lock {
+                    beneficiary: beneficiary.map(|b| HumanAddr(b.to_string())),
+                    unlock_height,
+                })
+                .unwrap(),
+            ),
+        }
+    }
+
+    fn token_locks(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+    ) -> (Vec<LockInfo>, u64, Uint128) {
+        let msg = QueryMsg::TokenLocks {
+            token: HumanAddr("lp_token".to_string()),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(deps, msg).unwrap()).unwrap() {
+            QueryAnswer::TokenLocks {
+                locks,
+                total,
+                locked,
+            } => (locks, total, locked),
+            _ => panic!("unexpected answer"),
+        }
+    }
+
+    fn assert_generic_err(result: StdResult<HandleResponse>, expected: &str) {
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.contains(expected), "{}", msg),
+            other => panic!("expected error {:?}, got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn test_lock_and_public_queries() {
+        let mut deps = init_lock();
+
+        let register = handle(
+            &mut deps,
+            mock_env("anyone", &[]),
+            HandleMsg::RegisterToken {
+                address: HumanAddr("snipix".to_string()),
+                code_hash: "snipix_hash".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            register.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("snipix".to_string()),
+                callback_code_hash: "snipix_hash".to_string(),
+                msg: to_binary(&Snip20HandleMsg::RegisterReceive {
+                    code_hash: mock_env("anyone", &[]).contract_code_hash,
+                    padding: None,
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("mallory", &[]),
+                HandleMsg::RegisterToken {
+                    address: HumanAddr("snipix".to_string()),
+                    code_hash: "mallory_hash".to_string(),
+                    padding: None,
+                },
+            ),
+            "Token is already registered",
+        );
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("unknown_token", 10),
+                lock_msg("alice", 100, None, 20),
+            ),
+            "Token is not registered",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("lp_token", 10),
+                lock_msg("alice", 100, None, 10),
+            ),
+            "Unlock height must be in the future",
+        );
+
+        handle(
+            &mut deps,
+            env_at("lp_token", 10),
+            lock_msg("alice", 100, None, 20),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("lp_token", 11),
+            lock_msg("bob", 50, Some("team"), 30),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("snipix", 12),
+            lock_msg("bob", 7, None, 40),
+        )
+        .unwrap();
+
+        let (locks, total, locked) = token_locks(&deps);
+        assert_eq!(total, 2);
+        assert_eq!(locked, Uint128(150));
+        assert_eq!(
+            locks,
+            vec![
+                LockInfo {
+                    id: 2,
+                    token: HumanAddr("lp_token".to_string()),
+                    beneficiary: HumanAddr("team".to_string()),
+                    amount: Uint128(50),
+                    locked_at: 11,
+                    unlock_height: 30,
+                    withdrawn: false,
+                },
+                LockInfo {
+                    id: 1,
+                    token: HumanAddr("lp_token".to_string()),
+                    beneficiary: HumanAddr("alice".to_string()),
+                    amount: Uint128(100),
+                    locked_at: 10,
+                    unlock_height: 20,
+                    withdrawn: false,
+                },
+            ]
+        );
+
+        let msg = QueryMsg::BeneficiaryLocks {
+            beneficiary: HumanAddr("bob".to_string()),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::BeneficiaryLocks { locks, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(locks[0].token, HumanAddr("snipix".to_string()));
+                assert_eq!(locks[0].amount, Uint128(7));
+            }
+            _ => panic!("unexpected answer"),
+        }
+    }
+
+    #[test]
+    fn test_extend_and_withdraw() {
+        let mut deps = init_lock();
+        handle(
+            &mut deps,
+            env_at("lp_token", 10),
+            lock_msg("alice", 100, None, 20),
+        )
+        .unwrap();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("bob", 12),
+                HandleMsg::ExtendLock {
+                    lock_id: 1,
+                    unlock_height: 50,
+                    padding: None,
+                },
+            ),
+            "Only the beneficiary of a lock can change it",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 12),
+                HandleMsg::ExtendLock {
+                    lock_id: 1,
+                    unlock_height: 15,
+                    padding: None,
+                },
+            ),
+            "Locks can only be extended past their unlock height",
+        );
+        handle(
+            &mut deps,
+            env_at("alice", 12),
+            HandleMsg::ExtendLock {
+                lock_id: 1,
+                unlock_height: 50,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 30),
+                HandleMsg::Withdraw {
+                    lock_id: 1,
+                    padding: None,
+                },
+            ),
+            "Lock is still locked until height 50",
+        );
+        let withdraw = handle(
+            &mut deps,
+            env_at("alice", 50),
+            HandleMsg::Withdraw {
+                lock_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            withdraw.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("lp_token".to_string()),
+                callback_code_hash: "lp_hash".to_string(),
+                msg: to_binary(&Snip20HandleMsg::Transfer {
+                    recipient: HumanAddr("alice".to_string()),
+                    amount: Uint128(100),
+                    memo: None,
+                    padding: None,
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+
+        let (locks, _, locked) = token_locks(&deps);
+        assert_eq!(locked, Uint128::zero());
+        assert!(locks[0].withdrawn);
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 60),
+                HandleMsg::Withdraw {
+                    lock_id: 1,
+                    padding: None,
+                },
+            ),
+            "Lock was already withdrawn",
+        );
+    }
+}
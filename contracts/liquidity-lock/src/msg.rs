@@ -0,0 +1,103 @@
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct InitMsg {}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// Has a SNIP-20 token notify the contract of what is sent to it, so it can be locked.
+    RegisterToken {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    /// Sent by a registered token when tokens are sent to the contract, with a `ReceiveMsg`.
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
+    },
+    /// Moves the unlock height of a lock further away. Only its beneficiary can extend it.
+    ExtendLock {
+        lock_id: u64,
+        unlock_height: u64,
+        padding: Option<String>,
+    },
+    /// Sends the tokens of an unlocked lock to its beneficiary.
+    Withdraw {
+        lock_id: u64,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Locks the received tokens until `unlock_height`, for `beneficiary` or else their sender.
+    Lock {
+        beneficiary: Option<HumanAddr>,
+        unlock_height: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    RegisterToken { status: ResponseStatus },
+    Receive { lock_id: u64 },
+    ExtendLock { status: ResponseStatus },
+    Withdraw { amount: Uint128 },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Lock {
+        lock_id: u64,
+    },
+    /// Locks of a token, most recent first, along with how much of it is still locked.
+    TokenLocks {
+        token: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    BeneficiaryLocks {
+        beneficiary: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct LockInfo {
+    pub id: u64,
+    pub token: HumanAddr,
+    pub beneficiary: HumanAddr,
+    pub amount: Uint128,
+    pub locked_at: u64,
+    pub unlock_height: u64,
+    pub withdrawn: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    Lock {
+        lock: LockInfo,
+    },
+    TokenLocks {
+        locks: Vec<LockInfo>,
+        total: u64,
+        locked: Uint128,
+    },
+    BeneficiaryLocks {
+        locks: Vec<LockInfo>,
+        total: u64,
+    },
+}
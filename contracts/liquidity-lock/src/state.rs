@@ -0,0 +1,52 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, Storage, Uint128};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+
+pub static LOCKED_KEY: &[u8] = b"locked";
+pub static LOCK_COUNT_KEY: &[u8] = b"lock_count";
+pub static LOCKS_KEY: &[u8] = b"locks";
+pub static TOKEN_LOCKS_KEY: &[u8] = b"token_locks";
+pub static BENEFICIARY_LOCKS_KEY: &[u8] = b"beneficiary_locks";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Lock {
+    pub token: CanonicalAddr,
+    pub beneficiary: CanonicalAddr,
+    pub amount: Uint128,
+    /// Block height at which the tokens were locked.
+    pub locked_at: u64,
+    pub unlock_height: u64,
+    /// Withdrawn locks are kept, so that lock histories stay public.
+    pub withdrawn: bool,
+}
+
+/// Amount of each token in locks that were not withdrawn yet, by token address.
+pub fn locked<S: Storage>(storage: &mut S) -> Bucket<S, Uint128> {
+    bucket(LOCKED_KEY, storage)
+}
+
+pub fn locked_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Uint128> {
+    bucket_read(LOCKED_KEY, storage)
+}
+
+pub fn lock_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, LOCK_COUNT_KEY)
+}
+
+pub fn lock_count_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, LOCK_COUNT_KEY)
+}
+
+/// Locks, by id.
+pub fn locks<S: Storage>(storage: &mut S) -> Bucket<S, Lock> {
+    bucket(LOCKS_KEY, storage)
+}
+
+pub fn locks_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Lock> {
+    bucket_read(LOCKS_KEY, storage)
+}
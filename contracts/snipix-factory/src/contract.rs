@@ -0,0 +1,2236 @@
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{
+    log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Decimal, Env, Extern,
+    HandleResponse, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128,
+    WasmMsg,
+};
+use sha2::{Digest, Sha256};
+use snipix::msg::{InitCallback, MarketingInfo, MigrationSource};
+
+use crate::msg::{
+    CodeVersion, FeeSchedule, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg,
+    SymbolStatus, Template, TokenInfo, TokenOverrides, Verification, VerificationStatus,
+};
+use crate::state::{
+    code_versions, code_versions_read, config, config_read, count_token_ids_below, fee_schedule,
+    fee_schedule_read, insert_sorted_token_id, name_tokens, name_tokens_read, page_token_ids,
+    pending_launch, push_token_id, remove_sorted_token_id, remove_token_id, reserved_symbols,
+    reserved_symbols_read, sorted_token_ids, symbol_tokens, symbol_tokens_read, template_names,
+    template_names_read, templates, templates_read, token_count, token_count_read, token_ids,
+    token_ids_read, tokens, tokens_read, treasury, treasury_read, Config, PendingLaunch, Token,
+    CREATOR_TOKENS_KEY, DEREGISTERED_TOKENS_KEY, SYMBOL_PREFIX_TOKENS_KEY, VERIFIED_TOKENS_KEY,
+};
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: InitMsg,
+) -> StdResult<InitResponse> {
+    let admin = msg.admin.unwrap_or(env.message.sender);
+    let curator = msg.curator.unwrap_or_else(|| admin.clone());
+    config(&mut deps.storage).save(&Config {
+        admin: deps.api.canonical_address(&admin)?,
+        curator: deps.api.canonical_address(&curator)?,
+        latest_version: 1,
+        prng_seed: msg.prng_seed.0,
+        unique_names: msg.unique_names.unwrap_or(false),
+    })?;
+    code_versions(&mut deps.storage).save(
+        &1u32.to_be_bytes(),
+        &CodeVersion {
+            version: 1,
+            code_id: msg.snipix_code_id,
+            code_hash: msg.snipix_code_hash,
+            changelog: "Initial version".to_string(),
+            deprecated: false,
+            released_at: env.block.height,
+        },
+    )?;
+    for symbol in msg.reserved_symbols.unwrap_or_default() {
+        reserved_symbols(&mut deps.storage).save(symbol.to_uppercase().as_bytes(), &true)?;
+    }
+    if let Some(schedule) = &msg.fee_schedule {
+        validate_fee_schedule(schedule)?;
+    }
+    fee_schedule(&mut deps.storage).save(&msg.fee_schedule)?;
+    for template in msg.templates.unwrap_or_default() {
+        save_template(&mut deps.storage, template)?;
+    }
+
+    Ok(InitResponse::default())
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        HandleMsg::CreateToken {
+            name,
+            symbol,
+            template,
+            code_version,
+            overrides,
+            ..
+        } => try_create_token(
+            deps,
+            env,
+            name,
+            symbol,
+            template,
+            code_version,
+            overrides.unwrap_or_default(),
+        ),
+        HandleMsg::RegisterToken {} => try_register_token(deps, env),
+        HandleMsg::UpdateMarketingInfo { marketing_info } => {
+            try_update_marketing_info(deps, env, marketing_info)
+        }
+        HandleMsg::MigrateToken {
+            code_version,
+            init_msg,
+        } => try_migrate_token(deps, env, code_version, *init_msg),
+        HandleMsg::SetVerification {
+            address,
+            status,
+            reason,
+            ..
+        } => try_set_verification(deps, env, address, status, reason),
+        HandleMsg::DeregisterToken { address, .. } => try_deregister_token(deps, env, address),
+        HandleMsg::AddCodeVersion {
+            code_id,
+            code_hash,
+            changelog,
+            ..
+        } => try_add_code_version(deps, env, code_id, code_hash, changelog),
+        HandleMsg::DeprecateCodeVersion { version, .. } => {
+            try_deprecate_code_version(deps, env, version)
+        }
+        HandleMsg::ChangeAdmin { address, .. } => try_change_admin(deps, env, address),
+        HandleMsg::SetCurator { address, .. } => try_set_curator(deps, env, address),
+        HandleMsg::ReserveSymbols { symbols, .. } => try_reserve_symbols(deps, env, symbols, true),
+        HandleMsg::UnreserveSymbols { symbols, .. } => {
+            try_reserve_symbols(deps, env, symbols, false)
+        }
+        HandleMsg::SetFeeSchedule { fee_schedule, .. } => {
+            try_set_fee_schedule(deps, env, fee_schedule)
+        }
+        HandleMsg::SetTemplate { template, .. } => try_set_template(deps, env, template),
+        HandleMsg::RemoveTemplate { name, .. } => try_remove_template(deps, env, name),
+        HandleMsg::WithdrawFees {
+            denom,
+            amount,
+            recipient,
+            ..
+        } => try_withdraw_fees(deps, env, denom, amount, recipient),
+    }
+}
+
+/// Instantiates a snipix token from `template`, which calls back `RegisterToken` once created.
+/// The launch fee is kept, and anything sent on top of it refunded.
+pub fn try_create_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    symbol: String,
+    template: String,
+    code_version: Option<u32>,
+    overrides: TokenOverrides,
+) -> StdResult<HandleResponse> {
+    let config = config_read(&deps.storage).load()?;
+    let creator = deps.api.canonical_address(&env.message.sender)?;
+    check_availability(&deps.storage, &config, &creator, &name, &symbol)?;
+    let template = templates_read(&deps.storage)
+        .may_load(template.as_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("Template {} not found", template)))?;
+    let version = load_launch_version(&deps.storage, &config, code_version)?;
+    let refund = charge_launch_fee(&mut deps.storage, &env, Some(&template.name))?;
+    let token_id = token_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+
+    let prng_seed = match &overrides.prng_seed {
+        Some(prng_seed) => prng_seed.clone(),
+        None => launch_seed(&config, &env, token_id),
+    };
+    let mut token_msg = apply_template(&template, name, symbol, overrides, prng_seed)?;
+
+    pending_launch(&mut deps.storage).save(&Some(PendingLaunch {
+        creator,
+        code_id: version.code_id,
+        code_hash: version.code_hash.clone(),
+        code_version: version.version,
+        name: token_msg.name.clone(),
+        symbol: token_msg.symbol.clone(),
+        decimals: token_msg.decimals,
+        template: template.name,
+        marketing_info: token_msg.marketing_info.clone(),
+        migrated_from: None,
+    }))?;
+
+    token_msg.admin = token_msg.admin.or_else(|| Some(env.message.sender.clone()));
+    let mut messages = vec![instantiate_token_msg(&env, &version, token_id, token_msg)?];
+    if !refund.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender,
+            amount: refund,
+        }));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Records the sender as the token of the pending launch.
+pub fn try_register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let launch = pending_launch(&mut deps.storage)
+        .may_load()?
+        .flatten()
+        .ok_or_else(|| StdError::generic_err("No token launch is pending"))?;
+    pending_launch(&mut deps.storage).save(&None)?;
+
+    let config = config_read(&deps.storage).load()?;
+    let token_id = token_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    let mut verification = None;
+    let mut messages = vec![];
+    match launch.migrated_from {
+        // The new version takes over the symbol and name of the token it replaces, which then
+        // only lets holders send their balances over.
+        Some(previous_id) => {
+            let mut previous = load_token(&deps.storage, previous_id)?;
+            symbol_tokens(&mut deps.storage).remove(previous.symbol.to_uppercase().as_bytes());
+            if config.unique_names {
+                name_tokens(&mut deps.storage).remove(previous.name.to_lowercase().as_bytes());
+            }
+            unlist_token(&mut deps.storage, previous_id, &previous)?;
+            previous.deregistered = true;
+            previous.migrated_to = Some(token_id);
+            verification = previous.verification.clone();
+            tokens(&mut deps.storage).save(&previous_id.to_be_bytes(), &previous)?;
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: deps.api.human_address(&previous.address)?,
+                callback_code_hash: previous.code_hash,
+                msg: to_binary(&snipix::msg::HandleMsg::CompleteMigration {
+                    successor: MigrationSource {
+                        address: env.message.sender.clone(),
+                        code_hash: launch.code_hash.clone(),
+                    },
+                    padding: None,
+                })?,
+                send: vec![],
+            }));
+        }
+        None => check_availability(
+            &deps.storage,
+            &config,
+            &launch.creator,
+            &launch.name,
+            &launch.symbol,
+        )?,
+    }
+
+    let address = deps.api.canonical_address(&env.message.sender)?;
+    token_count(&mut deps.storage).save(&token_id)?;
+    token_ids(&mut deps.storage).save(address.as_slice(), &token_id)?;
+    symbol_tokens(&mut deps.storage).save(launch.symbol.to_uppercase().as_bytes(), &token_id)?;
+    if config.unique_names {
+        name_tokens(&mut deps.storage).save(launch.name.to_lowercase().as_bytes(), &token_id)?;
+    }
+    let creator_index = push_token_id(
+        &mut deps.storage,
+        CREATOR_TOKENS_KEY,
+        launch.creator.as_slice(),
+        token_id,
+    )?;
+    let symbol_indexes = symbol_prefixes(&launch.symbol)
+        .iter()
+        .map(|prefix| {
+            push_token_id(
+                &mut deps.storage,
+                SYMBOL_PREFIX_TOKENS_KEY,
+                prefix.as_bytes(),
+                token_id,
+            )
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let token = Token {
+        address,
+        code_id: launch.code_id,
+        code_hash: launch.code_hash,
+        code_version: launch.code_version,
+        creator: launch.creator,
+        name: launch.name,
+        symbol: launch.symbol,
+        decimals: launch.decimals,
+        template: launch.template,
+        marketing_info: launch.marketing_info,
+        created_at: env.block.height,
+        verification,
+        creator_index,
+        symbol_indexes,
+        deregistered: false,
+        migrated_from: launch.migrated_from,
+        migrated_to: None,
+    };
+    // A new version of a verified token is listed as verified from the start.
+    if is_verified(&token) {
+        insert_sorted_token_id(&mut deps.storage, VERIFIED_TOKENS_KEY, token_id)?;
+    }
+    tokens(&mut deps.storage).save(&token_id.to_be_bytes(), &token)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("token_id", token_id),
+            log("token_address", env.message.sender),
+        ],
+        data: Some(to_binary(&HandleAnswer::RegisterToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Refreshes the marketing info snapshot of the sending token.
+pub fn try_update_marketing_info<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    marketing_info: Option<MarketingInfo>,
+) -> StdResult<HandleResponse> {
+    let address = deps.api.canonical_address(&env.message.sender)?;
+    let token_id = token_ids_read(&deps.storage)
+        .may_load(address.as_slice())?
+        .ok_or_else(|| {
+            StdError::generic_err("Only launched tokens can update their marketing info")
+        })?;
+
+    // Deregistered tokens keep working, so their updates are accepted but no longer recorded.
+    let mut token = load_token(&deps.storage, token_id)?;
+    if !token.deregistered {
+        token.marketing_info = marketing_info;
+        tokens(&mut deps.storage).save(&token_id.to_be_bytes(), &token)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::UpdateMarketingInfo {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Instantiates `init_msg`, sent by a launched token, with a newer code version and a fresh seed.
+/// The new instance takes the place of the sender in the registry once it calls back
+/// `RegisterToken`.
+pub fn try_migrate_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_version: Option<u32>,
+    mut init_msg: snipix::msg::InitMsg,
+) -> StdResult<HandleResponse> {
+    let config = config_read(&deps.storage).load()?;
+    let address = deps.api.canonical_address(&env.message.sender)?;
+    let previous_id = token_ids_read(&deps.storage)
+        .may_load(address.as_slice())?
+        .ok_or_else(|| StdError::generic_err("Only launched tokens can migrate"))?;
+    let previous = load_token(&deps.storage, previous_id)?;
+    if previous.migrated_to.is_some() {
+        return Err(StdError::generic_err("Token was already migrated"));
+    }
+    if previous.deregistered {
+        return Err(StdError::generic_err("Deregistered tokens cannot migrate"));
+    }
+    let version = load_launch_version(&deps.storage, &config, code_version)?;
+    if version.version <= previous.code_version {
+        return Err(StdError::generic_err(
+            "Tokens can only migrate to a newer code version",
+        ));
+    }
+
+    let token_id = token_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    pending_launch(&mut deps.storage).save(&Some(PendingLaunch {
+        creator: previous.creator,
+        code_id: version.code_id,
+        code_hash: version.code_hash.clone(),
+        code_version: version.version,
+        name: previous.name,
+        symbol: previous.symbol,
+        decimals: init_msg.decimals,
+        template: previous.template,
+        marketing_info: init_msg.marketing_info.clone(),
+        migrated_from: Some(previous_id),
+    }))?;
+
+    init_msg.prng_seed = launch_seed(&config, &env, token_id);
+    init_msg.migrated_from = Some(MigrationSource {
+        address: env.message.sender.clone(),
+        code_hash: previous.code_hash,
+    });
+
+    Ok(HandleResponse {
+        messages: vec![instantiate_token_msg(&env, &version, token_id, init_msg)?],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::MigrateToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_set_verification<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    status: VerificationStatus,
+    reason: String,
+) -> StdResult<HandleResponse> {
+    let config = config_read(&deps.storage).load()?;
+    if config.curator != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err("Only the curator can badge tokens"));
+    }
+
+    let (token_id, mut token) = load_registered_token(deps, &address)?;
+    let was_verified = is_verified(&token);
+    token.verification = Some(Verification {
+        status,
+        reason,
+        updated_at: env.block.height,
+    });
+    match (was_verified, is_verified(&token)) {
+        (false, true) => insert_sorted_token_id(&mut deps.storage, VERIFIED_TOKENS_KEY, token_id)?,
+        (true, false) => remove_sorted_token_id(&mut deps.storage, VERIFIED_TOKENS_KEY, token_id)?,
+        _ => {}
+    }
+    tokens(&mut deps.storage).save(&token_id.to_be_bytes(), &token)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetVerification {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_deregister_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let config = config_read(&deps.storage).load()?;
+    let (token_id, mut token) = load_registered_token(deps, &address)?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != token.creator && sender != config.admin {
+        return Err(StdError::generic_err(
+            "Only the creator of a token or the admin can deregister it",
+        ));
+    }
+    if token.migrated_to.is_some() {
+        return Err(StdError::generic_err(
+            "Migrated tokens cannot be deregistered",
+        ));
+    }
+
+    symbol_tokens(&mut deps.storage).remove(token.symbol.to_uppercase().as_bytes());
+    if config.unique_names {
+        name_tokens(&mut deps.storage).remove(token.name.to_lowercase().as_bytes());
+    }
+    unlist_token(&mut deps.storage, token_id, &token)?;
+    token.deregistered = true;
+    tokens(&mut deps.storage).save(&token_id.to_be_bytes(), &token)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DeregisterToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_add_code_version<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_id: u64,
+    code_hash: String,
+    changelog: String,
+) -> StdResult<HandleResponse> {
+    let mut state = load_admin_config(deps, &env)?;
+    state.latest_version += 1;
+    code_versions(&mut deps.storage).save(
+        &state.latest_version.to_be_bytes(),
+        &CodeVersion {
+            version: state.latest_version,
+            code_id,
+            code_hash,
+            changelog,
+            deprecated: false,
+            released_at: env.block.height,
+        },
+    )?;
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AddCodeVersion {
+            version: state.latest_version,
+        })?),
+    })
+}
+
+/// Tokens already running a deprecated version are not affected, and can still migrate away from
+/// it.
+pub fn try_deprecate_code_version<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    version: u32,
+) -> StdResult<HandleResponse> {
+    let state = load_admin_config(deps, &env)?;
+    if version == state.latest_version {
+        return Err(StdError::generic_err(
+            "The latest code version cannot be deprecated",
+        ));
+    }
+    let mut code_version = load_code_version(&deps.storage, version)?;
+    code_version.deprecated = true;
+    code_versions(&mut deps.storage).save(&version.to_be_bytes(), &code_version)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DeprecateCodeVersion {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_change_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut state = load_admin_config(deps, &env)?;
+    state.admin = deps.api.canonical_address(&address)?;
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ChangeAdmin {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_set_curator<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut state = load_admin_config(deps, &env)?;
+    state.curator = deps.api.canonical_address(&address)?;
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetCurator {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Reserves `symbols` for the admin, or releases them when `reserved` is false.
+pub fn try_reserve_symbols<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    symbols: Vec<String>,
+    reserved: bool,
+) -> StdResult<HandleResponse> {
+    load_admin_config(deps, &env)?;
+    for symbol in symbols {
+        let symbol = symbol.to_uppercase();
+        if reserved {
+            reserved_symbols(&mut deps.storage).save(symbol.as_bytes(), &true)?;
+        } else {
+            reserved_symbols(&mut deps.storage).remove(symbol.as_bytes());
+        }
+    }
+
+    let answer = if reserved {
+        HandleAnswer::ReserveSymbols {
+            status: ResponseStatus::Success,
+        }
+    } else {
+        HandleAnswer::UnreserveSymbols {
+            status: ResponseStatus::Success,
+        }
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+pub fn try_set_fee_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    schedule: Option<FeeSchedule>,
+) -> StdResult<HandleResponse> {
+    load_admin_config(deps, &env)?;
+    if let Some(schedule) = &schedule {
+        validate_fee_schedule(schedule)?;
+    }
+    fee_schedule(&mut deps.storage).save(&schedule)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetFeeSchedule {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_set_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    template: Template,
+) -> StdResult<HandleResponse> {
+    load_admin_config(deps, &env)?;
+    save_template(&mut deps.storage, template)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetTemplate {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Tokens already launched from the template are not affected.
+pub fn try_remove_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> StdResult<HandleResponse> {
+    load_admin_config(deps, &env)?;
+
+    let mut names = template_names_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    if !names.contains(&name) {
+        return Err(StdError::generic_err(format!(
+            "Template {} not found",
+            name
+        )));
+    }
+    names.retain(|other| other != &name);
+    template_names(&mut deps.storage).save(&names)?;
+    templates(&mut deps.storage).remove(name.as_bytes());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveTemplate {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_withdraw_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    denom: String,
+    amount: Option<Uint128>,
+    recipient: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    load_admin_config(deps, &env)?;
+
+    let mut balances = treasury_read(&deps.storage).may_load()?.unwrap_or_default();
+    let balance = balances
+        .iter_mut()
+        .find(|coin| coin.denom == denom)
+        .ok_or_else(|| StdError::generic_err(format!("No {} fees to withdraw", denom)))?;
+    let amount = amount.unwrap_or(balance.amount);
+    if amount.is_zero() || amount > balance.amount {
+        return Err(StdError::generic_err(format!(
+            "Insufficient fees: balance={}, required={}",
+            balance.amount, amount
+        )));
+    }
+    balance.amount = Uint128(balance.amount.u128() - amount.u128());
+    balances.retain(|coin| !coin.amount.is_zero());
+    treasury(&mut deps.storage).save(&balances)?;
+
+    let amount = Coin { denom, amount };
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: recipient.unwrap_or(env.message.sender),
+            amount: vec![amount.clone()],
+        })],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::WithdrawFees { amount })?),
+    })
+}
+
+fn load_admin_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<Config> {
+    let config = config_read(&deps.storage).load()?;
+    if config.admin != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+
+    Ok(config)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => query_config(deps),
+        QueryMsg::Token { address } => query_token(deps, &address),
+        QueryMsg::Tokens {
+            min_height,
+            max_height,
+            verified_only,
+            page,
+            page_size,
+        } => query_tokens(
+            deps,
+            min_height,
+            max_height,
+            verified_only.unwrap_or(false),
+            page.unwrap_or(0),
+            page_size,
+        ),
+        QueryMsg::TokensByCreator {
+            creator,
+            page,
+            page_size,
+        } => query_tokens_by_creator(deps, &creator, page.unwrap_or(0), page_size),
+        QueryMsg::TokensBySymbol {
+            prefix,
+            page,
+            page_size,
+        } => query_tokens_by_symbol(deps, &prefix, page.unwrap_or(0), page_size),
+        QueryMsg::Symbol { symbol } => query_symbol(deps, symbol),
+        QueryMsg::FeeSchedule {} => to_binary(&QueryAnswer::FeeSchedule {
+            fee_schedule: fee_schedule_read(&deps.storage).may_load()?.flatten(),
+        }),
+        QueryMsg::Templates {} => query_templates(deps),
+        QueryMsg::CodeVersions {} => query_code_versions(deps),
+        QueryMsg::Treasury {} => to_binary(&QueryAnswer::Treasury {
+            balances: treasury_read(&deps.storage).may_load()?.unwrap_or_default(),
+        }),
+    }
+}
+
+fn query_config<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config = config_read(&deps.storage).load()?;
+    let latest = load_code_version(&deps.storage, config.latest_version)?;
+
+    to_binary(&QueryAnswer::Config {
+        admin: deps.api.human_address(&config.admin)?,
+        curator: deps.api.human_address(&config.curator)?,
+        snipix_code_id: latest.code_id,
+        snipix_code_hash: latest.code_hash,
+        latest_version: config.latest_version,
+        unique_names: config.unique_names,
+    })
+}
+
+fn query_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let (token_id, token) = load_registered_token(deps, address)?;
+
+    to_binary(&QueryAnswer::Token {
+        token: token_info(deps, token_id, token)?,
+    })
+}
+
+fn query_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    min_height: Option<u64>,
+    max_height: Option<u64>,
+    verified_only: bool,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let count = token_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    // Tokens are numbered in launch order, so a range of heights is a range of ids.
+    let first = match min_height {
+        Some(height) => first_token_from(&deps.storage, count, height)?,
+        None => 1,
+    };
+    let last = match max_height {
+        Some(height) => first_token_from(&deps.storage, count, height.saturating_add(1))? - 1,
+        None => count,
+    };
+
+    if last < first {
+        return to_binary(&QueryAnswer::Tokens {
+            tokens: vec![],
+            total: 0,
+        });
+    }
+
+    let skip = page as u64 * page_size as u64;
+    if verified_only {
+        let start = count_token_ids_below(&deps.storage, VERIFIED_TOKENS_KEY, first)?;
+        let end = count_token_ids_below(&deps.storage, VERIFIED_TOKENS_KEY, last + 1)?;
+        let page_end = (end as u64).saturating_sub(skip).max(start as u64) as u32;
+        let page_start = page_end.saturating_sub(page_size).max(start);
+        let ids = sorted_token_ids(&deps.storage, VERIFIED_TOKENS_KEY, page_start, page_end)?;
+        let tokens = listed_tokens(deps, ids)?;
+
+        return to_binary(&QueryAnswer::Tokens {
+            tokens,
+            total: (end - start) as u64,
+        });
+    }
+
+    let deregistered = count_token_ids_below(&deps.storage, DEREGISTERED_TOKENS_KEY, last + 1)?
+        - count_token_ids_below(&deps.storage, DEREGISTERED_TOKENS_KEY, first)?;
+    let total = last + 1 - first - deregistered as u64;
+    let mut skipped = 0;
+    let mut tokens = vec![];
+    for token_id in (first..=last).rev() {
+        if tokens.len() >= page_size as usize {
+            break;
+        }
+        let token = load_token(&deps.storage, token_id)?;
+        if token.deregistered {
+            continue;
+        }
+        if skipped < skip {
+            skipped += 1;
+            continue;
+        }
+        tokens.push(token_info(deps, token_id, token)?);
+    }
+
+    to_binary(&QueryAnswer::Tokens { tokens, total })
+}
+
+fn query_tokens_by_creator<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    creator: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let creator = deps.api.canonical_address(creator)?;
+    let (ids, total) = page_token_ids(
+        &deps.storage,
+        CREATOR_TOKENS_KEY,
+        creator.as_slice(),
+        page,
+        page_size,
+    )?;
+    let tokens = listed_tokens(deps, ids)?;
+
+    to_binary(&QueryAnswer::Tokens { tokens, total })
+}
+
+fn query_tokens_by_symbol<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    prefix: &str,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    if prefix.is_empty() {
+        return Err(StdError::generic_err("Symbol prefix cannot be empty"));
+    }
+
+    let (ids, total) = page_token_ids(
+        &deps.storage,
+        SYMBOL_PREFIX_TOKENS_KEY,
+        prefix.to_uppercase().as_bytes(),
+        page,
+        page_size,
+    )?;
+    let tokens = listed_tokens(deps, ids)?;
+
+    to_binary(&QueryAnswer::Tokens { tokens, total })
+}
+
+fn query_symbol<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    symbol: String,
+) -> StdResult<Binary> {
+    let key = symbol.to_uppercase();
+    let status = match symbol_tokens_read(&deps.storage).may_load(key.as_bytes())? {
+        Some(token_id) => SymbolStatus::Taken {
+            token: deps
+                .api
+                .human_address(&load_token(&deps.storage, token_id)?.address)?,
+        },
+        None if reserved_symbols_read(&deps.storage)
+            .may_load(key.as_bytes())?
+            .is_some() =>
+        {
+            SymbolStatus::Reserved
+        }
+        None => SymbolStatus::Available,
+    };
+
+    to_binary(&QueryAnswer::Symbol { symbol, status })
+}
+
+fn query_templates<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let templates = template_names_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        .iter()
+        .map(|name| templates_read(&deps.storage).load(name.as_bytes()))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Templates { templates })
+}
+
+fn query_code_versions<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config = config_read(&deps.storage).load()?;
+    let versions = (1..=config.latest_version)
+        .map(|version| load_code_version(&deps.storage, version))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::CodeVersions { versions })
+}
+
+fn listed_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token_ids: Vec<u64>,
+) -> StdResult<Vec<TokenInfo>> {
+    token_ids
+        .into_iter()
+        .map(|token_id| token_info(deps, token_id, load_token(&deps.storage, token_id)?))
+        .collect()
+}
+
+fn token_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token_id: u64,
+    token: Token,
+) -> StdResult<TokenInfo> {
+    let token_address = |token_id: Option<u64>| {
+        token_id
+            .map(|token_id| {
+                deps.api
+                    .human_address(&load_token(&deps.storage, token_id)?.address)
+            })
+            .transpose()
+    };
+
+    Ok(TokenInfo {
+        id: token_id,
+        address: deps.api.human_address(&token.address)?,
+        code_id: token.code_id,
+        code_hash: token.code_hash,
+        code_version: token.code_version,
+        creator: deps.api.human_address(&token.creator)?,
+        name: token.name,
+        symbol: token.symbol,
+        decimals: token.decimals,
+        template: token.template,
+        marketing_info: token.marketing_info,
+        created_at: token.created_at,
+        verification: token.verification,
+        migrated_from: token_address(token.migrated_from)?,
+        migrated_to: token_address(token.migrated_to)?,
+    })
+}
+
+fn load_token<S: Storage>(storage: &S, token_id: u64) -> StdResult<Token> {
+    tokens_read(storage)
+        .may_load(&token_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Token not found"))
+}
+
+/// Loads the token launched at `address`, unless it was deregistered. Migrated tokens still
+/// resolve, to point to their next version.
+fn load_registered_token<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<(u64, Token)> {
+    let address = deps.api.canonical_address(address)?;
+    let token_id = token_ids_read(&deps.storage)
+        .may_load(address.as_slice())?
+        .ok_or_else(|| StdError::generic_err("Token not found"))?;
+    let token = load_token(&deps.storage, token_id)?;
+    if token.deregistered && token.migrated_to.is_none() {
+        return Err(StdError::generic_err("Token not found"));
+    }
+
+    Ok((token_id, token))
+}
+
+fn load_code_version<S: Storage>(storage: &S, version: u32) -> StdResult<CodeVersion> {
+    code_versions_read(storage)
+        .may_load(&version.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("Code version {} not found", version)))
+}
+
+/// Loads the code version a token is launched with, the latest unless `version` is set.
+fn load_launch_version<S: Storage>(
+    storage: &S,
+    config: &Config,
+    version: Option<u32>,
+) -> StdResult<CodeVersion> {
+    let code_version = load_code_version(storage, version.unwrap_or(config.latest_version))?;
+    if code_version.deprecated {
+        return Err(StdError::generic_err(format!(
+            "Code version {} is deprecated",
+            code_version.version
+        )));
+    }
+
+    Ok(code_version)
+}
+
+/// Instantiates a token with `version`, having it call back `RegisterToken` once created.
+fn instantiate_token_msg(
+    env: &Env,
+    version: &CodeVersion,
+    token_id: u64,
+    mut token_msg: snipix::msg::InitMsg,
+) -> StdResult<CosmosMsg> {
+    let label = format!(
+        "{} #{} ({})",
+        token_msg.symbol, token_id, env.contract.address
+    );
+    token_msg.init_callback = Some(InitCallback {
+        msg: to_binary(&HandleMsg::RegisterToken {})?,
+        contract_addr: env.contract.address.clone(),
+        code_hash: env.contract_code_hash.clone(),
+    });
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Instantiate {
+        code_id: version.code_id,
+        callback_code_hash: version.code_hash.clone(),
+        msg: to_binary(&token_msg)?,
+        send: vec![],
+        label,
+    }))
+}
+
+fn validate_fee_schedule(schedule: &FeeSchedule) -> StdResult<()> {
+    if schedule
+        .template_discounts
+        .iter()
+        .any(|discount| discount.discount > Decimal::one())
+    {
+        return Err(StdError::generic_err("Discounts cannot be greater than 1"));
+    }
+
+    Ok(())
+}
+
+/// Adds `template`, or replaces the one with the same name.
+fn save_template<S: Storage>(storage: &mut S, template: Template) -> StdResult<()> {
+    if template.name.is_empty() {
+        return Err(StdError::generic_err("Template name cannot be empty"));
+    }
+
+    let mut names = template_names_read(&*storage)
+        .may_load()?
+        .unwrap_or_default();
+    if !names.contains(&template.name) {
+        names.push(template.name.clone());
+        template_names(storage).save(&names)?;
+    }
+    templates(storage).save(template.name.as_bytes(), &template)
+}
+
+/// Builds the init message of a token launched from `template`, failing on overrides the
+/// template's rules do not allow.
+fn apply_template(
+    template: &Template,
+    name: String,
+    symbol: String,
+    overrides: TokenOverrides,
+    prng_seed: Binary,
+) -> StdResult<snipix::msg::InitMsg> {
+    let rules = &template.rules;
+    let not_overridable = |setting: &str| {
+        StdError::generic_err(format!(
+            "Template {} does not allow overriding {}",
+            template.name, setting
+        ))
+    };
+    if overrides.decimals.is_some() && !rules.decimals_overridable {
+        return Err(not_overridable("decimals"));
+    }
+    if overrides.config.is_some() && !rules.config_overridable {
+        return Err(not_overridable("config"));
+    }
+    if (overrides.supply_cap.is_some()
+        || overrides.transfer_fee.is_some()
+        || overrides.vesting_period.is_some())
+        && !rules.extensions_overridable
+    {
+        return Err(not_overridable("extensions"));
+    }
+    if let Some(max_initial_supply) = rules.max_initial_supply {
+        let initial_supply: u128 = overrides
+            .initial_balances
+            .iter()
+            .flatten()
+            .map(|balance| balance.amount.u128())
+            .sum();
+        if initial_supply > max_initial_supply.u128() {
+            return Err(StdError::generic_err(format!(
+                "Initial supply cannot exceed {} with template {}",
+                max_initial_supply, template.name
+            )));
+        }
+    }
+
+    Ok(snipix::msg::InitMsg {
+        name,
+        admin: overrides.admin,
+        symbol,
+        decimals: overrides.decimals.unwrap_or(template.decimals),
+        initial_balances: overrides.initial_balances,
+        prng_seed,
+        config: overrides.config.or_else(|| template.config.clone()),
+        marketing_info: overrides.marketing_info,
+        governance: template.extensions.governance.clone(),
+        reward_tokens: None,
+        supply_cap: overrides.supply_cap.or(template.extensions.supply_cap),
+        transfer_fee: overrides
+            .transfer_fee
+            .or_else(|| template.extensions.transfer_fee.clone()),
+        vesting_period: overrides
+            .vesting_period
+            .or(template.extensions.vesting_period),
+        migrated_from: None,
+        init_callback: None,
+    })
+}
+
+/// Derives the seed of a token from the factory's, so that no two tokens share one.
+fn launch_seed(config: &Config, env: &Env, token_id: u64) -> Binary {
+    let mut hasher = Sha256::new();
+    hasher.update(&config.prng_seed);
+    hasher.update(&token_id.to_be_bytes());
+    hasher.update(env.message.sender.as_str().as_bytes());
+
+    Binary::from(hasher.finalize().as_slice())
+}
+
+/// Moves the launch fee for `template` from the sent funds to the treasury, and returns what is
+/// left of them.
+fn charge_launch_fee<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    template: Option<&str>,
+) -> StdResult<Vec<Coin>> {
+    let mut funds: Vec<Coin> = env
+        .message
+        .sent_funds
+        .iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .cloned()
+        .collect();
+    let schedule = match fee_schedule_read(&*storage).may_load()?.flatten() {
+        Some(schedule) => schedule,
+        None => return Ok(funds),
+    };
+
+    let discount = schedule
+        .template_discounts
+        .iter()
+        .find(|discount| Some(discount.template.as_str()) == template)
+        .map_or(Decimal::zero(), |discount| discount.discount);
+    let waived = schedule.fee.amount * discount;
+    let fee = Coin {
+        denom: schedule.fee.denom,
+        amount: Uint128(schedule.fee.amount.u128() - waived.u128()),
+    };
+    if fee.amount.is_zero() {
+        return Ok(funds);
+    }
+
+    match funds.iter_mut().find(|coin| coin.denom == fee.denom) {
+        Some(paid) if paid.amount >= fee.amount => {
+            paid.amount = Uint128(paid.amount.u128() - fee.amount.u128())
+        }
+        _ => {
+            return Err(StdError::generic_err(format!(
+                "A launch fee of {}{} is required",
+                fee.amount, fee.denom
+            )))
+        }
+    }
+    funds.retain(|coin| !coin.amount.is_zero());
+
+    let mut balances = treasury_read(&*storage).may_load()?.unwrap_or_default();
+    match balances.iter_mut().find(|coin| coin.denom == fee.denom) {
+        Some(balance) => balance.amount = Uint128(balance.amount.u128() + fee.amount.u128()),
+        None => balances.push(fee),
+    }
+    treasury(storage).save(&balances)?;
+
+    Ok(funds)
+}
+
+/// Fails if `symbol`, or `name` when names have to be unique, is taken, or if `symbol` is reserved
+/// and `creator` is not the admin.
+fn check_availability<S: Storage>(
+    storage: &S,
+    config: &Config,
+    creator: &CanonicalAddr,
+    name: &str,
+    symbol: &str,
+) -> StdResult<()> {
+    let key = symbol.to_uppercase();
+    if symbol_tokens_read(storage)
+        .may_load(key.as_bytes())?
+        .is_some()
+    {
+        return Err(StdError::generic_err(format!(
+            "Symbol {} is already taken",
+            symbol
+        )));
+    }
+    if creator != &config.admin
+        && reserved_symbols_read(storage)
+            .may_load(key.as_bytes())?
+            .is_some()
+    {
+        return Err(StdError::generic_err(format!(
+            "Symbol {} is reserved",
+            symbol
+        )));
+    }
+    if config.unique_names
+        && name_tokens_read(storage)
+            .may_load(name.to_lowercase().as_bytes())?
+            .is_some()
+    {
+        return Err(StdError::generic_err(format!(
+            "Name {} is already taken",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Drops the token from its creator's list and from the lists of its symbol prefixes.
+fn unlist_token<S: Storage>(storage: &mut S, token_id: u64, token: &Token) -> StdResult<()> {
+    if is_verified(token) {
+        remove_sorted_token_id(storage, VERIFIED_TOKENS_KEY, token_id)?;
+    }
+    insert_sorted_token_id(storage, DEREGISTERED_TOKENS_KEY, token_id)?;
+
+    let creator = token.creator.as_slice();
+    if let Some(moved) = remove_token_id(storage, CREATOR_TOKENS_KEY, creator, token.creator_index)?
+    {
+        let mut moved_token = load_token(&*storage, moved)?;
+        moved_token.creator_index = token.creator_index;
+        tokens(storage).save(&moved.to_be_bytes(), &moved_token)?;
+    }
+
+    let prefixes = symbol_prefixes(&token.symbol);
+    for (position, (prefix, &index)) in prefixes.iter().zip(&token.symbol_indexes).enumerate() {
+        if let Some(moved) =
+            remove_token_id(storage, SYMBOL_PREFIX_TOKENS_KEY, prefix.as_bytes(), index)?
+        {
+            // Tokens sharing a prefix list it at the same position among their own prefixes.
+            let mut moved_token = load_token(&*storage, moved)?;
+            moved_token.symbol_indexes[position] = index;
+            tokens(storage).save(&moved.to_be_bytes(), &moved_token)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_verified(token: &Token) -> bool {
+    matches!(
+        &token.verification,
+        Some(verification) if verification.status == VerificationStatus::Verified
+    )
+}
+
+/// Returns the id of the first token launched at or after `height`, or `count + 1` if there is
+/// none.
+fn first_token_from<S: Storage>(storage: &S, count: u64, height: u64) -> StdResult<u64> {
+    let (mut low, mut high) = (1, count + 1);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if load_token(storage, mid)?.created_at < height {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
+
+/// The keys a token is listed under for symbol searches: every prefix of its upper-cased symbol.
+fn symbol_prefixes(symbol: &str) -> Vec<String> {
+    let symbol = symbol.to_uppercase();
+    symbol
+        .char_indices()
+        .map(|(index, c)| symbol[..index + c.len_utf8()].to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{TemplateDiscount, TemplateExtensions, TemplateRules};
+    use atl_snip20_reference_impl::msg::InitialBalance;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, coins, from_binary};
+    use snipix::msg::{MarketingInfo, TransferFee};
+
+    fn init_factory() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                curator: None,
+                snipix_code_id: 7,
+                snipix_code_hash: "snipix_hash".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                unique_names: None,
+                reserved_symbols: None,
+                fee_schedule: None,
+                templates: Some(vec![meme_template()]),
+            },
+        )
+        .unwrap();
+
+        deps
+    }
+
+    fn meme_template() -> Template {
+        Template {
+            name: "meme token".to_string(),
+            description: Some("Fixed supply, burnable".to_string()),
+            decimals: 6,
+            config: Some(
+                from_binary(&Binary::from(
+                    r#"{ "public_total_supply": true, "enable_burn": true }"#.as_bytes(),
+                ))
+                .unwrap(),
+            ),
+            extensions: TemplateExtensions {
+                supply_cap: None,
+                transfer_fee: None,
+                vesting_period: None,
+                governance: None,
+            },
+            rules: TemplateRules {
+                max_initial_supply: None,
+                decimals_overridable: false,
+                config_overridable: false,
+                extensions_overridable: false,
+            },
+        }
+    }
+
+    fn create_token_msg(symbol: &str) -> HandleMsg {
+        HandleMsg::CreateToken {
+            name: format!("{} token", symbol),
+            symbol: symbol.to_string(),
+            template: "meme token".to_string(),
+            code_version: None,
+            overrides: Some(TokenOverrides {
+                initial_balances: Some(vec![InitialBalance {
+                    address: HumanAddr("alice".to_string()),
+                    amount: Uint128(1000),
+                }]),
+                marketing_info: Some(MarketingInfo {
+                    project: Some("Atomik Labs".to_string()),
+                    description: None,
+                    marketing: None,
+                    logo: None,
+                }),
+                ..TokenOverrides::default()
+            }),
+            padding: None,
+        }
+    }
+
+    /// Returns the init message of the token instantiated by `response`.
+    fn instantiated_token_msg(response: &HandleResponse) -> snipix::msg::InitMsg {
+        match &response.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Instantiate { msg, .. }) => from_binary(msg).unwrap(),
+            other => panic!("Unexpected message {:?}", other),
+        }
+    }
+
+    /// Creates a token and runs the init callback it would send from `address`.
+    fn launch(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        creator: &str,
+        symbol: &str,
+        address: &str,
+        height: u64,
+    ) {
+        let mut env = mock_env(creator, &[]);
+        env.block.height = height;
+        handle(deps, env, create_token_msg(symbol)).unwrap();
+
+        let mut env = mock_env(address, &[]);
+        env.block.height = height;
+        handle(deps, env, HandleMsg::RegisterToken {}).unwrap();
+    }
+
+    fn token_list(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        msg: QueryMsg,
+    ) -> Vec<TokenInfo> {
+        match from_binary(&query(deps, msg).unwrap()).unwrap() {
+            QueryAnswer::Tokens { tokens, .. } => tokens,
+            other => panic!("Unexpected answer {:?}", other),
+        }
+    }
+
+    fn tokens_by_creator(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        creator: &str,
+    ) -> Vec<TokenInfo> {
+        token_list(
+            deps,
+            QueryMsg::TokensByCreator {
+                creator: HumanAddr(creator.to_string()),
+                page: None,
+                page_size: 10,
+            },
+        )
+    }
+
+    fn addresses(tokens: &[TokenInfo]) -> Vec<&str> {
+        tokens.iter().map(|token| token.address.as_str()).collect()
+    }
+
+    #[test]
+    fn test_create_and_register_token() {
+        let mut deps = init_factory();
+
+        let result = handle(&mut deps, mock_env("alice", &[]), create_token_msg("LNCH")).unwrap();
+        let token_msg: snipix::msg::InitMsg = match &result.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id,
+                callback_code_hash,
+                msg,
+                ..
+            })] => {
+                assert_eq!(*code_id, 7);
+                assert_eq!(callback_code_hash, "snipix_hash");
+                from_binary(msg).unwrap()
+            }
+            other => panic!("Unexpected messages {:?}", other),
+        };
+        assert_eq!(token_msg.admin, Some(HumanAddr("alice".to_string())));
+        assert_eq!(
+            token_msg.marketing_info.unwrap().project,
+            Some("Atomik Labs".to_string())
+        );
+        let callback = token_msg.init_callback.unwrap();
+        assert_eq!(
+            callback.contract_addr,
+            mock_env("alice", &[]).contract.address
+        );
+
+        // The token calls back once instantiated.
+        handle(
+            &mut deps,
+            mock_env("token", &[]),
+            from_binary(&callback.msg).unwrap(),
+        )
+        .unwrap();
+        let tokens = tokens_by_creator(&deps, "alice");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].address, HumanAddr("token".to_string()));
+        assert_eq!(tokens[0].symbol, "LNCH");
+        assert_eq!(tokens[0].code_hash, "snipix_hash");
+        assert!(tokens_by_creator(&deps, "bob").is_empty());
+
+        let answer = query(
+            &deps,
+            QueryMsg::Token {
+                address: HumanAddr("token".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<QueryAnswer>(&answer).unwrap(),
+            QueryAnswer::Token {
+                token: tokens[0].clone()
+            }
+        );
+
+        // Nothing is pending anymore, so a second callback is rejected.
+        let result = handle(
+            &mut deps,
+            mock_env("impostor", &[]),
+            HandleMsg::RegisterToken {},
+        );
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "No token launch is pending"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_registry_search() {
+        let mut deps = init_factory();
+        launch(&mut deps, "alice", "SNIP", "snip_token", 100);
+        launch(&mut deps, "bob", "SNAP", "snap_token", 200);
+        launch(&mut deps, "alice", "SCRT", "scrt_token", 300);
+
+        let by_height = |min_height, max_height| QueryMsg::Tokens {
+            min_height,
+            max_height,
+            verified_only: None,
+            page: None,
+            page_size: 10,
+        };
+        assert_eq!(
+            addresses(&token_list(&deps, by_height(None, None))),
+            vec!["scrt_token", "snap_token", "snip_token"]
+        );
+        assert_eq!(
+            addresses(&token_list(&deps, by_height(Some(150), Some(300)))),
+            vec!["scrt_token", "snap_token"]
+        );
+        assert_eq!(
+            addresses(&token_list(&deps, by_height(Some(100), Some(199)))),
+            vec!["snip_token"]
+        );
+        assert!(token_list(&deps, by_height(Some(301), None)).is_empty());
+
+        let by_symbol = |prefix: &str| QueryMsg::TokensBySymbol {
+            prefix: prefix.to_string(),
+            page: None,
+            page_size: 10,
+        };
+        assert_eq!(
+            addresses(&token_list(&deps, by_symbol("sn"))),
+            vec!["snap_token", "snip_token"]
+        );
+        assert_eq!(
+            addresses(&token_list(&deps, by_symbol("SCRT"))),
+            vec!["scrt_token"]
+        );
+        assert!(token_list(&deps, by_symbol("SCRTX")).is_empty());
+
+        // Tokens refresh their own marketing info snapshot, and only theirs.
+        let update = HandleMsg::UpdateMarketingInfo {
+            marketing_info: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), update.clone()).is_err());
+        handle(&mut deps, mock_env("snap_token", &[]), update).unwrap();
+        let tokens = tokens_by_creator(&deps, "bob");
+        assert_eq!(tokens[0].marketing_info, None);
+        assert_eq!(tokens[0].decimals, 6);
+        assert!(tokens_by_creator(&deps, "alice")
+            .iter()
+            .all(|token| token.marketing_info.is_some()));
+    }
+
+    #[test]
+    fn test_unique_and_reserved_symbols() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                curator: None,
+                snipix_code_id: 7,
+                snipix_code_hash: "snipix_hash".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                unique_names: Some(true),
+                reserved_symbols: Some(vec!["scrt".to_string()]),
+                fee_schedule: None,
+                templates: Some(vec![meme_template()]),
+            },
+        )
+        .unwrap();
+        launch(&mut deps, "alice", "SNIP", "snip_token", 100);
+        launch(&mut deps, "alice", "SNIPS", "snips_token", 101);
+
+        let assert_rejected = |result: StdResult<HandleResponse>, expected: &str| match result {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, expected),
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_rejected(
+            handle(&mut deps, mock_env("bob", &[]), create_token_msg("snip")),
+            "Symbol snip is already taken",
+        );
+        assert_rejected(
+            handle(&mut deps, mock_env("bob", &[]), create_token_msg("SCRT")),
+            "Symbol SCRT is reserved",
+        );
+        let mut copycat = create_token_msg("SNAP");
+        if let HandleMsg::CreateToken { name, .. } = &mut copycat {
+            *name = "snip TOKEN".to_string();
+        }
+        assert_rejected(
+            handle(&mut deps, mock_env("bob", &[]), copycat),
+            "Name snip TOKEN is already taken",
+        );
+
+        let symbol = |deps: &Extern<MockStorage, MockApi, MockQuerier>, symbol: &str| {
+            let answer = query(
+                deps,
+                QueryMsg::Symbol {
+                    symbol: symbol.to_string(),
+                },
+            )
+            .unwrap();
+            match from_binary(&answer).unwrap() {
+                QueryAnswer::Symbol { status, .. } => status,
+                other => panic!("Unexpected answer {:?}", other),
+            }
+        };
+        assert_eq!(symbol(&deps, "Scrt"), SymbolStatus::Reserved);
+        assert_eq!(
+            symbol(&deps, "snip"),
+            SymbolStatus::Taken {
+                token: HumanAddr("snip_token".to_string())
+            }
+        );
+
+        // Deregistering releases the symbol and drops the token from every listing.
+        let deregister = HandleMsg::DeregisterToken {
+            address: HumanAddr("snip_token".to_string()),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("bob", &[]), deregister.clone()).is_err());
+        handle(&mut deps, mock_env("alice", &[]), deregister.clone()).unwrap();
+        assert_eq!(symbol(&deps, "SNIP"), SymbolStatus::Available);
+        assert!(query(
+            &deps,
+            QueryMsg::Token {
+                address: HumanAddr("snip_token".to_string()),
+            },
+        )
+        .is_err());
+        assert!(handle(&mut deps, mock_env("alice", &[]), deregister).is_err());
+
+        // The token itself keeps working, so its marketing info updates are accepted and ignored.
+        handle(
+            &mut deps,
+            mock_env("snip_token", &[]),
+            HandleMsg::UpdateMarketingInfo {
+                marketing_info: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            addresses(&tokens_by_creator(&deps, "alice")),
+            vec!["snips_token"]
+        );
+        assert_eq!(
+            addresses(&token_list(
+                &deps,
+                QueryMsg::TokensBySymbol {
+                    prefix: "SNI".to_string(),
+                    page: None,
+                    page_size: 10,
+                }
+            )),
+            vec!["snips_token"]
+        );
+        assert_eq!(
+            addresses(&token_list(
+                &deps,
+                QueryMsg::Tokens {
+                    min_height: None,
+                    max_height: None,
+                    verified_only: None,
+                    page: None,
+                    page_size: 10,
+                }
+            )),
+            vec!["snips_token"]
+        );
+
+        // Reserved symbols are still open to the admin, and freed ones to anyone.
+        launch(&mut deps, "admin", "SCRT", "scrt_token", 102);
+        launch(&mut deps, "bob", "SNIP", "new_snip_token", 103);
+        assert_eq!(
+            symbol(&deps, "snip"),
+            SymbolStatus::Taken {
+                token: HumanAddr("new_snip_token".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_launch_fees() {
+        let mut deps = mock_dependencies(20, &[]);
+        let schedule = FeeSchedule {
+            fee: coin(100, "uscrt"),
+            template_discounts: vec![],
+        };
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                curator: None,
+                snipix_code_id: 7,
+                snipix_code_hash: "snipix_hash".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                unique_names: None,
+                reserved_symbols: None,
+                fee_schedule: Some(schedule.clone()),
+                templates: Some(vec![meme_template()]),
+            },
+        )
+        .unwrap();
+
+        let result = handle(
+            &mut deps,
+            mock_env("alice", &coins(99, "uscrt")),
+            create_token_msg("SNIP"),
+        );
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "A launch fee of 100uscrt is required")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        // Overpayments, in the fee denom or any other, are refunded.
+        let funds = vec![coin(150, "uscrt"), coin(5, "uatom")];
+        let result = handle(
+            &mut deps,
+            mock_env("alice", &funds),
+            create_token_msg("SNIP"),
+        )
+        .unwrap();
+        assert_eq!(
+            result.messages[1],
+            CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env("alice", &[]).contract.address,
+                to_address: HumanAddr("alice".to_string()),
+                amount: vec![coin(50, "uscrt"), coin(5, "uatom")],
+            })
+        );
+
+        let treasury = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(
+            &query(deps, QueryMsg::Treasury {}).unwrap(),
+        )
+        .unwrap()
+        {
+            QueryAnswer::Treasury { balances } => balances,
+            other => panic!("Unexpected answer {:?}", other),
+        };
+        assert_eq!(treasury(&deps), vec![coin(100, "uscrt")]);
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, QueryMsg::FeeSchedule {}).unwrap()).unwrap(),
+            QueryAnswer::FeeSchedule {
+                fee_schedule: Some(schedule)
+            }
+        );
+
+        let withdraw = HandleMsg::WithdrawFees {
+            denom: "uscrt".to_string(),
+            amount: Some(Uint128(60)),
+            recipient: Some(HumanAddr("treasurer".to_string())),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), withdraw.clone()).is_err());
+        let result = handle(&mut deps, mock_env("admin", &[]), withdraw).unwrap();
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env("admin", &[]).contract.address,
+                to_address: HumanAddr("treasurer".to_string()),
+                amount: vec![coin(60, "uscrt")],
+            })]
+        );
+        assert_eq!(treasury(&deps), vec![coin(40, "uscrt")]);
+    }
+
+    #[test]
+    fn test_code_versions_and_migration() {
+        let mut deps = init_factory();
+        launch(&mut deps, "alice", "SNIP", "snip_token", 100);
+
+        let add_version = HandleMsg::AddCodeVersion {
+            code_id: 8,
+            code_hash: "new_hash".to_string(),
+            changelog: "Adds migrations".to_string(),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), add_version.clone()).is_err());
+        handle(&mut deps, mock_env("admin", &[]), add_version).unwrap();
+
+        let answer = query(&deps, QueryMsg::Config {}).unwrap();
+        assert_eq!(
+            from_binary::<QueryAnswer>(&answer).unwrap(),
+            QueryAnswer::Config {
+                admin: HumanAddr("admin".to_string()),
+                curator: HumanAddr("admin".to_string()),
+                snipix_code_id: 8,
+                snipix_code_hash: "new_hash".to_string(),
+                latest_version: 2,
+                unique_names: false,
+            }
+        );
+
+        let deprecate = |version| HandleMsg::DeprecateCodeVersion {
+            version,
+            padding: None,
+        };
+        let assert_rejected = |result: StdResult<HandleResponse>, expected: &str| match result {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, expected),
+            other => panic!("Unexpected result {:?}", other),
+        };
+        assert_rejected(
+            handle(&mut deps, mock_env("admin", &[]), deprecate(2)),
+            "The latest code version cannot be deprecated",
+        );
+        handle(&mut deps, mock_env("admin", &[]), deprecate(1)).unwrap();
+        let mut old_version = create_token_msg("SNAP");
+        if let HandleMsg::CreateToken { code_version, .. } = &mut old_version {
+            *code_version = Some(1);
+        }
+        assert_rejected(
+            handle(&mut deps, mock_env("bob", &[]), old_version),
+            "Code version 1 is deprecated",
+        );
+        match from_binary(&query(&deps, QueryMsg::CodeVersions {}).unwrap()).unwrap() {
+            QueryAnswer::CodeVersions { versions } => {
+                assert_eq!(versions.len(), 2);
+                assert!(versions[0].deprecated);
+                assert_eq!(versions[1].changelog, "Adds migrations");
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        // The token asks for a new instance, which replaces it once registered.
+        let migrate = HandleMsg::MigrateToken {
+            code_version: None,
+            init_msg: Box::new(snipix::msg::InitMsg {
+                name: "SNIP token".to_string(),
+                admin: Some(HumanAddr("alice".to_string())),
+                symbol: "SNIP".to_string(),
+                decimals: 6,
+                initial_balances: None,
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                config: None,
+                marketing_info: None,
+                governance: None,
+                reward_tokens: None,
+                supply_cap: None,
+                transfer_fee: None,
+                vesting_period: None,
+                migrated_from: None,
+                init_callback: None,
+            }),
+        };
+        assert_rejected(
+            handle(&mut deps, mock_env("alice", &[]), migrate.clone()),
+            "Only launched tokens can migrate",
+        );
+        let response = handle(&mut deps, mock_env("snip_token", &[]), migrate.clone()).unwrap();
+        let token_msg = instantiated_token_msg(&response);
+        assert_eq!(
+            token_msg.migrated_from,
+            Some(MigrationSource {
+                address: HumanAddr("snip_token".to_string()),
+                code_hash: "snipix_hash".to_string(),
+            })
+        );
+        assert_ne!(token_msg.prng_seed, Binary::from("lolz fun yay".as_bytes()));
+        assert!(token_msg.init_callback.is_some());
+        let response = handle(
+            &mut deps,
+            mock_env("snip_v2_token", &[]),
+            HandleMsg::RegisterToken {},
+        )
+        .unwrap();
+        match &response.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            })] => {
+                assert_eq!(contract_addr, &HumanAddr("snip_token".to_string()));
+                assert_eq!(
+                    from_binary::<snipix::msg::HandleMsg>(msg).unwrap(),
+                    snipix::msg::HandleMsg::CompleteMigration {
+                        successor: MigrationSource {
+                            address: HumanAddr("snip_v2_token".to_string()),
+                            code_hash: "new_hash".to_string(),
+                        },
+                        padding: None,
+                    }
+                );
+            }
+            other => panic!("Unexpected messages {:?}", other),
+        }
+
+        let token = |address: &str| {
+            let answer = query(
+                &deps,
+                QueryMsg::Token {
+                    address: HumanAddr(address.to_string()),
+                },
+            )
+            .unwrap();
+            match from_binary(&answer).unwrap() {
+                QueryAnswer::Token { token } => token,
+                other => panic!("Unexpected answer {:?}", other),
+            }
+        };
+        let new_token = token("snip_v2_token");
+        assert_eq!(new_token.code_version, 2);
+        assert_eq!(new_token.code_hash, "new_hash");
+        assert_eq!(
+            new_token.migrated_from,
+            Some(HumanAddr("snip_token".to_string()))
+        );
+        assert_eq!(
+            token("snip_token").migrated_to,
+            Some(HumanAddr("snip_v2_token".to_string()))
+        );
+        assert_eq!(
+            addresses(&tokens_by_creator(&deps, "alice")),
+            vec!["snip_v2_token"]
+        );
+
+        assert_rejected(
+            handle(&mut deps, mock_env("snip_token", &[]), migrate.clone()),
+            "Token was already migrated",
+        );
+        assert_rejected(
+            handle(&mut deps, mock_env("snip_v2_token", &[]), migrate),
+            "Tokens can only migrate to a newer code version",
+        );
+    }
+
+    #[test]
+    fn test_launch_templates() {
+        let mut deps = mock_dependencies(20, &[]);
+        let stablecoin = Template {
+            name: "stablecoin".to_string(),
+            description: None,
+            decimals: 6,
+            config: Some(
+                from_binary(&Binary::from(
+                    r#"{ "enable_mint": true, "enable_burn": true }"#.as_bytes(),
+                ))
+                .unwrap(),
+            ),
+            extensions: TemplateExtensions {
+                supply_cap: Some(Uint128(1_000_000)),
+                transfer_fee: Some(TransferFee {
+                    rate: Decimal::permille(5),
+                    collector: HumanAddr("treasury".to_string()),
+                }),
+                vesting_period: Some(100),
+                governance: None,
+            },
+            rules: TemplateRules {
+                max_initial_supply: Some(Uint128(1000)),
+                decimals_overridable: false,
+                config_overridable: false,
+                extensions_overridable: true,
+            },
+        };
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                curator: None,
+                snipix_code_id: 7,
+                snipix_code_hash: "snipix_hash".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                unique_names: None,
+                reserved_symbols: None,
+                fee_schedule: Some(FeeSchedule {
+                    fee: coin(100, "uscrt"),
+                    template_discounts: vec![TemplateDiscount {
+                        template: "stablecoin".to_string(),
+                        discount: Decimal::percent(50),
+                    }],
+                }),
+                templates: Some(vec![meme_template(), stablecoin.clone()]),
+            },
+        )
+        .unwrap();
+
+        let create_stablecoin = |overrides: TokenOverrides| HandleMsg::CreateToken {
+            name: "Secret dollar".to_string(),
+            symbol: "SUSD".to_string(),
+            template: "stablecoin".to_string(),
+            code_version: None,
+            overrides: Some(overrides),
+            padding: None,
+        };
+        let initial_balances = |amount: u128| {
+            Some(vec![InitialBalance {
+                address: HumanAddr("alice".to_string()),
+                amount: Uint128(amount),
+            }])
+        };
+        let assert_rejected = |result: StdResult<HandleResponse>, expected: &str| match result {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, expected),
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let mut unknown = create_token_msg("SUSD");
+        if let HandleMsg::CreateToken { template, .. } = &mut unknown {
+            *template = "unknown".to_string();
+        }
+        assert_rejected(
+            handle(&mut deps, mock_env("alice", &coins(100, "uscrt")), unknown),
+            "Template unknown not found",
+        );
+        assert_rejected(
+            handle(
+                &mut deps,
+                mock_env("alice", &coins(50, "uscrt")),
+                create_stablecoin(TokenOverrides {
+                    decimals: Some(18),
+                    ..TokenOverrides::default()
+                }),
+            ),
+            "Template stablecoin does not allow overriding decimals",
+        );
+        assert_rejected(
+            handle(
+                &mut deps,
+                mock_env("alice", &coins(50, "uscrt")),
+                create_stablecoin(TokenOverrides {
+                    initial_balances: initial_balances(1001),
+                    ..TokenOverrides::default()
+                }),
+            ),
+            "Initial supply cannot exceed 1000 with template stablecoin",
+        );
+
+        // The template fills in everything the creator leaves out, at its discounted fee.
+        let response = handle(
+            &mut deps,
+            mock_env("alice", &coins(50, "uscrt")),
+            create_stablecoin(TokenOverrides {
+                initial_balances: initial_balances(1000),
+                supply_cap: Some(Uint128(5000)),
+                ..TokenOverrides::default()
+            }),
+        )
+        .unwrap();
+        let token_msg = instantiated_token_msg(&response);
+        assert_eq!(token_msg.decimals, 6);
+        assert_eq!(token_msg.config, stablecoin.config);
+        assert_eq!(token_msg.supply_cap, Some(Uint128(5000)));
+        assert_eq!(token_msg.transfer_fee, stablecoin.extensions.transfer_fee);
+        assert_eq!(token_msg.vesting_period, Some(100));
+        assert_eq!(token_msg.admin, Some(HumanAddr("alice".to_string())));
+        assert!(!token_msg.prng_seed.as_slice().is_empty());
+
+        handle(
+            &mut deps,
+            mock_env("susd_token", &[]),
+            HandleMsg::RegisterToken {},
+        )
+        .unwrap();
+        assert_eq!(tokens_by_creator(&deps, "alice")[0].template, "stablecoin");
+
+        let templates = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(
+            &query(deps, QueryMsg::Templates {}).unwrap(),
+        )
+        .unwrap()
+        {
+            QueryAnswer::Templates { templates } => templates,
+            other => panic!("Unexpected answer {:?}", other),
+        };
+        assert_eq!(templates(&deps), vec![meme_template(), stablecoin]);
+
+        let remove = HandleMsg::RemoveTemplate {
+            name: "stablecoin".to_string(),
+            padding: None,
+        };
+        assert!(handle(&mut deps, mock_env("alice", &[]), remove.clone()).is_err());
+        handle(&mut deps, mock_env("admin", &[]), remove).unwrap();
+        assert_eq!(templates(&deps), vec![meme_template()]);
+    }
+
+    #[test]
+    fn test_verification_badges() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                curator: Some(HumanAddr("curator".to_string())),
+                snipix_code_id: 7,
+                snipix_code_hash: "snipix_hash".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                unique_names: None,
+                reserved_symbols: None,
+                fee_schedule: None,
+                templates: Some(vec![meme_template()]),
+            },
+        )
+        .unwrap();
+        launch(&mut deps, "alice", "SNIP", "snip_token", 100);
+        launch(&mut deps, "bob", "SNAP", "snap_token", 200);
+        launch(&mut deps, "bob", "SCRT", "scrt_token", 300);
+
+        let badge = |address: &str, status, reason: &str| HandleMsg::SetVerification {
+            address: HumanAddr(address.to_string()),
+            status,
+            reason: reason.to_string(),
+            padding: None,
+        };
+        assert!(handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            badge("snip_token", VerificationStatus::Verified, "Trust me"),
+        )
+        .is_err());
+        handle(
+            &mut deps,
+            mock_env("curator", &[]),
+            badge("snip_token", VerificationStatus::Verified, "Audited"),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("curator", &[]),
+            badge("snap_token", VerificationStatus::Flagged, "Copies SNIP"),
+        )
+        .unwrap();
+
+        let verified = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            token_list(
+                deps,
+                QueryMsg::Tokens {
+                    min_height: None,
+                    max_height: None,
+                    verified_only: Some(true),
+                    page: None,
+                    page_size: 10,
+                },
+            )
+        };
+        assert_eq!(addresses(&verified(&deps)), vec!["snip_token"]);
+        let flagged = tokens_by_creator(&deps, "bob")
+            .into_iter()
+            .find(|token| token.symbol == "SNAP")
+            .unwrap();
+        assert_eq!(
+            flagged.verification,
+            Some(Verification {
+                status: VerificationStatus::Flagged,
+                reason: "Copies SNIP".to_string(),
+                updated_at: mock_env("curator", &[]).block.height,
+            })
+        );
+
+        // Verified tokens are paged from their own index, most recent first.
+        handle(
+            &mut deps,
+            mock_env("curator", &[]),
+            badge("scrt_token", VerificationStatus::Verified, "Audited"),
+        )
+        .unwrap();
+        let verified_page = |deps: &Extern<MockStorage, MockApi, MockQuerier>, min_height, page| {
+            let msg = QueryMsg::Tokens {
+                min_height,
+                max_height: None,
+                verified_only: Some(true),
+                page: Some(page),
+                page_size: 1,
+            };
+            match from_binary(&query(deps, msg).unwrap()).unwrap() {
+                QueryAnswer::Tokens { tokens, total } => (addresses(&tokens).join(","), total),
+                other => panic!("Unexpected answer {:?}", other),
+            }
+        };
+        assert_eq!(verified_page(&deps, None, 0), ("scrt_token".to_string(), 2));
+        assert_eq!(verified_page(&deps, None, 1), ("snip_token".to_string(), 2));
+        assert_eq!(verified_page(&deps, None, 2), (String::new(), 2));
+        assert_eq!(verified_page(&deps, Some(150), 1), (String::new(), 1));
+
+        // Deregistered tokens leave the index and the count of all tokens.
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::DeregisterToken {
+                address: HumanAddr("scrt_token".to_string()),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(verified_page(&deps, None, 0), ("snip_token".to_string(), 1));
+        let all = QueryMsg::Tokens {
+            min_height: None,
+            max_height: None,
+            verified_only: None,
+            page: Some(1),
+            page_size: 1,
+        };
+        match from_binary(&query(&deps, all).unwrap()).unwrap() {
+            QueryAnswer::Tokens { tokens, total } => {
+                assert_eq!(addresses(&tokens), vec!["snip_token"]);
+                assert_eq!(total, 2);
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        // Only the current curator can change badges.
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetCurator {
+                address: HumanAddr("new_curator".to_string()),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let neutral = badge("snip_token", VerificationStatus::Neutral, "Team left");
+        assert!(handle(&mut deps, mock_env("curator", &[]), neutral.clone()).is_err());
+        handle(&mut deps, mock_env("new_curator", &[]), neutral).unwrap();
+        assert!(verified(&deps).is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use atl_snip20_reference_impl::msg::{InitConfig, InitialBalance, ResponseStatus};
+use cosmwasm_std::{Binary, Coin, Decimal, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use snipix::msg::{GovernanceConfig, MarketingInfo, TransferFee};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct InitMsg {
+    pub admin: Option<HumanAddr>,
+    /// Badges tokens. Defaults to the admin.
+    pub curator: Option<HumanAddr>,
+    /// The first code version tokens are launched with.
+    pub snipix_code_id: u64,
+    pub snipix_code_hash: String,
+    /// Seeds the randomness of launched tokens that do not bring their own.
+    pub prng_seed: Binary,
+    /// Requires token names to be unique too, ignoring case. Symbols always are.
+    pub unique_names: Option<bool>,
+    /// Symbols only the admin can launch tokens with.
+    pub reserved_symbols: Option<Vec<String>>,
+    /// Launches are free without one.
+    pub fee_schedule: Option<FeeSchedule>,
+    pub templates: Option<Vec<Template>>,
+}
+
+/// A preset tokens are launched from, such as a meme token or a stablecoin. Launches from a
+/// template can be discounted through the fee schedule.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub description: Option<String>,
+    pub decimals: u8,
+    pub config: Option<InitConfig>,
+    pub extensions: TemplateExtensions,
+    pub rules: TemplateRules,
+}
+
+/// The snipix extensions enabled on tokens launched from a template.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct TemplateExtensions {
+    pub supply_cap: Option<Uint128>,
+    pub transfer_fee: Option<TransferFee>,
+    /// Number of blocks over which the initial balances vest.
+    pub vesting_period: Option<u64>,
+    pub governance: Option<GovernanceConfig>,
+}
+
+/// What creators can change when launching from a template.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct TemplateRules {
+    /// Caps the sum of the initial balances.
+    pub max_initial_supply: Option<Uint128>,
+    pub decimals_overridable: bool,
+    pub config_overridable: bool,
+    /// Covers the supply cap, the transfer fee and the vesting period.
+    pub extensions_overridable: bool,
+}
+
+/// Settings of a launch on top of its template, each only accepted if the template's rules allow
+/// changing it.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+pub struct TokenOverrides {
+    pub admin: Option<HumanAddr>,
+    pub initial_balances: Option<Vec<InitialBalance>>,
+    pub prng_seed: Option<Binary>,
+    pub marketing_info: Option<MarketingInfo>,
+    pub decimals: Option<u8>,
+    pub config: Option<InitConfig>,
+    pub supply_cap: Option<Uint128>,
+    pub transfer_fee: Option<TransferFee>,
+    pub vesting_period: Option<u64>,
+}
+
+/// What `CreateToken` costs, in a native coin.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct FeeSchedule {
+    pub fee: Coin,
+    pub template_discounts: Vec<TemplateDiscount>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct TemplateDiscount {
+    pub template: String,
+    /// Share of the fee waived for launches from the template, between 0 and 1.
+    pub discount: Decimal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// Instantiates a new snipix token from `template`, with the sender as its admin unless
+    /// overridden, and with the latest code version unless `code_version` is set.
+    CreateToken {
+        name: String,
+        symbol: String,
+        template: String,
+        code_version: Option<u32>,
+        overrides: Option<TokenOverrides>,
+        padding: Option<String>,
+    },
+    /// Sent by a newly instantiated token, through its init callback.
+    RegisterToken {},
+    /// Sent by a launched token whenever its marketing info changes.
+    UpdateMarketingInfo {
+        marketing_info: Option<MarketingInfo>,
+    },
+    /// Sent by a launched token to be replaced by an instance of a newer code version, the latest
+    /// unless `code_version` is set.
+    MigrateToken {
+        code_version: Option<u32>,
+        init_msg: Box<snipix::msg::InitMsg>,
+    },
+    /// Badges a token as verified, flagged or neutral. Only the curator can set badges.
+    SetVerification {
+        address: HumanAddr,
+        status: VerificationStatus,
+        reason: String,
+        padding: Option<String>,
+    },
+    /// Removes a token from the registry, releasing its symbol. Only its creator or the admin can
+    /// deregister it.
+    DeregisterToken {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+
+    // Admin
+    /// Registers a new snipix code version, which tokens are launched with from then on.
+    AddCodeVersion {
+        code_id: u64,
+        code_hash: String,
+        changelog: String,
+        padding: Option<String>,
+    },
+    /// Stops tokens from being launched or migrated with a code version.
+    DeprecateCodeVersion {
+        version: u32,
+        padding: Option<String>,
+    },
+    ChangeAdmin {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    SetCurator {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    ReserveSymbols {
+        symbols: Vec<String>,
+        padding: Option<String>,
+    },
+    UnreserveSymbols {
+        symbols: Vec<String>,
+        padding: Option<String>,
+    },
+    SetFeeSchedule {
+        fee_schedule: Option<FeeSchedule>,
+        padding: Option<String>,
+    },
+    /// Adds a template, or replaces the one with the same name.
+    SetTemplate {
+        template: Template,
+        padding: Option<String>,
+    },
+    RemoveTemplate {
+        name: String,
+        padding: Option<String>,
+    },
+    /// Pays collected fees out of the treasury, to the sender unless `recipient` is set. Withdraws
+    /// everything collected in `denom` unless `amount` is set.
+    WithdrawFees {
+        denom: String,
+        amount: Option<Uint128>,
+        recipient: Option<HumanAddr>,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    CreateToken { status: ResponseStatus },
+    RegisterToken { status: ResponseStatus },
+    UpdateMarketingInfo { status: ResponseStatus },
+    MigrateToken { status: ResponseStatus },
+    SetVerification { status: ResponseStatus },
+    DeregisterToken { status: ResponseStatus },
+    AddCodeVersion { version: u32 },
+    DeprecateCodeVersion { status: ResponseStatus },
+    ChangeAdmin { status: ResponseStatus },
+    SetCurator { status: ResponseStatus },
+    ReserveSymbols { status: ResponseStatus },
+    UnreserveSymbols { status: ResponseStatus },
+    SetFeeSchedule { status: ResponseStatus },
+    SetTemplate { status: ResponseStatus },
+    RemoveTemplate { status: ResponseStatus },
+    WithdrawFees { amount: Coin },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Token {
+        address: HumanAddr,
+    },
+    /// Launched tokens, most recent first, optionally only those created within a range of
+    /// block heights, or only verified ones.
+    Tokens {
+        min_height: Option<u64>,
+        max_height: Option<u64>,
+        verified_only: Option<bool>,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    TokensByCreator {
+        creator: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// Tokens whose symbol starts with `prefix`, ignoring case.
+    TokensBySymbol {
+        prefix: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// Whether a token could be launched with `symbol`.
+    Symbol {
+        symbol: String,
+    },
+    FeeSchedule {},
+    Templates {},
+    CodeVersions {},
+    /// Fees collected and not withdrawn yet.
+    Treasury {},
+}
+
+/// A snipix wasm tokens can be launched with, numbered from 1 in the order they were added.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct CodeVersion {
+    pub version: u32,
+    pub code_id: u64,
+    pub code_hash: String,
+    pub changelog: String,
+    pub deprecated: bool,
+    /// Block height at which the version was added.
+    pub released_at: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Verified,
+    /// Reported as a scam.
+    Flagged,
+    Neutral,
+}
+
+/// A badge set by the curator.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub status: VerificationStatus,
+    pub reason: String,
+    /// Block height at which the badge was set.
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    pub id: u64,
+    pub address: HumanAddr,
+    pub code_id: u64,
+    pub code_hash: String,
+    pub code_version: u32,
+    pub creator: HumanAddr,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub template: String,
+    pub marketing_info: Option<MarketingInfo>,
+    pub created_at: u64,
+    pub verification: Option<Verification>,
+    /// The previous and next versions of the token, if it was migrated.
+    pub migrated_from: Option<HumanAddr>,
+    pub migrated_to: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolStatus {
+    Available,
+    /// Only the admin can launch a token with it.
+    Reserved,
+    Taken {
+        token: HumanAddr,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    Config {
+        admin: HumanAddr,
+        curator: HumanAddr,
+        snipix_code_id: u64,
+        snipix_code_hash: String,
+        latest_version: u32,
+        unique_names: bool,
+    },
+    Token {
+        token: TokenInfo,
+    },
+    Tokens {
+        tokens: Vec<TokenInfo>,
+        total: u64,
+    },
+    Symbol {
+        symbol: String,
+        status: SymbolStatus,
+    },
+    FeeSchedule {
+        fee_schedule: Option<FeeSchedule>,
+    },
+    Templates {
+        templates: Vec<Template>,
+    },
+    CodeVersions {
+        versions: Vec<CodeVersion>,
+    },
+    Treasury {
+        balances: Vec<Coin>,
+    },
+}
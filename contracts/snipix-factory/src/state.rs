@@ -0,0 +1,345 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, Coin, ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, PrefixedStorage, ReadonlyBucket,
+    ReadonlyPrefixedStorage, ReadonlySingleton, Singleton,
+};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+use snipix::msg::MarketingInfo;
+
+use crate::msg::{CodeVersion, FeeSchedule, Template, Verification};
+
+pub static CONFIG_KEY: &[u8] = b"config";
+pub static PENDING_LAUNCH_KEY: &[u8] = b"pending_launch";
+pub static TOKEN_COUNT_KEY: &[u8] = b"token_count";
+pub static TOKENS_KEY: &[u8] = b"tokens";
+pub static TOKEN_IDS_KEY: &[u8] = b"token_ids";
+pub static CREATOR_TOKENS_KEY: &[u8] = b"creator_tokens";
+pub static SYMBOL_PREFIX_TOKENS_KEY: &[u8] = b"symbol_prefix_tokens";
+pub static SYMBOL_TOKENS_KEY: &[u8] = b"symbol_tokens";
+pub static NAME_TOKENS_KEY: &[u8] = b"name_tokens";
+pub static RESERVED_SYMBOLS_KEY: &[u8] = b"reserved_symbols";
+pub static FEE_SCHEDULE_KEY: &[u8] = b"fee_schedule";
+pub static TREASURY_KEY: &[u8] = b"treasury";
+pub static TEMPLATES_KEY: &[u8] = b"templates";
+pub static TEMPLATE_NAMES_KEY: &[u8] = b"template_names";
+pub static CODE_VERSIONS_KEY: &[u8] = b"code_versions";
+pub static VERIFIED_TOKENS_KEY: &[u8] = b"verified_tokens";
+pub static DEREGISTERED_TOKENS_KEY: &[u8] = b"deregistered_tokens";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub admin: CanonicalAddr,
+    pub curator: CanonicalAddr,
+    /// Code version tokens are launched with by default.
+    pub latest_version: u32,
+    pub prng_seed: Vec<u8>,
+    pub unique_names: bool,
+}
+
+/// A token being instantiated. Its init callback runs in the same transaction as `CreateToken`,
+/// so there is never more than one launch pending.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingLaunch {
+    pub creator: CanonicalAddr,
+    pub code_id: u64,
+    pub code_hash: String,
+    pub code_version: u32,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub template: String,
+    pub marketing_info: Option<MarketingInfo>,
+    /// Id of the token the launch replaces, if it is a migration.
+    pub migrated_from: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Token {
+    pub address: CanonicalAddr,
+    pub code_id: u64,
+    pub code_hash: String,
+    pub code_version: u32,
+    pub creator: CanonicalAddr,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub template: String,
+    /// Kept up to date by the token whenever its marketing info changes.
+    pub marketing_info: Option<MarketingInfo>,
+    /// Block height at which the token was launched.
+    pub created_at: u64,
+    /// Badge set by the curator, carried over to the next version of the token.
+    pub verification: Option<Verification>,
+    /// Positions of the token in its creator's list, and in the list of each prefix of its symbol.
+    pub creator_index: u32,
+    pub symbol_indexes: Vec<u32>,
+    /// Deregistered tokens keep their id, but are left out of every listing.
+    pub deregistered: bool,
+    /// Ids of the previous and next versions of the token. Migrated tokens are deregistered.
+    pub migrated_from: Option<u64>,
+    pub migrated_to: Option<u64>,
+}
+
+pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, Config> {
+    singleton(storage, CONFIG_KEY)
+}
+
+pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Config> {
+    singleton_read(storage, CONFIG_KEY)
+}
+
+pub fn pending_launch<S: Storage>(storage: &mut S) -> Singleton<S, Option<PendingLaunch>> {
+    singleton(storage, PENDING_LAUNCH_KEY)
+}
+
+pub fn fee_schedule<S: Storage>(storage: &mut S) -> Singleton<S, Option<FeeSchedule>> {
+    singleton(storage, FEE_SCHEDULE_KEY)
+}
+
+pub fn fee_schedule_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Option<FeeSchedule>> {
+    singleton_read(storage, FEE_SCHEDULE_KEY)
+}
+
+/// Collected fees, one coin per denom.
+pub fn treasury<S: Storage>(storage: &mut S) -> Singleton<S, Vec<Coin>> {
+    singleton(storage, TREASURY_KEY)
+}
+
+pub fn treasury_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<Coin>> {
+    singleton_read(storage, TREASURY_KEY)
+}
+
+/// Launch templates, by name.
+pub fn templates<S: Storage>(storage: &mut S) -> Bucket<S, Template> {
+    bucket(TEMPLATES_KEY, storage)
+}
+
+pub fn templates_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Template> {
+    bucket_read(TEMPLATES_KEY, storage)
+}
+
+/// Names of the launch templates, in the order they were added.
+pub fn template_names<S: Storage>(storage: &mut S) -> Singleton<S, Vec<String>> {
+    singleton(storage, TEMPLATE_NAMES_KEY)
+}
+
+pub fn template_names_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Vec<String>> {
+    singleton_read(storage, TEMPLATE_NAMES_KEY)
+}
+
+/// Snipix code versions, by version number.
+pub fn code_versions<S: Storage>(storage: &mut S) -> Bucket<S, CodeVersion> {
+    bucket(CODE_VERSIONS_KEY, storage)
+}
+
+pub fn code_versions_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, CodeVersion> {
+    bucket_read(CODE_VERSIONS_KEY, storage)
+}
+
+pub fn token_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, TOKEN_COUNT_KEY)
+}
+
+pub fn token_count_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, TOKEN_COUNT_KEY)
+}
+
+/// Launched tokens, by id.
+pub fn tokens<S: Storage>(storage: &mut S) -> Bucket<S, Token> {
+    bucket(TOKENS_KEY, storage)
+}
+
+pub fn tokens_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Token> {
+    bucket_read(TOKENS_KEY, storage)
+}
+
+/// Token ids, by token address.
+pub fn token_ids<S: Storage>(storage: &mut S) -> Bucket<S, u64> {
+    bucket(TOKEN_IDS_KEY, storage)
+}
+
+pub fn token_ids_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, u64> {
+    bucket_read(TOKEN_IDS_KEY, storage)
+}
+
+/// Token ids, by upper-cased symbol.
+pub fn symbol_tokens<S: Storage>(storage: &mut S) -> Bucket<S, u64> {
+    bucket(SYMBOL_TOKENS_KEY, storage)
+}
+
+pub fn symbol_tokens_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, u64> {
+    bucket_read(SYMBOL_TOKENS_KEY, storage)
+}
+
+/// Token ids, by lower-cased name. Only kept when names have to be unique.
+pub fn name_tokens<S: Storage>(storage: &mut S) -> Bucket<S, u64> {
+    bucket(NAME_TOKENS_KEY, storage)
+}
+
+pub fn name_tokens_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, u64> {
+    bucket_read(NAME_TOKENS_KEY, storage)
+}
+
+/// Upper-cased symbols only the admin can launch tokens with.
+pub fn reserved_symbols<S: Storage>(storage: &mut S) -> Bucket<S, bool> {
+    bucket(RESERVED_SYMBOLS_KEY, storage)
+}
+
+pub fn reserved_symbols_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, bool> {
+    bucket_read(RESERVED_SYMBOLS_KEY, storage)
+}
+
+/// Appends `token_id` to the list stored under `namespace` and `key`, such as the tokens of a
+/// creator, and returns its position. Lists let tokens be enumerated without iterating over
+/// storage.
+pub fn push_token_id<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    key: &[u8],
+    token_id: u64,
+) -> StdResult<u32> {
+    let mut store = PrefixedStorage::multilevel(&[namespace, key], storage);
+    let mut store = AppendStoreMut::<u64, _, _>::attach_or_create(&mut store)?;
+    store.push(&token_id)?;
+
+    Ok(store.len() - 1)
+}
+
+/// Removes the token id at `index` by moving the last id into its place. Returns the moved id, if
+/// any, whose token then has to have its stored position updated.
+pub fn remove_token_id<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    key: &[u8],
+    index: u32,
+) -> StdResult<Option<u64>> {
+    let mut store = PrefixedStorage::multilevel(&[namespace, key], storage);
+    let mut store = AppendStoreMut::<u64, _, _>::attach_or_create(&mut store)?;
+
+    let last = store.pop()?;
+    if index < store.len() {
+        store.set_at(index, &last)?;
+        Ok(Some(last))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns a page of the token ids in a list, most recent first, along with the length of the list.
+pub fn page_token_ids<S: ReadonlyStorage>(
+    storage: &S,
+    namespace: &[u8],
+    key: &[u8],
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<u64>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[namespace, key], storage);
+    let store = match AppendStore::<u64, _, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok((vec![], 0)),
+    };
+
+    let ids = store
+        .iter()
+        .rev()
+        .skip(page as usize * page_size as usize)
+        .take(page_size as usize)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok((ids, store.len() as u64))
+}
+
+// Verified and deregistered tokens are kept in lists sorted by id, so that a range of ids can be
+// located in them by binary search. Inserting or removing an id shifts the ids after it.
+
+/// Inserts `token_id` into a sorted list.
+pub fn insert_sorted_token_id<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    token_id: u64,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(namespace, storage);
+    let mut store = AppendStoreMut::<u64, _, _>::attach_or_create(&mut store)?;
+
+    let mut index = store.len();
+    store.push(&token_id)?;
+    while index > 0 {
+        let previous = store.get_at(index - 1)?;
+        if previous < token_id {
+            break;
+        }
+        store.set_at(index, &previous)?;
+        index -= 1;
+    }
+
+    store.set_at(index, &token_id)
+}
+
+/// Removes `token_id` from a sorted list, if it is there.
+pub fn remove_sorted_token_id<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    token_id: u64,
+) -> StdResult<()> {
+    let index = count_token_ids_below(&*storage, namespace, token_id)?;
+    let mut store = PrefixedStorage::new(namespace, storage);
+    let mut store = AppendStoreMut::<u64, _, _>::attach_or_create(&mut store)?;
+    if index >= store.len() || store.get_at(index)? != token_id {
+        return Ok(());
+    }
+
+    for next in index + 1..store.len() {
+        let moved = store.get_at(next)?;
+        store.set_at(next - 1, &moved)?;
+    }
+    store.pop()?;
+
+    Ok(())
+}
+
+/// Returns how many ids of a sorted list are lower than `token_id`.
+pub fn count_token_ids_below<S: ReadonlyStorage>(
+    storage: &S,
+    namespace: &[u8],
+    token_id: u64,
+) -> StdResult<u32> {
+    let store = ReadonlyPrefixedStorage::new(namespace, storage);
+    let store = match AppendStore::<u64, _, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok(0),
+    };
+
+    let (mut low, mut high) = (0, store.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.get_at(mid)? < token_id {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
+
+/// Returns the ids of a sorted list at positions `start..end`, highest first.
+pub fn sorted_token_ids<S: ReadonlyStorage>(
+    storage: &S,
+    namespace: &[u8],
+    start: u32,
+    end: u32,
+) -> StdResult<Vec<u64>> {
+    let store = ReadonlyPrefixedStorage::new(namespace, storage);
+    let store = match AppendStore::<u64, _, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok(vec![]),
+    };
+
+    (start..end.min(store.len()))
+        .rev()
+        .map(|index| store.get_at(index))
+        .collect()
+}
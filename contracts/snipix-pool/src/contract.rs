@@ -0,0 +1,1149 @@
+use atl_snip20_reference_impl::msg::{HandleMsg as Snip20HandleMsg, ResponseStatus};
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Env, Extern,
+    HandleResponse, HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128,
+    WasmMsg,
+};
+use serde::Serialize;
+use snipix::msg::InitCallback;
+
+use crate::msg::{
+    Asset, Contract, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, ReceiveMsg,
+    SCRT_DENOM,
+};
+use crate::state::{config, config_read, deposits, deposits_read, pool, pool_read, Config, Pool};
+
+/// Mirrors `InitConfig`, whose fields the reference implementation keeps private.
+#[derive(Serialize)]
+struct LpTokenConfig {
+    public_total_supply: bool,
+    enable_mint: bool,
+    enable_burn: bool,
+}
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: InitMsg,
+) -> StdResult<InitResponse> {
+    validate_swap_fee(msg.swap_fee)?;
+    let admin = msg.admin.unwrap_or_else(|| env.message.sender.clone());
+    config(&mut deps.storage).save(&Config {
+        admin: deps.api.canonical_address(&admin)?,
+        token: msg.token.clone(),
+        lp_token: None,
+        lp_token_code_hash: msg.lp_token_code_hash.clone(),
+        swap_fee: msg.swap_fee,
+    })?;
+    pool(&mut deps.storage).save(&Pool::default())?;
+
+    // The pool is the admin of its LP token, and so its only minter.
+    let lp_token_msg = snipix::msg::InitMsg {
+        name: msg.lp_token_name,
+        admin: Some(env.contract.address.clone()),
+        symbol: msg.lp_token_symbol.clone(),
+        decimals: 6,
+        initial_balances: None,
+        prng_seed: msg.prng_seed,
+        config: Some(from_binary(&to_binary(&LpTokenConfig {
+            public_total_supply: true,
+            enable_mint: true,
+            enable_burn: true,
+        })?)?),
+        marketing_info: None,
+        governance: None,
+        reward_tokens: None,
+        supply_cap: None,
+        transfer_fee: None,
+        vesting_period: None,
+        migrated_from: None,
+        init_callback: Some(InitCallback {
+            msg: to_binary(&HandleMsg::RegisterLpToken {})?,
+            contract_addr: env.contract.address.clone(),
+            code_hash: env.contract_code_hash.clone(),
+        }),
+    };
+
+    Ok(InitResponse {
+        messages: vec![
+            register_receive_msg(&env, &msg.token)?,
+            CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id: msg.lp_token_code_id,
+                callback_code_hash: msg.lp_token_code_hash,
+                msg: to_binary(&lp_token_msg)?,
+                send: vec![],
+                label: format!("{} ({})", msg.lp_token_symbol, env.contract.address),
+            }),
+        ],
+        log: vec![],
+    })
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        HandleMsg::RegisterLpToken {} => try_register_lp_token(deps, env),
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => try_receive(deps, env, from, amount, msg),
+        HandleMsg::Swap { min_return, to, .. } => {
+            let amount = sent_scrt(&env)?;
+            let recipient = to.unwrap_or_else(|| env.message.sender.clone());
+            try_swap(deps, env, Asset::Scrt, amount, min_return, recipient)
+        }
+        HandleMsg::ProvideLiquidity {
+            token_amount,
+            min_lp_amount,
+            ..
+        } => try_provide_liquidity(deps, env, token_amount, min_lp_amount),
+        HandleMsg::WithdrawDeposit { .. } => try_withdraw_deposit(deps, env),
+        HandleMsg::SetSwapFee { swap_fee, .. } => try_set_swap_fee(deps, env, swap_fee),
+        HandleMsg::ChangeAdmin { address, .. } => try_change_admin(deps, env, address),
+    }
+}
+
+pub fn try_register_lp_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut state = config_read(&deps.storage).load()?;
+    if state.lp_token.is_some() {
+        return Err(StdError::generic_err("The LP token is already registered"));
+    }
+
+    let lp_token = Contract {
+        address: env.message.sender.clone(),
+        code_hash: state.lp_token_code_hash.clone(),
+    };
+    state.lp_token = Some(lp_token.clone());
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![register_receive_msg(&env, &lp_token)?],
+        log: vec![log("lp_token", lp_token.address)],
+        data: Some(to_binary(&HandleAnswer::RegisterLpToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Tokens are deposited or swapped, while LP shares are withdrawn as liquidity.
+pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let msg = msg.ok_or_else(|| StdError::generic_err("Missing receive message"))?;
+    let is_lp_token = state
+        .lp_token
+        .as_ref()
+        .map_or(false, |lp_token| lp_token.address == env.message.sender);
+
+    let msg: ReceiveMsg = from_binary(&msg)?;
+    if is_lp_token {
+        return match msg {
+            ReceiveMsg::WithdrawLiquidity {
+                min_scrt,
+                min_token,
+            } => try_withdraw_liquidity(deps, env, from, amount, min_scrt, min_token),
+            _ => Err(StdError::generic_err(
+                "LP shares can only be withdrawn as liquidity",
+            )),
+        };
+    }
+    if env.message.sender != state.token.address {
+        return Err(StdError::generic_err(
+            "Only the pool tokens can be sent to the pool",
+        ));
+    }
+
+    match msg {
+        ReceiveMsg::Deposit {} => try_deposit(deps, from, amount),
+        ReceiveMsg::Swap { min_return, to } => {
+            let recipient = to.unwrap_or(from);
+            try_swap(deps, env, Asset::Token, amount, min_return, recipient)
+        }
+        ReceiveMsg::WithdrawLiquidity { .. } => Err(StdError::generic_err(
+            "Only LP shares can be withdrawn as liquidity",
+        )),
+    }
+}
+
+fn try_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    from: HumanAddr,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let depositor = deps.api.canonical_address(&from)?;
+    let deposit = deposits_read(&deps.storage)
+        .may_load(depositor.as_slice())?
+        .unwrap_or_default();
+    deposits(&mut deps.storage).save(
+        depositor.as_slice(),
+        &Uint128(deposit.u128() + amount.u128()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("depositor", from), log("amount", amount)],
+        data: Some(to_binary(&HandleAnswer::Receive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    offer: Asset,
+    amount: Uint128,
+    min_return: Option<Uint128>,
+    recipient: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let mut reserves = pool_read(&deps.storage).load()?;
+    let (return_amount, fee_amount) = simulate_swap(&reserves, state.swap_fee, &offer, amount)?;
+    if return_amount.is_zero() {
+        return Err(StdError::generic_err("Swap amount is too small"));
+    }
+    if let Some(min_return) = min_return {
+        if return_amount < min_return {
+            return Err(StdError::generic_err(format!(
+                "Swap returns {} which is below the minimum return of {}",
+                return_amount, min_return
+            )));
+        }
+    }
+
+    // The fee is part of what is offered, so it stays in the reserves.
+    let message = match offer {
+        Asset::Scrt => {
+            reserves.scrt_reserve = Uint128(reserves.scrt_reserve.u128() + amount.u128());
+            reserves.token_reserve = Uint128(reserves.token_reserve.u128() - return_amount.u128());
+            transfer_msg(&state.token, recipient.clone(), return_amount)?
+        }
+        Asset::Token => {
+            reserves.token_reserve = Uint128(reserves.token_reserve.u128() + amount.u128());
+            reserves.scrt_reserve = Uint128(reserves.scrt_reserve.u128() - return_amount.u128());
+            scrt_msg(&env, recipient.clone(), return_amount)
+        }
+    };
+    pool(&mut deps.storage).save(&reserves)?;
+
+    Ok(HandleResponse {
+        messages: vec![message],
+        log: vec![
+            log("recipient", recipient),
+            log("offer_amount", amount),
+            log("return_amount", return_amount),
+            log("fee_amount", fee_amount),
+        ],
+        data: Some(to_binary(&HandleAnswer::Swap {
+            return_amount,
+            fee_amount,
+        })?),
+    })
+}
+
+pub fn try_provide_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    token_amount: Uint128,
+    min_lp_amount: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let lp_token = state
+        .lp_token
+        .ok_or_else(|| StdError::generic_err("The LP token is not registered yet"))?;
+    let scrt_amount = sent_scrt(&env)?;
+    let provider = deps.api.canonical_address(&env.message.sender)?;
+    let deposit = deposits_read(&deps.storage)
+        .may_load(provider.as_slice())?
+        .unwrap_or_default();
+    if deposit < token_amount {
+        return Err(StdError::generic_err(format!(
+            "insufficient deposit: deposit={}, required={}",
+            deposit, token_amount
+        )));
+    }
+
+    let mut reserves = pool_read(&deps.storage).load()?;
+    let (lp_amount, scrt_used, token_used) =
+        simulate_provide_liquidity(&reserves, scrt_amount, token_amount)?;
+    if lp_amount.is_zero() {
+        return Err(StdError::generic_err("Liquidity provided is too small"));
+    }
+    if let Some(min_lp_amount) = min_lp_amount {
+        if lp_amount < min_lp_amount {
+            return Err(StdError::generic_err(format!(
+                "Providing liquidity mints {} LP shares which is below the minimum of {}",
+                lp_amount, min_lp_amount
+            )));
+        }
+    }
+
+    reserves.scrt_reserve = Uint128(reserves.scrt_reserve.u128() + scrt_used.u128());
+    reserves.token_reserve = Uint128(reserves.token_reserve.u128() + token_used.u128());
+    reserves.lp_supply = Uint128(reserves.lp_supply.u128() + lp_amount.u128());
+    pool(&mut deps.storage).save(&reserves)?;
+    deposits(&mut deps.storage).save(
+        provider.as_slice(),
+        &Uint128(deposit.u128() - token_used.u128()),
+    )?;
+
+    let mut messages = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: lp_token.address,
+        callback_code_hash: lp_token.code_hash,
+        msg: to_binary(&Snip20HandleMsg::Mint {
+            recipient: env.message.sender.clone(),
+            amount: lp_amount,
+            memo: None,
+            padding: None,
+        })?,
+        send: vec![],
+    })];
+    if scrt_used < scrt_amount {
+        messages.push(scrt_msg(
+            &env,
+            env.message.sender.clone(),
+            Uint128(scrt_amount.u128() - scrt_used.u128()),
+        ));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("provider", &env.message.sender),
+            log("lp_amount", lp_amount),
+            log("scrt_amount", scrt_used),
+            log("token_amount", token_used),
+        ],
+        data: Some(to_binary(&HandleAnswer::ProvideLiquidity {
+            lp_amount,
+            scrt_amount: scrt_used,
+            token_amount: token_used,
+        })?),
+    })
+}
+
+fn try_withdraw_liquidity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    lp_amount: Uint128,
+    min_scrt: Option<Uint128>,
+    min_token: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let mut reserves = pool_read(&deps.storage).load()?;
+    let (scrt_amount, token_amount) = simulate_withdraw_liquidity(&reserves, lp_amount)?;
+    if min_scrt.map_or(false, |min_scrt| scrt_amount < min_scrt)
+        || min_token.map_or(false, |min_token| token_amount < min_token)
+    {
+        return Err(StdError::generic_err(format!(
+            "Withdrawing returns {}{} and {} tokens which is below the minimum",
+            scrt_amount, SCRT_DENOM, token_amount
+        )));
+    }
+
+    reserves.scrt_reserve = Uint128(reserves.scrt_reserve.u128() - scrt_amount.u128());
+    reserves.token_reserve = Uint128(reserves.token_reserve.u128() - token_amount.u128());
+    reserves.lp_supply = Uint128(reserves.lp_supply.u128() - lp_amount.u128());
+    pool(&mut deps.storage).save(&reserves)?;
+
+    // The LP token sent the shares to the pool, which burns them from its own balance.
+    let mut messages = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: env.message.sender.clone(),
+        callback_code_hash: state.lp_token_code_hash,
+        msg: to_binary(&Snip20HandleMsg::Burn {
+            amount: lp_amount,
+            memo: None,
+            padding: None,
+        })?,
+        send: vec![],
+    })];
+    if !scrt_amount.is_zero() {
+        messages.push(scrt_msg(&env, from.clone(), scrt_amount));
+    }
+    if !token_amount.is_zero() {
+        messages.push(transfer_msg(&state.token, from.clone(), token_amount)?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("provider", from),
+            log("lp_amount", lp_amount),
+            log("scrt_amount", scrt_amount),
+            log("token_amount", token_amount),
+        ],
+        data: Some(to_binary(&HandleAnswer::Receive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_withdraw_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let state = config_read(&deps.storage).load()?;
+    let depositor = deps.api.canonical_address(&env.message.sender)?;
+    let amount = deposits_read(&deps.storage)
+        .may_load(depositor.as_slice())?
+        .unwrap_or_default();
+    if amount.is_zero() {
+        return Err(StdError::generic_err("No tokens are deposited"));
+    }
+    deposits(&mut deps.storage).remove(depositor.as_slice());
+
+    Ok(HandleResponse {
+        messages: vec![transfer_msg(&state.token, env.message.sender, amount)?],
+        log: vec![log("amount", amount)],
+        data: Some(to_binary(&HandleAnswer::WithdrawDeposit { amount })?),
+    })
+}
+
+pub fn try_set_swap_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    swap_fee: Decimal,
+) -> StdResult<HandleResponse> {
+    let mut state = load_admin_config(deps, &env)?;
+    validate_swap_fee(swap_fee)?;
+    state.swap_fee = swap_fee;
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("swap_fee", swap_fee)],
+        data: Some(to_binary(&HandleAnswer::SetSwapFee {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_change_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut state = load_admin_config(deps, &env)?;
+    state.admin = deps.api.canonical_address(&address)?;
+    config(&mut deps.storage).save(&state)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ChangeAdmin {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+fn load_admin_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<Config> {
+    let config = config_read(&deps.storage).load()?;
+    if config.admin != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+
+    Ok(config)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => query_config(deps),
+        QueryMsg::Pool {} => query_pool(deps),
+        QueryMsg::SimulateSwap { offer, amount } => {
+            let state = config_read(&deps.storage).load()?;
+            let reserves = pool_read(&deps.storage).load()?;
+            let (return_amount, fee_amount) =
+                simulate_swap(&reserves, state.swap_fee, &offer, amount)?;
+            to_binary(&QueryAnswer::SimulateSwap {
+                return_amount,
+                fee_amount,
+            })
+        }
+        QueryMsg::SimulateProvideLiquidity {
+            scrt_amount,
+            token_amount,
+        } => {
+            let reserves = pool_read(&deps.storage).load()?;
+            let (lp_amount, scrt_amount, token_amount) =
+                simulate_provide_liquidity(&reserves, scrt_amount, token_amount)?;
+            to_binary(&QueryAnswer::SimulateProvideLiquidity {
+                lp_amount,
+                scrt_amount,
+                token_amount,
+            })
+        }
+        QueryMsg::SimulateWithdrawLiquidity { lp_amount } => {
+            let reserves = pool_read(&deps.storage).load()?;
+            let (scrt_amount, token_amount) = simulate_withdraw_liquidity(&reserves, lp_amount)?;
+            to_binary(&QueryAnswer::SimulateWithdrawLiquidity {
+                scrt_amount,
+                token_amount,
+            })
+        }
+    }
+}
+
+fn query_config<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let config = config_read(&deps.storage).load()?;
+
+    to_binary(&QueryAnswer::Config {
+        admin: deps.api.human_address(&config.admin)?,
+        token: config.token,
+        lp_token: config.lp_token,
+        swap_fee: config.swap_fee,
+    })
+}
+
+fn query_pool<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let reserves = pool_read(&deps.storage).load()?;
+
+    to_binary(&QueryAnswer::Pool {
+        scrt_reserve: reserves.scrt_reserve,
+        token_reserve: reserves.token_reserve,
+        lp_supply: reserves.lp_supply,
+    })
+}
+
+/// Returns what offering `amount` returns, and the fee kept from it. The fee is taken before
+/// swapping, and the rest keeps the product of the reserves from going down.
+fn simulate_swap(
+    reserves: &Pool,
+    swap_fee: Decimal,
+    offer: &Asset,
+    amount: Uint128,
+) -> StdResult<(Uint128, Uint128)> {
+    let (offer_reserve, ask_reserve) = match offer {
+        Asset::Scrt => (reserves.scrt_reserve, reserves.token_reserve),
+        Asset::Token => (reserves.token_reserve, reserves.scrt_reserve),
+    };
+    if offer_reserve.is_zero() || ask_reserve.is_zero() {
+        return Err(StdError::generic_err("The pool has no liquidity yet"));
+    }
+
+    let fee_amount = amount * swap_fee;
+    let offer_net = amount.u128() - fee_amount.u128();
+    let return_amount = ask_reserve.multiply_ratio(offer_net, offer_reserve.u128() + offer_net);
+
+    Ok((return_amount, fee_amount))
+}
+
+/// Returns the LP shares minted for providing up to `scrt_amount` and `token_amount`, along with
+/// the amounts used, which keep the ratio of the reserves. The first provider sets the ratio, and
+/// gets the geometric mean of the amounts in shares.
+fn simulate_provide_liquidity(
+    reserves: &Pool,
+    scrt_amount: Uint128,
+    token_amount: Uint128,
+) -> StdResult<(Uint128, Uint128, Uint128)> {
+    if scrt_amount.is_zero() || token_amount.is_zero() {
+        return Err(StdError::generic_err(
+            "Liquidity must be provided in both SCRT and tokens",
+        ));
+    }
+    if reserves.lp_supply.is_zero() {
+        let product = scrt_amount
+            .u128()
+            .checked_mul(token_amount.u128())
+            .ok_or_else(|| StdError::generic_err("Liquidity amounts are too large"))?;
+        return Ok((Uint128(integer_sqrt(product)), scrt_amount, token_amount));
+    }
+
+    let token_needed =
+        scrt_amount.multiply_ratio(reserves.token_reserve.u128(), reserves.scrt_reserve.u128());
+    let (scrt_used, token_used) = if token_needed <= token_amount {
+        (scrt_amount, token_needed)
+    } else {
+        let scrt_needed = token_amount
+            .multiply_ratio(reserves.scrt_reserve.u128(), reserves.token_reserve.u128());
+        (scrt_needed, token_amount)
+    };
+    let lp_amount = std::cmp::min(
+        scrt_used.multiply_ratio(reserves.lp_supply.u128(), reserves.scrt_reserve.u128()),
+        token_used.multiply_ratio(reserves.lp_supply.u128(), reserves.token_reserve.u128()),
+    );
+
+    Ok((lp_amount, scrt_used, token_used))
+}
+
+/// Returns the part of the reserves `lp_amount` shares are worth.
+fn simulate_withdraw_liquidity(
+    reserves: &Pool,
+    lp_amount: Uint128,
+) -> StdResult<(Uint128, Uint128)> {
+    if lp_amount.is_zero() || lp_amount > reserves.lp_supply {
+        return Err(StdError::generic_err(format!(
+            "LP amount must be above 0 and at most the LP supply of {}",
+            reserves.lp_supply
+        )));
+    }
+
+    Ok((
+        reserves
+            .scrt_reserve
+            .multiply_ratio(lp_amount.u128(), reserves.lp_supply.u128()),
+        reserves
+            .token_reserve
+            .multiply_ratio(lp_amount.u128(), reserves.lp_supply.u128()),
+    ))
+}
+
+fn validate_swap_fee(swap_fee: Decimal) -> StdResult<()> {
+    if swap_fee >= Decimal::one() {
+        return Err(StdError::generic_err("Swap fee must be below 100%"));
+    }
+
+    Ok(())
+}
+
+/// Adds up the SCRT sent along with a message, failing if other coins were sent.
+fn sent_scrt(env: &Env) -> StdResult<Uint128> {
+    let mut amount = 0;
+    for coin in &env.message.sent_funds {
+        if coin.denom != SCRT_DENOM && !coin.amount.is_zero() {
+            return Err(StdError::generic_err(format!(
+                "Only {} can be sent to the pool",
+                SCRT_DENOM
+            )));
+        }
+        amount += coin.amount.u128();
+    }
+
+    Ok(Uint128(amount))
+}
+
+fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    // Newton's method, starting above the root so that it decreases to it.
+    let mut root = value;
+    let mut next = value / 2 + 1;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    root
+}
+
+fn register_receive_msg(env: &Env, token: &Contract) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(&Snip20HandleMsg::RegisterReceive {
+            code_hash: env.contract_code_hash.clone(),
+            padding: None,
+        })?,
+        send: vec![],
+    }))
+}
+
+fn transfer_msg(token: &Contract, recipient: HumanAddr, amount: Uint128) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(&Snip20HandleMsg::Transfer {
+            recipient,
+            amount,
+            memo: None,
+            padding: None,
+        })?,
+        send: vec![],
+    }))
+}
+
+fn scrt_msg(env: &Env, recipient: HumanAddr, amount: Uint128) -> CosmosMsg {
+    CosmosMsg::Bank(BankMsg::Send {
+        from_address: env.contract.address.clone(),
+        to_address: recipient,
+        amount: vec![Coin {
+            denom: SCRT_DENOM.to_string(),
+            amount,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::coins;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+
+    fn init_pool() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            mock_env("admin", &[]),
+            InitMsg {
+                admin: None,
+                token: token(),
+                lp_token_code_id: 9,
+                lp_token_code_hash: "lp_hash".to_string(),
+                lp_token_name: "TTX-SCRT liquidity".to_string(),
+                lp_token_symbol: "TTXLP".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                swap_fee: Decimal::permille(3),
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            mock_env("lp_token", &[]),
+            HandleMsg::RegisterLpToken {},
+        )
+        .unwrap();
+
+        deps
+    }
+
+    fn token() -> Contract {
+        Contract {
+            address: HumanAddr("token".to_string()),
+            code_hash: "token_hash".to_string(),
+        }
+    }
+
+    fn receive(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        token: &str,
+        from: &str,
+        amount: u128,
+        msg: ReceiveMsg,
+    ) -> StdResult<HandleResponse> {
+        handle(
+            deps,
+            mock_env(token, &[]),
+            HandleMsg::Receive {
+                sender: HumanAddr(from.to_string()),
+                from: HumanAddr(from.to_string()),
+                amount: Uint128(amount),
+                memo: None,
+                msg: Some(to_binary(&msg).unwrap()),
+            },
+        )
+    }
+
+    fn provide_liquidity(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        provider: &str,
+        scrt_amount: u128,
+        token_amount: u128,
+    ) -> StdResult<HandleResponse> {
+        handle(
+            deps,
+            mock_env(provider, &coins(scrt_amount, SCRT_DENOM)),
+            HandleMsg::ProvideLiquidity {
+                token_amount: Uint128(token_amount),
+                min_lp_amount: None,
+                padding: None,
+            },
+        )
+    }
+
+    fn reserves(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> Pool {
+        match from_binary(&query(deps, QueryMsg::Pool {}).unwrap()).unwrap() {
+            QueryAnswer::Pool {
+                scrt_reserve,
+                token_reserve,
+                lp_supply,
+            } => Pool {
+                scrt_reserve,
+                token_reserve,
+                lp_supply,
+            },
+            _ => panic!("unexpected answer"),
+        }
+    }
+
+    fn simulate(deps: &Extern<MockStorage, MockApi, MockQuerier>, msg: QueryMsg) -> QueryAnswer {
+        from_binary(&query(deps, msg).unwrap()).unwrap()
+    }
+
+    fn assert_generic_err(result: StdResult<HandleResponse>, expected: &str) {
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.contains(expected), "{}", msg),
+            other => panic!("expected error {:?}, got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn test_init_instantiates_lp_token() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("admin", &[]);
+        let response = init(
+            &mut deps,
+            env.clone(),
+            InitMsg {
+                admin: None,
+                token: token(),
+                lp_token_code_id: 9,
+                lp_token_code_hash: "lp_hash".to_string(),
+                lp_token_name: "TTX-SCRT liquidity".to_string(),
+                lp_token_symbol: "TTXLP".to_string(),
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                swap_fee: Decimal::permille(3),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages[0],
+            register_receive_msg(&env, &token()).unwrap()
+        );
+        match &response.messages[1] {
+            CosmosMsg::Wasm(WasmMsg::Instantiate {
+                code_id,
+                callback_code_hash,
+                msg,
+                ..
+            }) => {
+                assert_eq!(*code_id, 9);
+                assert_eq!(callback_code_hash, "lp_hash");
+                let lp_token_msg: snipix::msg::InitMsg = from_binary(msg).unwrap();
+                assert_eq!(lp_token_msg.admin, Some(env.contract.address.clone()));
+                assert_eq!(
+                    lp_token_msg.init_callback.unwrap().msg,
+                    to_binary(&HandleMsg::RegisterLpToken {}).unwrap()
+                );
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        assert_generic_err(
+            provide_liquidity(&mut deps, "alice", 1000, 1000),
+            "The LP token is not registered yet",
+        );
+        let register = handle(
+            &mut deps,
+            mock_env("lp_token", &[]),
+            HandleMsg::RegisterLpToken {},
+        )
+        .unwrap();
+        assert_eq!(
+            register.messages,
+            vec![register_receive_msg(
+                &env,
+                &Contract {
+                    address: HumanAddr("lp_token".to_string()),
+                    code_hash: "lp_hash".to_string(),
+                }
+            )
+            .unwrap()]
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("impostor", &[]),
+                HandleMsg::RegisterLpToken {},
+            ),
+            "The LP token is already registered",
+        );
+    }
+
+    #[test]
+    fn test_provide_swap_and_withdraw() {
+        let mut deps = init_pool();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("bob", &coins(10_000, SCRT_DENOM)),
+                HandleMsg::Swap {
+                    min_return: None,
+                    to: None,
+                    padding: None,
+                },
+            ),
+            "The pool has no liquidity yet",
+        );
+        assert_generic_err(
+            provide_liquidity(&mut deps, "alice", 1_000_000, 2_000_000),
+            "insufficient deposit: deposit=0, required=2000000",
+        );
+        receive(
+            &mut deps,
+            "token",
+            "alice",
+            2_000_000,
+            ReceiveMsg::Deposit {},
+        )
+        .unwrap();
+        let provide = provide_liquidity(&mut deps, "alice", 1_000_000, 2_000_000).unwrap();
+        // The first provider gets the geometric mean of the amounts in shares.
+        assert_eq!(
+            from_binary::<HandleAnswer>(&provide.data.unwrap()).unwrap(),
+            HandleAnswer::ProvideLiquidity {
+                lp_amount: Uint128(1_414_213),
+                scrt_amount: Uint128(1_000_000),
+                token_amount: Uint128(2_000_000),
+            }
+        );
+        assert_eq!(provide.messages.len(), 1);
+
+        // A 0.3% fee is taken from the 10000 uscrt offered.
+        let swap = HandleMsg::Swap {
+            min_return: Some(Uint128(20_000)),
+            to: None,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, mock_env("bob", &coins(10_000, SCRT_DENOM)), swap),
+            "Swap returns 19743 which is below the minimum return of 20000",
+        );
+        let swap = handle(
+            &mut deps,
+            mock_env("bob", &coins(10_000, SCRT_DENOM)),
+            HandleMsg::Swap {
+                min_return: Some(Uint128(19_000)),
+                to: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            swap.messages,
+            vec![transfer_msg(&token(), HumanAddr("bob".to_string()), Uint128(19_743)).unwrap()]
+        );
+
+        let simulated = simulate(
+            &deps,
+            QueryMsg::SimulateSwap {
+                offer: Asset::Token,
+                amount: Uint128(19_743),
+            },
+        );
+        let swap = receive(
+            &mut deps,
+            "token",
+            "bob",
+            19_743,
+            ReceiveMsg::Swap {
+                min_return: None,
+                to: Some(HumanAddr("carol".to_string())),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            simulated,
+            QueryAnswer::SimulateSwap {
+                return_amount: Uint128(9_940),
+                fee_amount: Uint128(59),
+            }
+        );
+        assert_eq!(
+            swap.messages,
+            vec![scrt_msg(
+                &mock_env("token", &[]),
+                HumanAddr("carol".to_string()),
+                Uint128(9_940)
+            )]
+        );
+        // Fees stay in the pool, so the product of the reserves grew.
+        assert_eq!(
+            reserves(&deps),
+            Pool {
+                scrt_reserve: Uint128(1_000_060),
+                token_reserve: Uint128(2_000_000),
+                lp_supply: Uint128(1_414_213),
+            }
+        );
+
+        // carol brings too much SCRT for her tokens, and gets the rest back.
+        receive(&mut deps, "token", "carol", 5_000, ReceiveMsg::Deposit {}).unwrap();
+        let provide = provide_liquidity(&mut deps, "carol", 5_000, 5_000).unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&provide.data.unwrap()).unwrap(),
+            HandleAnswer::ProvideLiquidity {
+                lp_amount: Uint128(3_535),
+                scrt_amount: Uint128(2_500),
+                token_amount: Uint128(5_000),
+            }
+        );
+        assert_eq!(
+            provide.messages[1],
+            scrt_msg(
+                &mock_env("carol", &[]),
+                HumanAddr("carol".to_string()),
+                Uint128(2_500)
+            )
+        );
+
+        let pool_before = reserves(&deps);
+        let simulated = simulate(
+            &deps,
+            QueryMsg::SimulateWithdrawLiquidity {
+                lp_amount: Uint128(1_414_213),
+            },
+        );
+        let (scrt_amount, token_amount) = match simulated {
+            QueryAnswer::SimulateWithdrawLiquidity {
+                scrt_amount,
+                token_amount,
+            } => (scrt_amount, token_amount),
+            _ => panic!("unexpected answer"),
+        };
+        assert_generic_err(
+            receive(
+                &mut deps,
+                "lp_token",
+                "alice",
+                1_414_213,
+                ReceiveMsg::WithdrawLiquidity {
+                    min_scrt: None,
+                    min_token: Some(Uint128(token_amount.u128() + 1)),
+                },
+            ),
+            "which is below the minimum",
+        );
+        let withdraw = receive(
+            &mut deps,
+            "lp_token",
+            "alice",
+            1_414_213,
+            ReceiveMsg::WithdrawLiquidity {
+                min_scrt: Some(scrt_amount),
+                min_token: Some(token_amount),
+            },
+        )
+        .unwrap();
+        assert_eq!(withdraw.messages.len(), 3);
+        assert_eq!(
+            withdraw.messages[2],
+            transfer_msg(&token(), HumanAddr("alice".to_string()), token_amount).unwrap()
+        );
+        assert_eq!(
+            reserves(&deps),
+            Pool {
+                scrt_reserve: Uint128(pool_before.scrt_reserve.u128() - scrt_amount.u128()),
+                token_reserve: Uint128(pool_before.token_reserve.u128() - token_amount.u128()),
+                lp_supply: Uint128(3_535),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pool_guards() {
+        let mut deps = init_pool();
+
+        assert_generic_err(
+            receive(
+                &mut deps,
+                "other_token",
+                "alice",
+                100,
+                ReceiveMsg::Deposit {},
+            ),
+            "Only the pool tokens can be sent to the pool",
+        );
+        assert_generic_err(
+            receive(&mut deps, "lp_token", "alice", 100, ReceiveMsg::Deposit {}),
+            "LP shares can only be withdrawn as liquidity",
+        );
+        assert_generic_err(
+            receive(
+                &mut deps,
+                "token",
+                "alice",
+                100,
+                ReceiveMsg::WithdrawLiquidity {
+                    min_scrt: None,
+                    min_token: None,
+                },
+            ),
+            "Only LP shares can be withdrawn as liquidity",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("alice", &coins(100, "uatom")),
+                HandleMsg::ProvideLiquidity {
+                    token_amount: Uint128::zero(),
+                    min_lp_amount: None,
+                    padding: None,
+                },
+            ),
+            "Only uscrt can be sent to the pool",
+        );
+
+        receive(&mut deps, "token", "alice", 400, ReceiveMsg::Deposit {}).unwrap();
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("alice", &coins(100, SCRT_DENOM)),
+                HandleMsg::ProvideLiquidity {
+                    token_amount: Uint128(400),
+                    min_lp_amount: Some(Uint128(201)),
+                    padding: None,
+                },
+            ),
+            "Providing liquidity mints 200 LP shares which is below the minimum of 201",
+        );
+        let withdraw = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::WithdrawDeposit { padding: None },
+        )
+        .unwrap();
+        assert_eq!(
+            withdraw.messages,
+            vec![transfer_msg(&token(), HumanAddr("alice".to_string()), Uint128(400)).unwrap()]
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("alice", &[]),
+                HandleMsg::WithdrawDeposit { padding: None },
+            ),
+            "No tokens are deposited",
+        );
+
+        let set_swap_fee = |swap_fee| HandleMsg::SetSwapFee {
+            swap_fee,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("alice", &[]),
+                set_swap_fee(Decimal::percent(1)),
+            ),
+            "This is an admin command",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("admin", &[]),
+                set_swap_fee(Decimal::one()),
+            ),
+            "Swap fee must be below 100%",
+        );
+        handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            set_swap_fee(Decimal::percent(1)),
+        )
+        .unwrap();
+        match simulate(&deps, QueryMsg::Config {}) {
+            QueryAnswer::Config { swap_fee, .. } => assert_eq!(swap_fee, Decimal::percent(1)),
+            _ => panic!("unexpected answer"),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Native denom the pool trades against.
+pub const SCRT_DENOM: &str = "uscrt";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct Contract {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct InitMsg {
+    pub admin: Option<HumanAddr>,
+    /// SNIP-20 token traded against SCRT.
+    pub token: Contract,
+    /// Snipix code the LP token is instantiated from. The pool is its admin, so it alone can
+    /// mint and burn LP shares.
+    pub lp_token_code_id: u64,
+    pub lp_token_code_hash: String,
+    pub lp_token_name: String,
+    pub lp_token_symbol: String,
+    pub prng_seed: Binary,
+    /// Part of what swaps offer that stays in the pool, for liquidity providers.
+    pub swap_fee: Decimal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// Called back by the LP token once instantiated.
+    RegisterLpToken {},
+    /// Sent by the token or the LP token when they are sent to the pool, with a `ReceiveMsg`.
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
+    },
+    /// Swaps the SCRT sent for tokens, sent to `to` or else the sender. Fails if that returns
+    /// less than `min_return`.
+    Swap {
+        min_return: Option<Uint128>,
+        to: Option<HumanAddr>,
+        padding: Option<String>,
+    },
+    /// Adds the SCRT sent and up to `token_amount` of the sender's deposit to the pool, in the
+    /// ratio of its reserves, for LP shares. SCRT left over is sent back, tokens left over stay
+    /// deposited. Fails if that mints less than `min_lp_amount`.
+    ProvideLiquidity {
+        token_amount: Uint128,
+        min_lp_amount: Option<Uint128>,
+        padding: Option<String>,
+    },
+    /// Sends back the tokens the sender deposited but did not provide.
+    WithdrawDeposit { padding: Option<String> },
+    SetSwapFee {
+        swap_fee: Decimal,
+        padding: Option<String>,
+    },
+    ChangeAdmin {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Deposits the tokens received, to be provided with `ProvideLiquidity`.
+    Deposit {},
+    /// Swaps the tokens received for SCRT, sent to `to` or else their sender.
+    Swap {
+        min_return: Option<Uint128>,
+        to: Option<HumanAddr>,
+    },
+    /// Burns the LP shares received for their part of the reserves.
+    WithdrawLiquidity {
+        min_scrt: Option<Uint128>,
+        min_token: Option<Uint128>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Asset {
+    Scrt,
+    Token,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    RegisterLpToken {
+        status: ResponseStatus,
+    },
+    Receive {
+        status: ResponseStatus,
+    },
+    Swap {
+        return_amount: Uint128,
+        fee_amount: Uint128,
+    },
+    ProvideLiquidity {
+        lp_amount: Uint128,
+        scrt_amount: Uint128,
+        token_amount: Uint128,
+    },
+    WithdrawDeposit {
+        amount: Uint128,
+    },
+    SetSwapFee {
+        status: ResponseStatus,
+    },
+    ChangeAdmin {
+        status: ResponseStatus,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Pool {},
+    SimulateSwap {
+        offer: Asset,
+        amount: Uint128,
+    },
+    SimulateProvideLiquidity {
+        scrt_amount: Uint128,
+        token_amount: Uint128,
+    },
+    SimulateWithdrawLiquidity {
+        lp_amount: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    Config {
+        admin: HumanAddr,
+        token: Contract,
+        lp_token: Option<Contract>,
+        swap_fee: Decimal,
+    },
+    Pool {
+        scrt_reserve: Uint128,
+        token_reserve: Uint128,
+        lp_supply: Uint128,
+    },
+    SimulateSwap {
+        return_amount: Uint128,
+        fee_amount: Uint128,
+    },
+    /// Amounts `ProvideLiquidity` would use, and the LP shares it would mint for them.
+    SimulateProvideLiquidity {
+        lp_amount: Uint128,
+        scrt_amount: Uint128,
+        token_amount: Uint128,
+    },
+    SimulateWithdrawLiquidity {
+        scrt_amount: Uint128,
+        token_amount: Uint128,
+    },
+}
@@ -0,0 +1,58 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, Decimal, Storage, Uint128};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+
+use crate::msg::Contract;
+
+pub static CONFIG_KEY: &[u8] = b"config";
+pub static POOL_KEY: &[u8] = b"pool";
+pub static DEPOSITS_KEY: &[u8] = b"deposits";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub admin: CanonicalAddr,
+    pub token: Contract,
+    /// Set once the LP token calls back after being instantiated.
+    pub lp_token: Option<Contract>,
+    pub lp_token_code_hash: String,
+    pub swap_fee: Decimal,
+}
+
+/// Reserves are tracked rather than read from balances, so that deposits and funds sent by
+/// mistake are not traded.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Pool {
+    pub scrt_reserve: Uint128,
+    pub token_reserve: Uint128,
+    pub lp_supply: Uint128,
+}
+
+pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, Config> {
+    singleton(storage, CONFIG_KEY)
+}
+
+pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Config> {
+    singleton_read(storage, CONFIG_KEY)
+}
+
+pub fn pool<S: Storage>(storage: &mut S) -> Singleton<S, Pool> {
+    singleton(storage, POOL_KEY)
+}
+
+pub fn pool_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Pool> {
+    singleton_read(storage, POOL_KEY)
+}
+
+/// Tokens deposited but not provided yet, by depositor.
+pub fn deposits<S: Storage>(storage: &mut S) -> Bucket<S, Uint128> {
+    bucket(DEPOSITS_KEY, storage)
+}
+
+pub fn deposits_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Uint128> {
+    bucket_read(DEPOSITS_KEY, storage)
+}
@@ -0,0 +1,440 @@
+use crate::{
+    lists::{page_ids, push_id, remove_id},
+    msg::{
+        AllowanceInfo, AllowancePeriod, HandleAnswer, HandleMsg, QueryAnswer,
+        RecurringAllowanceInfo,
+    },
+};
+use atl_snip20_reference_impl::{
+    msg::ResponseStatus,
+    state::{read_allowance, write_allowance, Allowance},
+};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+use serde::{Deserialize, Serialize};
+
+/// An allowance of `amount` per period, which takes precedence over the flat allowance the
+/// owner may have given to the same spender.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecurringAllowance {
+    pub amount: Uint128,
+    pub period: AllowancePeriod,
+    pub max_per_tx: Option<Uint128>,
+    /// Block height or time, depending on `period`, at which the current period started.
+    pub period_start: u64,
+    pub spent: Uint128,
+}
+
+impl From<RecurringAllowance> for RecurringAllowanceInfo {
+    fn from(allowance: RecurringAllowance) -> Self {
+        RecurringAllowanceInfo {
+            amount: allowance.amount,
+            period: allowance.period,
+            max_per_tx: allowance.max_per_tx,
+            period_start: allowance.period_start,
+            spent: allowance.spent,
+        }
+    }
+}
+
+/// Positions of a spender in the owner's list of given allowances, and of the owner in the
+/// spender's list of received allowances.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AllowanceIndex {
+    pub given_index: u32,
+    pub received_index: u32,
+}
+
+impl RecurringAllowance {
+    /// Starts a new period, and forgets what was spent, once the current one is over.
+    fn refresh(&mut self, env: &Env) {
+        let (now, length) = match self.period {
+            AllowancePeriod::Blocks(blocks) => (env.block.height, blocks),
+            AllowancePeriod::Seconds(seconds) => (env.block.time, seconds),
+        };
+
+        if now >= self.period_start.saturating_add(length) {
+            self.period_start = now - (now - self.period_start) % length;
+            self.spent = Uint128::zero();
+        }
+    }
+}
+
+pub fn handle_set_recurring_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    amount: Uint128,
+    period: AllowancePeriod,
+    max_per_tx: Option<Uint128>,
+) -> StdResult<HandleResponse> {
+    let period_start = match period {
+        AllowancePeriod::Blocks(0) | AllowancePeriod::Seconds(0) => {
+            return Err(StdError::generic_err("Allowance period cannot be empty"));
+        }
+        AllowancePeriod::Blocks(_) => env.block.height,
+        AllowancePeriod::Seconds(_) => env.block.time,
+    };
+
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let spender = deps.api.canonical_address(&spender)?;
+    write_recurring_allowances(&mut deps.storage, &owner).save(
+        spender.as_slice(),
+        &RecurringAllowance {
+            amount,
+            period,
+            max_per_tx,
+            period_start,
+            spent: Uint128::zero(),
+        },
+    )?;
+    update_allowance_index(&mut deps.storage, &owner, &spender)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetRecurringAllowance {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_remove_recurring_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let spender = deps.api.canonical_address(&spender)?;
+    write_recurring_allowances(&mut deps.storage, &owner).remove(spender.as_slice());
+    update_allowance_index(&mut deps.storage, &owner, &spender)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveRecurringAllowance {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn query_recurring_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &HumanAddr,
+    spender: &HumanAddr,
+) -> StdResult<Binary> {
+    let owner_raw = deps.api.canonical_address(owner)?;
+    let spender_raw = deps.api.canonical_address(spender)?;
+    let allowance = read_recurring_allowances(&deps.storage, &owner_raw)
+        .may_load(spender_raw.as_slice())?
+        .map(RecurringAllowanceInfo::from);
+
+    to_binary(&QueryAnswer::RecurringAllowance {
+        owner: owner.clone(),
+        spender: spender.clone(),
+        allowance,
+    })
+}
+
+/// Lists the allowances `owner` has given, most recently indexed first.
+pub fn query_allowances_given<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let owner_raw = deps.api.canonical_address(owner)?;
+    let (spenders, total) = page_ids(
+        &deps.storage,
+        PREFIX_ALLOWANCES_GIVEN,
+        &owner_raw,
+        page,
+        page_size,
+    )?;
+
+    let allowances = spenders
+        .into_iter()
+        .map(|spender| allowance_info(deps, &owner_raw, &CanonicalAddr(Binary(spender))))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::AllowancesGiven { allowances, total })
+}
+
+/// Lists the allowances `spender` has received, most recently indexed first.
+pub fn query_allowances_received<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    spender: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let spender_raw = deps.api.canonical_address(spender)?;
+    let (owners, total) = page_ids(
+        &deps.storage,
+        PREFIX_ALLOWANCES_RECEIVED,
+        &spender_raw,
+        page,
+        page_size,
+    )?;
+
+    let allowances = owners
+        .into_iter()
+        .map(|owner| allowance_info(deps, &CanonicalAddr(Binary(owner)), &spender_raw))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::AllowancesReceived { allowances, total })
+}
+
+fn allowance_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+) -> StdResult<AllowanceInfo> {
+    let allowance = read_allowance(&deps.storage, owner, spender)?;
+    let recurring = read_recurring_allowances(&deps.storage, owner).may_load(spender.as_slice())?;
+
+    Ok(AllowanceInfo {
+        owner: deps.api.human_address(owner)?,
+        spender: deps.api.human_address(spender)?,
+        allowance: Uint128(allowance.amount),
+        expiration: allowance.expiration,
+        recurring: recurring.map(RecurringAllowanceInfo::from),
+    })
+}
+
+/// Lists the (owner, spender) pairs whose allowance may change when `msg` is handled by the
+/// reference implementation.
+pub fn affected_allowances(env: &Env, msg: &HandleMsg) -> StdResult<Vec<(HumanAddr, HumanAddr)>> {
+    let sender = &env.message.sender;
+
+    Ok(match msg {
+        HandleMsg::IncreaseAllowance { spender, .. }
+        | HandleMsg::DecreaseAllowance { spender, .. } => vec![(sender.clone(), spender.clone())],
+        msg => spends(msg)?
+            .into_iter()
+            .map(|(owner, _)| (owner, sender.clone()))
+            .collect(),
+    })
+}
+
+pub fn update_allowance_indexes<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    pairs: &[(HumanAddr, HumanAddr)],
+) -> StdResult<()> {
+    for (owner, spender) in pairs {
+        let owner = deps.api.canonical_address(owner)?;
+        let spender = deps.api.canonical_address(spender)?;
+        update_allowance_index(&mut deps.storage, &owner, &spender)?;
+    }
+
+    Ok(())
+}
+
+/// Keeps the pair listed for both the owner and the spender for as long as there is an
+/// allowance between them.
+pub fn update_allowance_index<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    spender: &CanonicalAddr,
+) -> StdResult<()> {
+    let has_allowance = read_allowance(&*storage, owner, spender)?.amount > 0
+        || read_recurring_allowances(&*storage, owner)
+            .may_load(spender.as_slice())?
+            .is_some();
+    let index = read_allowance_indexes(&*storage, owner).may_load(spender.as_slice())?;
+
+    match (has_allowance, index) {
+        (true, None) => {
+            let index = AllowanceIndex {
+                given_index: push_id(storage, PREFIX_ALLOWANCES_GIVEN, owner, spender.as_slice())?,
+                received_index: push_id(
+                    storage,
+                    PREFIX_ALLOWANCES_RECEIVED,
+                    spender,
+                    owner.as_slice(),
+                )?,
+            };
+            write_allowance_indexes(storage, owner).save(spender.as_slice(), &index)?;
+        }
+        (false, Some(index)) => {
+            write_allowance_indexes(storage, owner).remove(spender.as_slice());
+
+            let moved = remove_id(storage, PREFIX_ALLOWANCES_GIVEN, owner, index.given_index)?;
+            if let Some(moved_spender) = moved {
+                let mut moved_index = load_allowance_index(&*storage, owner, &moved_spender)?;
+                moved_index.given_index = index.given_index;
+                write_allowance_indexes(storage, owner).save(&moved_spender, &moved_index)?;
+            }
+
+            let moved = remove_id(
+                storage,
+                PREFIX_ALLOWANCES_RECEIVED,
+                spender,
+                index.received_index,
+            )?;
+            if let Some(moved_owner) = moved {
+                let moved_owner = CanonicalAddr(Binary(moved_owner));
+                let mut moved_index =
+                    load_allowance_index(&*storage, &moved_owner, spender.as_slice())?;
+                moved_index.received_index = index.received_index;
+                write_allowance_indexes(storage, &moved_owner)
+                    .save(spender.as_slice(), &moved_index)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn load_allowance_index<S: ReadonlyStorage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    spender: &[u8],
+) -> StdResult<AllowanceIndex> {
+    read_allowance_indexes(storage, owner)
+        .may_load(spender)?
+        .ok_or_else(|| StdError::generic_err("Allowance index not found"))
+}
+
+/// Checks the spends in `msg` against the recurring allowances the owners gave to the sender,
+/// and temporarily grants a matching flat allowance for the reference implementation to consume.
+/// Returns the flat allowances to put back with `restore_allowances` once `msg` was handled.
+pub fn grant_recurring_allowances<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    msg: &HandleMsg,
+) -> StdResult<Vec<(CanonicalAddr, Allowance)>> {
+    let spender = deps.api.canonical_address(&env.message.sender)?;
+
+    let mut replaced = vec![];
+    for (owner, amount) in spends(msg)? {
+        let owner = deps.api.canonical_address(&owner)?;
+        let mut allowance =
+            match read_recurring_allowances(&deps.storage, &owner).may_load(spender.as_slice())? {
+                Some(allowance) => allowance,
+                None => continue,
+            };
+
+        if let Some(max_per_tx) = allowance.max_per_tx {
+            if amount > max_per_tx.u128() {
+                return Err(StdError::generic_err(format!(
+                    "Transaction exceeds the allowed maximum: max={}, required={}",
+                    max_per_tx, amount
+                )));
+            }
+        }
+
+        allowance.refresh(env);
+        let remaining = allowance.amount.u128() - allowance.spent.u128();
+        if amount > remaining {
+            return Err(StdError::generic_err(format!(
+                "Recurring allowance exceeded for this period: remaining={}, required={}",
+                remaining, amount
+            )));
+        }
+        allowance.spent = Uint128(allowance.spent.u128() + amount);
+        write_recurring_allowances(&mut deps.storage, &owner)
+            .save(spender.as_slice(), &allowance)?;
+
+        replaced.push((
+            owner.clone(),
+            read_allowance(&deps.storage, &owner, &spender)?,
+        ));
+        write_allowance(
+            &mut deps.storage,
+            &owner,
+            &spender,
+            Allowance {
+                amount,
+                expiration: None,
+            },
+        )?;
+    }
+
+    Ok(replaced)
+}
+
+pub fn restore_allowances<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    replaced: Vec<(CanonicalAddr, Allowance)>,
+) -> StdResult<()> {
+    let spender = deps.api.canonical_address(&env.message.sender)?;
+    for (owner, allowance) in replaced {
+        write_allowance(&mut deps.storage, &owner, &spender, allowance)?;
+    }
+
+    Ok(())
+}
+
+/// Totals the amounts `msg` spends from each owner's allowance.
+fn spends(msg: &HandleMsg) -> StdResult<Vec<(HumanAddr, u128)>> {
+    let actions: Vec<(&HumanAddr, Uint128)> = match msg {
+        HandleMsg::TransferFrom { owner, amount, .. }
+        | HandleMsg::SendFrom { owner, amount, .. }
+        | HandleMsg::BurnFrom { owner, amount, .. } => vec![(owner, *amount)],
+        HandleMsg::BatchTransferFrom { actions, .. } => actions
+            .iter()
+            .map(|action| (&action.owner, action.amount))
+            .collect(),
+        HandleMsg::BatchSendFrom { actions, .. } => actions
+            .iter()
+            .map(|action| (&action.owner, action.amount))
+            .collect(),
+        HandleMsg::BatchBurnFrom { actions, .. } => actions
+            .iter()
+            .map(|action| (&action.owner, action.amount))
+            .collect(),
+        _ => vec![],
+    };
+
+    let mut totals: Vec<(HumanAddr, u128)> = vec![];
+    for (owner, amount) in actions {
+        match totals.iter_mut().find(|(account, _)| account == owner) {
+            Some((_, total)) => {
+                *total = total
+                    .checked_add(amount.u128())
+                    .ok_or_else(|| StdError::generic_err("Spent amounts overflow"))?
+            }
+            None => totals.push((owner.clone(), amount.u128())),
+        }
+    }
+
+    Ok(totals)
+}
+
+pub const PREFIX_RECURRING_ALLOWANCES: &[u8] = b"recurring_allowances";
+pub const PREFIX_ALLOWANCE_INDEXES: &[u8] = b"allowance_indexes";
+pub const PREFIX_ALLOWANCES_GIVEN: &[u8] = b"allowances_given";
+pub const PREFIX_ALLOWANCES_RECEIVED: &[u8] = b"allowances_received";
+
+pub fn read_recurring_allowances<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    owner: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, RecurringAllowance> {
+    ReadonlyBucket::multilevel(&[PREFIX_RECURRING_ALLOWANCES, owner.as_slice()], storage)
+}
+
+pub fn write_recurring_allowances<'a, S: Storage>(
+    storage: &'a mut S,
+    owner: &CanonicalAddr,
+) -> Bucket<'a, S, RecurringAllowance> {
+    Bucket::multilevel(&[PREFIX_RECURRING_ALLOWANCES, owner.as_slice()], storage)
+}
+
+pub fn read_allowance_indexes<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    owner: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, AllowanceIndex> {
+    ReadonlyBucket::multilevel(&[PREFIX_ALLOWANCE_INDEXES, owner.as_slice()], storage)
+}
+
+pub fn write_allowance_indexes<'a, S: Storage>(
+    storage: &'a mut S,
+    owner: &CanonicalAddr,
+) -> Bucket<'a, S, AllowanceIndex> {
+    Bucket::multilevel(&[PREFIX_ALLOWANCE_INDEXES, owner.as_slice()], storage)
+}
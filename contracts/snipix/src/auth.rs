@@ -0,0 +1,55 @@
+use crate::msg::QueryAnswer;
+use atl_snip20_reference_impl::{
+    contract::PREFIX_REVOKED_PERMITS,
+    state::{read_viewing_key, ReadonlyConfig},
+    viewing_key::{ViewingKey, VIEWING_KEY_SIZE},
+};
+use cosmwasm_std::{
+    to_binary, Api, Binary, Extern, HumanAddr, Querier, StdError, StdResult, Storage,
+};
+use secret_toolkit::permit::{validate, Permission, Permit};
+
+/// Checks `key` against the viewing key of `address`, taking the same time whether a key is set or not.
+pub fn is_valid_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    key: &str,
+) -> StdResult<bool> {
+    let address = deps.api.canonical_address(address)?;
+    let key = ViewingKey(key.to_string());
+
+    Ok(match read_viewing_key(&deps.storage, &address) {
+        Some(expected_key) => key.check_viewing_key(expected_key.as_slice()),
+        None => {
+            key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+            false
+        }
+    })
+}
+
+pub fn viewing_key_error() -> StdResult<Binary> {
+    to_binary(&QueryAnswer::ViewingKeyError {
+        msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+    })
+}
+
+/// Returns the address that signed `permit`, as long as it grants `permission`.
+pub fn validate_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: &Permit,
+    permission: Permission,
+) -> StdResult<HumanAddr> {
+    let token_address = ReadonlyConfig::from_storage(&deps.storage)
+        .constants()?
+        .contract_address;
+    let account = validate(deps, PREFIX_REVOKED_PERMITS, permit, token_address)?;
+
+    if !permit.check_permission(&permission) {
+        return Err(StdError::generic_err(format!(
+            "No permission to query, got permissions {:?}",
+            permit.params.permissions
+        )));
+    }
+
+    Ok(account)
+}
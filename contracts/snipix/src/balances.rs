@@ -0,0 +1,252 @@
+use crate::{
+    checkpoints::{balance_at, checkpoint_balance, checkpoint_total_supply},
+    delegation, dividends,
+    msg::HandleMsg,
+    transfer_fee::transfer_fee_for,
+};
+use atl_snip20_reference_impl::{
+    msg::ContractStatusLevel,
+    state::{Balances, ReadonlyBalances, ReadonlyConfig},
+    transaction_history::store_transfer,
+};
+use cosmwasm_std::{
+    Api, CanonicalAddr, Env, Extern, HumanAddr, Querier, ReadonlyStorage, StdError, StdResult,
+    Storage, Uint128,
+};
+use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+
+pub fn balance_of<S: ReadonlyStorage>(storage: &S, account: &CanonicalAddr) -> u128 {
+    ReadonlyBalances::from_storage(storage).account_amount(account)
+}
+
+/// Moves `amount` between two accounts outside of the reference implementation, e.g. into or
+/// out of an escrow held by the contract itself. The move shows up in the transfer history.
+///
+/// The sender pays the transfer fee on top, except on moves out of escrow, whose fee was paid when
+/// the tokens went in.
+pub fn move_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    amount: u128,
+    memo: Option<String>,
+) -> StdResult<()> {
+    transfer_balance(deps, env, from, to, amount, memo)?;
+    if from == &env.contract.address {
+        return Ok(());
+    }
+
+    match transfer_fee_for(&deps.storage, from, to, amount)? {
+        Some((collector, fee)) => transfer_balance(
+            deps,
+            env,
+            from,
+            &collector,
+            fee,
+            Some("Transfer fee".to_string()),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Moves `amount` between two accounts without charging any fee.
+pub fn transfer_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    amount: u128,
+    memo: Option<String>,
+) -> StdResult<()> {
+    let config = ReadonlyConfig::from_storage(&deps.storage);
+    if config.contract_status() != ContractStatusLevel::NormalRun {
+        return Err(StdError::generic_err(
+            "This contract is stopped and this action is not allowed",
+        ));
+    }
+    let symbol = config.constants()?.symbol;
+
+    let from_raw = deps.api.canonical_address(from)?;
+    let to_raw = deps.api.canonical_address(to)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let from_balance = balances.balance(&from_raw);
+    if from_balance < amount {
+        return Err(StdError::generic_err(format!(
+            "insufficient funds: balance={}, required={}",
+            from_balance, amount
+        )));
+    }
+    balances.set_account_balance(&from_raw, from_balance - amount);
+    let to_balance = balances.balance(&to_raw);
+    balances.set_account_balance(
+        &to_raw,
+        to_balance.checked_add(amount).ok_or_else(|| {
+            StdError::generic_err(
+                "This tx will literally make them too rich. Try transferring less",
+            )
+        })?,
+    );
+
+    if from == &env.contract.address {
+        check_escrow_covered(deps, env)?;
+    }
+
+    store_transfer(
+        &mut deps.storage,
+        &from_raw,
+        &from_raw,
+        &to_raw,
+        Uint128(amount),
+        symbol,
+        memo,
+        &env.block,
+    )?;
+
+    after_balance_change(deps, env, &[from.clone(), to.clone()])
+}
+
+/// Moves `amount` from `from` into escrow. Escrowed tokens sit in the contract's own balance,
+/// and are counted apart so that nothing the contract does as itself can spend them.
+pub fn lock_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    from: &HumanAddr,
+    amount: u128,
+    memo: Option<String>,
+) -> StdResult<()> {
+    move_balance(deps, env, from, &env.contract.address, amount, memo)?;
+    let escrowed = read_escrowed(&deps.storage).may_load()?.unwrap_or_default();
+    let escrowed = escrowed
+        .u128()
+        .checked_add(amount)
+        .ok_or_else(|| StdError::generic_err("Too many tokens are held in escrow"))?;
+    write_escrowed(&mut deps.storage).save(&Uint128(escrowed))
+}
+
+/// Moves `amount` out of escrow to `to`.
+pub fn release_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    to: &HumanAddr,
+    amount: u128,
+    memo: Option<String>,
+) -> StdResult<()> {
+    let escrowed = read_escrowed(&deps.storage).may_load()?.unwrap_or_default();
+    let escrowed = escrowed
+        .u128()
+        .checked_sub(amount)
+        .ok_or_else(|| StdError::generic_err("Cannot release more than is held in escrow"))?;
+    write_escrowed(&mut deps.storage).save(&Uint128(escrowed))?;
+    move_balance(deps, env, &env.contract.address, to, amount, memo)
+}
+
+/// Fails if the contract's balance no longer covers the tokens it holds in escrow, i.e. if the
+/// contract spent some of them as itself.
+pub fn check_escrow_covered<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<()> {
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+    let escrowed = read_escrowed(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        .u128();
+    let balance = balance_of(&deps.storage, &contract);
+    if balance < escrowed {
+        return Err(StdError::generic_err(format!(
+            "The contract cannot spend tokens held in escrow: balance={}, escrowed={}",
+            balance, escrowed
+        )));
+    }
+
+    Ok(())
+}
+
+/// Lists the accounts whose balance may change when `msg` is handled by the reference implementation.
+pub fn affected_accounts(env: &Env, msg: &HandleMsg) -> Vec<HumanAddr> {
+    let sender = env.message.sender.clone();
+
+    let mut accounts = match msg {
+        HandleMsg::Redeem { .. } | HandleMsg::Deposit { .. } | HandleMsg::Burn { .. } => {
+            vec![sender]
+        }
+        HandleMsg::Transfer { recipient, .. } | HandleMsg::Send { recipient, .. } => {
+            vec![sender, recipient.clone()]
+        }
+        HandleMsg::BatchTransfer { actions, .. } => std::iter::once(sender)
+            .chain(actions.iter().map(|action| action.recipient.clone()))
+            .collect(),
+        HandleMsg::BatchSend { actions, .. } => std::iter::once(sender)
+            .chain(actions.iter().map(|action| action.recipient.clone()))
+            .collect(),
+        HandleMsg::TransferFrom {
+            owner, recipient, ..
+        }
+        | HandleMsg::SendFrom {
+            owner, recipient, ..
+        } => vec![owner.clone(), recipient.clone()],
+        HandleMsg::BatchTransferFrom { actions, .. } => actions
+            .iter()
+            .flat_map(|action| vec![action.owner.clone(), action.recipient.clone()])
+            .collect(),
+        HandleMsg::BatchSendFrom { actions, .. } => actions
+            .iter()
+            .flat_map(|action| vec![action.owner.clone(), action.recipient.clone()])
+            .collect(),
+        HandleMsg::BurnFrom { owner, .. } => vec![owner.clone()],
+        HandleMsg::BatchBurnFrom { actions, .. } => {
+            actions.iter().map(|action| action.owner.clone()).collect()
+        }
+        HandleMsg::Mint { recipient, .. } => vec![recipient.clone()],
+        HandleMsg::BatchMint { actions, .. } => actions
+            .iter()
+            .map(|action| action.recipient.clone())
+            .collect(),
+        _ => vec![],
+    };
+
+    accounts.sort_by(|a, b| a.0.cmp(&b.0));
+    accounts.dedup();
+    accounts
+}
+
+/// Brings everything derived from balances up to date once `accounts` were updated.
+pub fn after_balance_change<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    accounts: &[HumanAddr],
+) -> StdResult<()> {
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+
+    for account in accounts {
+        let account = deps.api.canonical_address(account)?;
+        let previous = balance_at(&deps.storage, &account, env.block.height)?;
+        let balance = balance_of(&deps.storage, &account);
+
+        delegation::on_balance_change(
+            &mut deps.storage,
+            &account,
+            env.block.height,
+            previous,
+            balance,
+        )?;
+        dividends::on_balance_change(&mut deps.storage, &account, &contract, previous)?;
+        checkpoint_balance(&mut deps.storage, &account, env.block.height, balance)?;
+    }
+
+    let total_supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+    checkpoint_total_supply(&mut deps.storage, env.block.height, total_supply)
+}
+
+pub const PREFIX_ESCROWED: &[u8] = b"escrowed";
+
+/// Total amount held in escrow by claims, swaps, streams and escrows.
+pub fn read_escrowed<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, Uint128> {
+    singleton_read(storage, PREFIX_ESCROWED)
+}
+
+pub fn write_escrowed<S: Storage>(storage: &mut S) -> Singleton<S, Uint128> {
+    singleton(storage, PREFIX_ESCROWED)
+}
@@ -0,0 +1,137 @@
+use crate::msg::QueryAnswer;
+use atl_snip20_reference_impl::state::ReadonlyConfig;
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Extern, HumanAddr, Querier, ReadonlyStorage, StdError,
+    StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+use serde::{Deserialize, Serialize};
+
+pub const PREFIX_BALANCE_CHECKPOINTS: &[u8] = b"balance_checkpoints";
+pub const PREFIX_SUPPLY_CHECKPOINTS: &[u8] = b"supply_checkpoints";
+
+/// The value an amount took from `height` on, until the next checkpoint.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub height: u64,
+    pub amount: Uint128,
+}
+
+pub fn query_balance_at<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    height: u64,
+) -> StdResult<Binary> {
+    let account = deps.api.canonical_address(address)?;
+    let amount = balance_at(&deps.storage, &account, height)?;
+
+    to_binary(&QueryAnswer::BalanceAt {
+        amount: Uint128(amount),
+        height,
+    })
+}
+
+pub fn query_total_supply_at<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    height: u64,
+) -> StdResult<Binary> {
+    let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
+    if !constants.total_supply_is_public {
+        return Err(StdError::generic_err("Total supply is not public"));
+    }
+
+    let total_supply = total_supply_at(&deps.storage, height)?;
+
+    to_binary(&QueryAnswer::TotalSupplyAt {
+        total_supply: Uint128(total_supply),
+        height,
+    })
+}
+
+pub fn checkpoint_balance<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    height: u64,
+    amount: u128,
+) -> StdResult<()> {
+    let mut store =
+        PrefixedStorage::multilevel(&[PREFIX_BALANCE_CHECKPOINTS, account.as_slice()], storage);
+
+    push_checkpoint(&mut store, height, amount)
+}
+
+pub fn checkpoint_total_supply<S: Storage>(
+    storage: &mut S,
+    height: u64,
+    amount: u128,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_SUPPLY_CHECKPOINTS, storage);
+
+    push_checkpoint(&mut store, height, amount)
+}
+
+pub fn balance_at<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    height: u64,
+) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_BALANCE_CHECKPOINTS, account.as_slice()],
+        storage,
+    );
+
+    find_checkpoint(&store, height)
+}
+
+pub fn total_supply_at<S: ReadonlyStorage>(storage: &S, height: u64) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_SUPPLY_CHECKPOINTS, storage);
+
+    find_checkpoint(&store, height)
+}
+
+/// Records `amount` at `height` in the checkpoint list kept in `storage`.
+pub fn push_checkpoint<S: Storage>(storage: &mut S, height: u64, amount: u128) -> StdResult<()> {
+    let mut store = AppendStoreMut::<Checkpoint, _, _>::attach_or_create(storage)?;
+    let checkpoint = Checkpoint {
+        height,
+        amount: Uint128(amount),
+    };
+
+    if let Some(last) = store.len().checked_sub(1) {
+        let latest = store.get_at(last)?;
+        if latest.amount == checkpoint.amount {
+            return Ok(());
+        }
+        // Only the final value of a block is kept.
+        if latest.height == height {
+            return store.set_at(last, &checkpoint);
+        }
+    }
+
+    store.push(&checkpoint)
+}
+
+/// Finds the amount recorded by the last checkpoint at or before `height`.
+pub fn find_checkpoint<S: ReadonlyStorage>(storage: &S, height: u64) -> StdResult<u128> {
+    let store = match AppendStore::<Checkpoint, _, _>::attach(storage) {
+        Some(store) => store?,
+        None => return Ok(0),
+    };
+
+    let (mut low, mut high) = (0, store.len());
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.get_at(mid)?.height <= height {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    if low == 0 {
+        Ok(0)
+    } else {
+        Ok(store.get_at(low - 1)?.amount.u128())
+    }
+}
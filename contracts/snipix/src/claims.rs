@@ -0,0 +1,208 @@
+use crate::{
+    balances::{lock_escrow, release_escrow},
+    lists::{page_ids, push_id, remove_id},
+    msg::{ClaimInfo, HandleAnswer, QueryAnswer},
+};
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{bucket, bucket_read, Bucket, ReadonlyBucket};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Tokens escrowed by `creator` until someone presents the secret behind the claim's hash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Claim {
+    pub creator: CanonicalAddr,
+    pub amount: Uint128,
+    /// Seconds since epoch after which the claim can only be reclaimed by its creator.
+    pub expiration: u64,
+    /// Position of the claim in the creator's list of outstanding claims.
+    pub index: u32,
+}
+
+pub fn handle_create_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    hash: Binary,
+    amount: Uint128,
+    expiration: u64,
+) -> StdResult<HandleResponse> {
+    if hash.len() != 32 {
+        return Err(StdError::generic_err(
+            "Hash must be the SHA-256 digest of the secret",
+        ));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot create an empty claim"));
+    }
+    if expiration <= env.block.time {
+        return Err(StdError::generic_err("Expiration must be in the future"));
+    }
+    if read_claims(&deps.storage)
+        .may_load(hash.as_slice())?
+        .is_some()
+    {
+        return Err(StdError::generic_err(
+            "A claim with this hash already exists",
+        ));
+    }
+
+    lock_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        amount.u128(),
+        Some("Create claim".to_string()),
+    )?;
+
+    let creator = deps.api.canonical_address(&env.message.sender)?;
+    let index = push_id(
+        &mut deps.storage,
+        PREFIX_CREATOR_CLAIMS,
+        &creator,
+        hash.as_slice(),
+    )?;
+    write_claims(&mut deps.storage).save(
+        hash.as_slice(),
+        &Claim {
+            creator,
+            amount,
+            expiration,
+            index,
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateClaim {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    secret: String,
+) -> StdResult<HandleResponse> {
+    let hash = Sha256::digest(secret.as_bytes());
+    let claim = load_claim(&deps.storage, &hash)?;
+    if env.block.time >= claim.expiration {
+        return Err(StdError::generic_err("Claim has expired"));
+    }
+
+    remove_claim(&mut deps.storage, &hash, &claim)?;
+    release_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        claim.amount.u128(),
+        Some("Claim".to_string()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Claim {
+            amount: claim.amount,
+        })?),
+    })
+}
+
+pub fn handle_reclaim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    hash: Binary,
+) -> StdResult<HandleResponse> {
+    let claim = load_claim(&deps.storage, hash.as_slice())?;
+    if claim.creator != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the creator of a claim can reclaim it",
+        ));
+    }
+    if env.block.time < claim.expiration {
+        return Err(StdError::generic_err("Claim has not expired yet"));
+    }
+
+    remove_claim(&mut deps.storage, hash.as_slice(), &claim)?;
+    release_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        claim.amount.u128(),
+        Some("Reclaim".to_string()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Reclaim {
+            amount: claim.amount,
+        })?),
+    })
+}
+
+/// Lists the outstanding claims created by `address`, most recent first.
+pub fn query_claims<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let creator = deps.api.canonical_address(address)?;
+    let (hashes, total) = page_ids(
+        &deps.storage,
+        PREFIX_CREATOR_CLAIMS,
+        &creator,
+        page,
+        page_size,
+    )?;
+
+    let claims = hashes
+        .into_iter()
+        .map(|hash| {
+            let claim = load_claim(&deps.storage, &hash)?;
+            Ok(ClaimInfo {
+                hash: Binary(hash),
+                amount: claim.amount,
+                expiration: claim.expiration,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Claims { claims, total })
+}
+
+fn load_claim<S: ReadonlyStorage>(storage: &S, hash: &[u8]) -> StdResult<Claim> {
+    read_claims(storage)
+        .may_load(hash)?
+        .ok_or_else(|| StdError::generic_err("Claim not found"))
+}
+
+/// Drops the claim, along with its place in the creator's list.
+fn remove_claim<S: Storage>(storage: &mut S, hash: &[u8], claim: &Claim) -> StdResult<()> {
+    write_claims(storage).remove(hash);
+
+    if let Some(moved) = remove_id(storage, PREFIX_CREATOR_CLAIMS, &claim.creator, claim.index)? {
+        let mut moved_claim = load_claim(&*storage, &moved)?;
+        moved_claim.index = claim.index;
+        write_claims(storage).save(&moved, &moved_claim)?;
+    }
+
+    Ok(())
+}
+
+pub const PREFIX_CLAIMS: &[u8] = b"claims";
+pub const PREFIX_CREATOR_CLAIMS: &[u8] = b"creator_claims";
+
+pub fn read_claims<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Claim> {
+    bucket_read(PREFIX_CLAIMS, storage)
+}
+
+pub fn write_claims<S: Storage>(storage: &mut S) -> Bucket<S, Claim> {
+    bucket(PREFIX_CLAIMS, storage)
+}
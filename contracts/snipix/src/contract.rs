@@ -1,24 +1,130 @@
 use std::convert::TryInto;
 
 use crate::{
-    marketing_info::{handle_set_marketing_info, init_marketing_info, query_marketing_info},
-    msg::{HandleMsg, InitMsg, QueryMsg},
+    allowances::{
+        affected_allowances, grant_recurring_allowances, handle_remove_recurring_allowance,
+        handle_set_recurring_allowance, query_allowances_given, query_allowances_received,
+        query_recurring_allowance, restore_allowances, update_allowance_indexes,
+    },
+    auth::{is_valid_viewing_key, validate_permit, viewing_key_error},
+    balances::{affected_accounts, after_balance_change, check_escrow_covered},
+    checkpoints::{query_balance_at, query_total_supply_at},
+    claims::{handle_claim, handle_create_claim, handle_reclaim, query_claims},
+    delegation::{
+        handle_delegate, handle_set_voting_power_readers, handle_undelegate,
+        is_voting_power_reader, query_voting_power, query_voting_power_readers,
+    },
+    dividends::{
+        handle_add_reward_token, handle_claim_dividends, handle_deposit_dividends,
+        handle_receive_dividends, init_reward_tokens, query_reward_tokens,
+        query_unclaimed_dividends,
+    },
+    escrows::{
+        handle_create_escrow, handle_expire_escrow, handle_refund_escrow, handle_release_escrow,
+        handle_resolve_escrow, query_escrows,
+    },
+    governance::{
+        handle_cast_vote, handle_execute_proposal, handle_propose, init_governance,
+        query_governance_config, query_proposal, query_proposals,
+    },
+    htlc::{handle_claim_swap, handle_create_swap, handle_refund_swap, query_swap},
+    inheritance::{
+        handle_claim_inheritance, handle_remove_beneficiary, handle_set_beneficiary,
+        query_inheritance, record_activity,
+    },
+    invoices::{
+        handle_create_invoice, handle_pay_invoice, handle_refund_invoice, query_invoice,
+        query_invoices, record_invoice_send,
+    },
+    marketing_info::{
+        handle_set_marketing_info, init_factory, init_marketing_info, query_marketing_info,
+    },
+    migration::{
+        check_not_migrating, handle_complete_migration, handle_migrate, handle_receive_migration,
+        init_migration, is_migration_source,
+    },
+    msg::{HandleMsg, InitMsg, QueryMsg, QueryWithPermit},
+    schedules::{
+        handle_cancel_schedule, handle_create_schedule, handle_execute_schedules,
+        query_due_schedules, query_schedules,
+    },
+    streams::{
+        handle_cancel_stream, handle_create_stream, handle_withdraw_stream, init_vesting,
+        query_streams,
+    },
+    supply_cap::{check_supply_cap, init_supply_cap, minted_amount, query_supply_cap},
+    transfer_fee::{charge_transfer_fee, init_transfer_fee, query_transfer_fee},
+    transfer_permits::handle_transfer_with_permit,
 };
 pub use atl_snip20_reference_impl::contract::*;
 use cosmwasm_std::{
-    Api, Env, Extern, HandleResponse, InitResponse, Querier, QueryResult, StdResult, Storage,
+    Api, CosmosMsg, Env, Extern, HandleResponse, InitResponse, Querier, QueryResult, StdError,
+    StdResult, Storage, WasmMsg,
 };
+use secret_toolkit::permit::{Permission, Permit};
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    if msg.governance.is_some() && msg.admin.is_some() {
+        return Err(StdError::generic_err(
+            "Tokens under governance cannot have an admin",
+        ));
+    }
     if let Some(marketing_info) = msg.clone().marketing_info {
         init_marketing_info(deps, marketing_info)?;
     }
 
-    atl_snip20_reference_impl::contract::init(deps, env, msg.into())
+    let governance = msg.clone().governance;
+    let reward_tokens = msg.clone().reward_tokens;
+    let supply_cap = msg.supply_cap;
+    let transfer_fee = msg.clone().transfer_fee;
+    let vesting_period = msg.vesting_period;
+    let migrated_from = msg.clone().migrated_from;
+    let init_callback = msg.clone().init_callback;
+    let initial_balances = msg.clone().initial_balances.unwrap_or_default();
+    let initial_accounts: Vec<_> = initial_balances
+        .iter()
+        .map(|balance| balance.address.clone())
+        .collect();
+
+    let mut response = atl_snip20_reference_impl::contract::init(deps, env.clone(), msg.into())?;
+
+    after_balance_change(deps, &env, &initial_accounts)?;
+
+    if let Some(supply_cap) = supply_cap {
+        init_supply_cap(&mut deps.storage, supply_cap)?;
+    }
+    if let Some(transfer_fee) = transfer_fee {
+        init_transfer_fee(&mut deps.storage, transfer_fee)?;
+    }
+    if let Some(vesting_period) = vesting_period {
+        init_vesting(deps, &env, &initial_balances, vesting_period)?;
+    }
+    if let Some(governance) = governance {
+        init_governance(deps, &env, governance)?;
+    }
+    if let Some(reward_tokens) = reward_tokens {
+        response
+            .messages
+            .extend(init_reward_tokens(deps, &env, reward_tokens)?);
+    }
+    if let Some(source) = migrated_from {
+        response.messages.push(init_migration(deps, &env, source)?);
+    }
+    if let Some(callback) = init_callback {
+        init_factory(deps, &callback)?;
+        response.messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: callback.contract_addr,
+            callback_code_hash: callback.code_hash,
+            msg: callback.msg,
+            send: vec![],
+        }));
+    }
+
+    Ok(response)
 }
 
 pub fn handle<S: Storage, A: Api, Q: Querier>(
@@ -26,28 +132,540 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
+    check_not_migrating(&deps.storage, &msg)?;
+    record_activity(deps, &env, &env.message.sender)?;
+
     match msg {
-        HandleMsg::SetMarketingInfo { marketing_info, .. } => handle_set_marketing_info(deps, marketing_info),
-        msg => atl_snip20_reference_impl::contract::handle(deps, env, msg.try_into()?),
+        HandleMsg::SetMarketingInfo { marketing_info, .. } => {
+            handle_set_marketing_info(deps, marketing_info)
+        }
+        HandleMsg::Propose {
+            title,
+            description,
+            msgs,
+            ..
+        } => handle_propose(deps, env, title, description, msgs.unwrap_or_default()),
+        HandleMsg::CastVote {
+            proposal_id, vote, ..
+        } => handle_cast_vote(deps, env, proposal_id, vote),
+        HandleMsg::ExecuteProposal { proposal_id, .. } => {
+            handle_execute_proposal(deps, env, proposal_id)
+        }
+        HandleMsg::Delegate { delegatee, .. } => handle_delegate(deps, env, delegatee),
+        HandleMsg::Undelegate { .. } => handle_undelegate(deps, env),
+        HandleMsg::SetVotingPowerReaders { readers, .. } => {
+            handle_set_voting_power_readers(deps, env, readers)
+        }
+        HandleMsg::DepositDividends { .. } => handle_deposit_dividends(deps, env),
+        HandleMsg::ClaimDividends { .. } => handle_claim_dividends(deps, env),
+        HandleMsg::AddRewardToken { token, .. } => handle_add_reward_token(deps, env, token),
+        HandleMsg::Receive { from, amount, .. } => {
+            if is_migration_source(&deps.storage, &env.message.sender)? {
+                handle_receive_migration(deps, env, from, amount)
+            } else {
+                handle_receive_dividends(deps, env, amount)
+            }
+        }
+        HandleMsg::CreateClaim {
+            hash,
+            amount,
+            expiration,
+            ..
+        } => handle_create_claim(deps, env, hash, amount, expiration),
+        HandleMsg::Claim { secret, .. } => handle_claim(deps, env, secret),
+        HandleMsg::Reclaim { hash, .. } => handle_reclaim(deps, env, hash),
+        HandleMsg::CreateSwap {
+            recipient,
+            amount,
+            hash_lock,
+            timeout_height,
+            ..
+        } => handle_create_swap(deps, env, recipient, amount, hash_lock, timeout_height),
+        HandleMsg::ClaimSwap {
+            swap_id, preimage, ..
+        } => handle_claim_swap(deps, env, swap_id, preimage),
+        HandleMsg::RefundSwap { swap_id, .. } => handle_refund_swap(deps, env, swap_id),
+        HandleMsg::CreateStream {
+            recipient,
+            amount,
+            start_height,
+            end_height,
+            ..
+        } => handle_create_stream(deps, env, recipient, amount, start_height, end_height),
+        HandleMsg::WithdrawStream { stream_id, .. } => handle_withdraw_stream(deps, env, stream_id),
+        HandleMsg::CancelStream { stream_id, .. } => handle_cancel_stream(deps, env, stream_id),
+        HandleMsg::SetRecurringAllowance {
+            spender,
+            amount,
+            period,
+            max_per_tx,
+            ..
+        } => handle_set_recurring_allowance(deps, env, spender, amount, period, max_per_tx),
+        HandleMsg::RemoveRecurringAllowance { spender, .. } => {
+            handle_remove_recurring_allowance(deps, env, spender)
+        }
+        HandleMsg::TransferWithPermit { permit, .. } => {
+            handle_transfer_with_permit(deps, env, permit)
+        }
+        HandleMsg::CreateInvoice {
+            invoice_id,
+            amount,
+            payer,
+            expiration,
+            ..
+        } => handle_create_invoice(deps, env, invoice_id, amount, payer, expiration),
+        HandleMsg::PayInvoice {
+            payee,
+            invoice_id,
+            amount,
+            ..
+        } => handle_pay_invoice(deps, env, payee, invoice_id, amount),
+        HandleMsg::RefundInvoice {
+            invoice_id,
+            payer,
+            amount,
+            ..
+        } => handle_refund_invoice(deps, env, invoice_id, payer, amount),
+        HandleMsg::CreateSchedule {
+            recipient,
+            amount,
+            start_height,
+            interval,
+            count,
+            tip,
+            memo,
+            escrow,
+            ..
+        } => handle_create_schedule(
+            deps,
+            env,
+            recipient,
+            amount,
+            start_height,
+            interval,
+            count,
+            tip,
+            memo,
+            escrow.unwrap_or(false),
+        ),
+        HandleMsg::ExecuteSchedules { schedule_ids, .. } => {
+            handle_execute_schedules(deps, env, schedule_ids)
+        }
+        HandleMsg::CancelSchedule { schedule_id, .. } => {
+            handle_cancel_schedule(deps, env, schedule_id)
+        }
+        HandleMsg::CreateEscrow {
+            seller,
+            arbiter,
+            amount,
+            deadline,
+            memo,
+            ..
+        } => handle_create_escrow(deps, env, seller, arbiter, amount, deadline, memo),
+        HandleMsg::ReleaseEscrow { escrow_id, .. } => handle_release_escrow(deps, env, escrow_id),
+        HandleMsg::RefundEscrow { escrow_id, .. } => handle_refund_escrow(deps, env, escrow_id),
+        HandleMsg::ResolveEscrow {
+            escrow_id,
+            seller_amount,
+            ..
+        } => handle_resolve_escrow(deps, env, escrow_id, seller_amount),
+        HandleMsg::ExpireEscrow { escrow_id, .. } => handle_expire_escrow(deps, env, escrow_id),
+        HandleMsg::SetBeneficiary {
+            beneficiary,
+            inactivity_period,
+            ..
+        } => handle_set_beneficiary(deps, env, beneficiary, inactivity_period),
+        HandleMsg::RemoveBeneficiary { .. } => handle_remove_beneficiary(deps, env),
+        HandleMsg::ClaimInheritance { owner, .. } => handle_claim_inheritance(deps, env, owner),
+        HandleMsg::Migrate { code_version, .. } => handle_migrate(deps, env, code_version),
+        HandleMsg::CompleteMigration { successor, .. } => {
+            handle_complete_migration(deps, env, successor)
+        }
+        msg => {
+            let accounts = affected_accounts(&env, &msg);
+            let allowances = affected_allowances(&env, &msg)?;
+            let replaced_allowances = grant_recurring_allowances(deps, &env, &msg)?;
+            record_invoice_send(deps, &env, &msg)?;
+            check_supply_cap(&deps.storage, minted_amount(&env, &msg))?;
+            let response = atl_snip20_reference_impl::contract::handle(
+                deps,
+                env.clone(),
+                msg.clone().try_into()?,
+            )?;
+            charge_transfer_fee(deps, &env, &msg)?;
+
+            restore_allowances(deps, &env, replaced_allowances)?;
+            update_allowance_indexes(deps, &allowances)?;
+            after_balance_change(deps, &env, &accounts)?;
+            check_escrow_covered(deps, &env)?;
+
+            Ok(response)
+        }
     }
 }
 
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
     match msg {
         QueryMsg::MarketingInfo {} => query_marketing_info(deps),
+        QueryMsg::SupplyCap {} => query_supply_cap(deps),
+        QueryMsg::TransferFee {} => query_transfer_fee(deps),
+        QueryMsg::GovernanceConfig {} => query_governance_config(deps),
+        QueryMsg::Proposal { proposal_id } => query_proposal(deps, proposal_id),
+        QueryMsg::Proposals { page, page_size } => {
+            query_proposals(deps, page.unwrap_or(0), page_size)
+        }
+        QueryMsg::TotalSupplyAt { height } => query_total_supply_at(deps, height),
+        QueryMsg::BalanceAt {
+            address,
+            key,
+            height,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_balance_at(deps, &address, height)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::VotingPower {
+            address,
+            height,
+            reader,
+            key,
+        } => {
+            if !is_voting_power_reader(deps, &reader)? {
+                return Err(StdError::generic_err(
+                    "Only voting power readers can query the voting power of others",
+                ));
+            }
+            if is_valid_viewing_key(deps, &reader, &key)? {
+                query_voting_power(deps, &address, height)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::VotingPowerReaders {} => query_voting_power_readers(deps),
+        QueryMsg::UnclaimedDividends { address, key } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_unclaimed_dividends(deps, &address)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::RewardTokens {} => query_reward_tokens(deps),
+        QueryMsg::Claims {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_claims(deps, &address, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::Swap {
+            swap_id,
+            address,
+            key,
+        } => match (address, key) {
+            (Some(address), Some(key)) => {
+                if is_valid_viewing_key(deps, &address, &key)? {
+                    query_swap(deps, swap_id, Some(&address))
+                } else {
+                    viewing_key_error()
+                }
+            }
+            _ => query_swap(deps, swap_id, None),
+        },
+        QueryMsg::Streams {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_streams(deps, &address, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::RecurringAllowance {
+            owner,
+            spender,
+            key,
+        } => {
+            if is_valid_viewing_key(deps, &owner, &key)?
+                || is_valid_viewing_key(deps, &spender, &key)?
+            {
+                query_recurring_allowance(deps, &owner, &spender)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::AllowancesGiven {
+            owner,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &owner, &key)? {
+                query_allowances_given(deps, &owner, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::AllowancesReceived {
+            spender,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &spender, &key)? {
+                query_allowances_received(deps, &spender, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::Invoice {
+            payee,
+            key,
+            invoice_id,
+            time,
+        } => {
+            if is_valid_viewing_key(deps, &payee, &key)? {
+                query_invoice(deps, &payee, invoice_id, time)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::Invoices {
+            payee,
+            key,
+            page,
+            page_size,
+            time,
+        } => {
+            if is_valid_viewing_key(deps, &payee, &key)? {
+                query_invoices(deps, &payee, page.unwrap_or(0), page_size, time)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::Schedules {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_schedules(deps, &address, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::DueSchedules { start_after, limit } => {
+            query_due_schedules(deps, start_after, limit)
+        }
+        QueryMsg::Escrows {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_escrows(deps, &address, page.unwrap_or(0), page_size)
+            } else {
+                viewing_key_error()
+            }
+        }
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         msg => atl_snip20_reference_impl::contract::query(deps, msg.try_into()?),
     }
 }
 
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> QueryResult {
+    match query {
+        QueryWithPermit::BalanceAt { height } => {
+            let account = validate_permit(deps, &permit, Permission::Balance)?;
+            query_balance_at(deps, &account, height)
+        }
+        QueryWithPermit::VotingPower { address, height } => {
+            let account = validate_permit(deps, &permit, Permission::Balance)?;
+            if account != address {
+                return Err(StdError::generic_err(
+                    "Voting power can only be queried for the signer of the permit",
+                ));
+            }
+            query_voting_power(deps, &address, height)
+        }
+        QueryWithPermit::UnclaimedDividends {} => {
+            let account = validate_permit(deps, &permit, Permission::Balance)?;
+            query_unclaimed_dividends(deps, &account)
+        }
+        QueryWithPermit::Claims { page, page_size } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_claims(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Swap { swap_id } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_swap(deps, swap_id, Some(&account))
+        }
+        QueryWithPermit::Streams { page, page_size } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_streams(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::RecurringAllowance { owner, spender } => {
+            let account = validate_permit(deps, &permit, Permission::Allowance)?;
+            if account != owner && account != spender {
+                return Err(StdError::generic_err(
+                    "Recurring allowances can only be queried by the owner or the spender",
+                ));
+            }
+            query_recurring_allowance(deps, &owner, &spender)
+        }
+        QueryWithPermit::AllowancesGiven {
+            owner,
+            page,
+            page_size,
+        } => {
+            let account = validate_permit(deps, &permit, Permission::Allowance)?;
+            if account != owner {
+                return Err(StdError::generic_err(
+                    "Allowances given can only be queried by the owner",
+                ));
+            }
+            query_allowances_given(deps, &owner, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::AllowancesReceived {
+            spender,
+            page,
+            page_size,
+        } => {
+            let account = validate_permit(deps, &permit, Permission::Allowance)?;
+            if account != spender {
+                return Err(StdError::generic_err(
+                    "Allowances received can only be queried by the spender",
+                ));
+            }
+            query_allowances_received(deps, &spender, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Invoice { invoice_id, time } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_invoice(deps, &account, invoice_id, time)
+        }
+        QueryWithPermit::Invoices {
+            page,
+            page_size,
+            time,
+        } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_invoices(deps, &account, page.unwrap_or(0), page_size, time)
+        }
+        QueryWithPermit::Schedules { page, page_size } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_schedules(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Escrows { page, page_size } => {
+            let account = validate_permit(deps, &permit, Permission::History)?;
+            query_escrows(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Inheritance { owner } => {
+            let account = validate_permit(deps, &permit, Permission::Owner)?;
+            query_inheritance(deps, owner.as_ref().unwrap_or(&account), &account)
+        }
+        query => atl_snip20_reference_impl::contract::query(
+            deps,
+            QueryMsg::WithPermit { permit, query }.try_into()?,
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::msg::{Logo, MarketingInfo, QueryAnswer};
+    use crate::msg::{
+        AllowancePeriod, FactoryMsg, GovernanceConfig, HandleAnswer, InitCallback, Logo,
+        MarketingInfo, MigrationSource, ProposalStatus, QueryAnswer, RewardToken, SwapStatus,
+        TransferFee, VoteOption,
+    };
+    use atl_snip20_reference_impl::{batch::BurnFromAction, msg::InitialBalance};
     use cosmwasm_std::{
-        from_binary,
+        coins, from_binary,
         testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage},
-        Binary, HumanAddr,
+        to_binary, BankMsg, Binary, CanonicalAddr, CosmosMsg, Decimal, HumanAddr, StdError,
+        Uint128, WasmMsg,
     };
+    use secret_toolkit::permit::{pubkey_to_account, PermitSignature, PubKey, SignedPermit};
+    use sha2::{Digest, Sha256};
+
+    fn init_msg(initial_balances: &[(&str, u128)]) -> InitMsg {
+        InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(
+                initial_balances
+                    .iter()
+                    .map(|(address, amount)| InitialBalance {
+                        address: HumanAddr(address.to_string()),
+                        amount: Uint128(*amount),
+                    })
+                    .collect(),
+            ),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: None,
+            marketing_info: None,
+            governance: None,
+            reward_tokens: None,
+            supply_cap: None,
+            transfer_fee: None,
+            vesting_period: None,
+            migrated_from: None,
+            init_callback: None,
+        }
+    }
+
+    fn env_at(sender: &str, height: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.height = height;
+        env
+    }
+
+    fn transfer(recipient: &str, amount: u128) -> HandleMsg {
+        HandleMsg::Transfer {
+            recipient: HumanAddr(recipient.to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        }
+    }
+
+    fn balance<A: Api>(deps: &Extern<MockStorage, A, MockQuerier>, address: &str) -> u128 {
+        let account = deps
+            .api
+            .canonical_address(&HumanAddr(address.to_string()))
+            .unwrap();
+        crate::balances::balance_of(&deps.storage, &account)
+    }
+
+    fn env_at_time(sender: &str, height: u64, time: u64) -> Env {
+        let mut env = env_at(sender, height);
+        env.block.time = time;
+        env
+    }
+
+    fn assert_generic_err<T: std::fmt::Debug>(result: StdResult<T>, expected: &str) {
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.contains(expected), "{}", msg),
+            other => panic!("Expected \"{}\" error, got {:?}", expected, other),
+        }
+    }
 
     fn init_helper(
         marketing_info: Option<MarketingInfo>,
@@ -67,6 +685,13 @@ mod tests {
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: None,
             marketing_info,
+            governance: None,
+            reward_tokens: None,
+            supply_cap: None,
+            transfer_fee: None,
+            vesting_period: None,
+            migrated_from: None,
+            init_callback: None,
         };
 
         (init(&mut deps, env, init_msg), deps)
@@ -134,4 +759,2292 @@ mod tests {
             _ => panic!("Impossible"),
         }
     }
+
+    fn init_governance_helper() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 600), ("bob", 300), ("carol", 100)]);
+        msg.admin = None;
+        msg.governance = Some(GovernanceConfig {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            voting_period: 10,
+        });
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+
+        let response = handle(
+            &mut deps,
+            env_at("bob", 100),
+            HandleMsg::Propose {
+                title: "Rebrand".to_string(),
+                description: "Point the project to its new home".to_string(),
+                msgs: Some(vec![HandleMsg::SetMarketingInfo {
+                    marketing_info: None,
+                    padding: None,
+                }]),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let answer: HandleAnswer = from_binary(&response.data.unwrap()).unwrap();
+        assert_eq!(answer, HandleAnswer::Propose { proposal_id: 1 });
+
+        deps
+    }
+
+    fn vote(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        voter: &str,
+        height: u64,
+        vote: VoteOption,
+    ) -> StdResult<HandleResponse> {
+        handle(
+            deps,
+            env_at(voter, height),
+            HandleMsg::CastVote {
+                proposal_id: 1,
+                vote,
+                padding: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_passed_proposal_is_executed_by_the_contract() {
+        let mut deps = init_governance_helper();
+
+        vote(&mut deps, "alice", 101, VoteOption::Yes).unwrap();
+        vote(&mut deps, "bob", 105, VoteOption::No).unwrap();
+        assert_generic_err(
+            vote(&mut deps, "alice", 106, VoteOption::No),
+            "Already voted",
+        );
+
+        let execute = HandleMsg::ExecuteProposal {
+            proposal_id: 1,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("anyone", 110), execute.clone()),
+            "Voting period has not ended yet",
+        );
+
+        let response = handle(&mut deps, env_at("anyone", 111), execute).unwrap();
+        assert_eq!(response.messages.len(), 1);
+        match &response.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => {
+                assert_eq!(contract_addr, &mock_env("anyone", &[]).contract.address)
+            }
+            other => panic!("Unexpected message {:?}", other),
+        }
+
+        let query_result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::Proposal { proposal_id: 1 }).unwrap()).unwrap();
+        match query_result {
+            QueryAnswer::Proposal { proposal } => {
+                assert_eq!(proposal.yes_votes, Uint128(600));
+                assert_eq!(proposal.no_votes, Uint128(300));
+                assert_eq!(proposal.status, ProposalStatus::Executed);
+            }
+            _ => panic!("Impossible"),
+        }
+    }
+
+    #[test]
+    fn test_proposals_cannot_move_the_contract_balance() {
+        let mut deps = init_governance_helper();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("bob", 101),
+                HandleMsg::Propose {
+                    title: "Drain".to_string(),
+                    description: "Send the escrowed tokens to bob".to_string(),
+                    msgs: Some(vec![transfer("bob", 100)]),
+                    padding: None,
+                },
+            ),
+            "Proposals can only contain admin messages",
+        );
+
+        handle(
+            &mut deps,
+            env_at("bob", 101),
+            HandleMsg::Propose {
+                title: "Unvest".to_string(),
+                description: "Take back what the team has not vested yet".to_string(),
+                msgs: Some(vec![HandleMsg::CancelStream {
+                    stream_id: 1,
+                    padding: None,
+                }]),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        // A passed proposal left unexecuted for a voting period expires.
+        vote(&mut deps, "alice", 101, VoteOption::Yes).unwrap();
+        let response = handle(
+            &mut deps,
+            env_at("anyone", 121),
+            HandleMsg::ExecuteProposal {
+                proposal_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert!(response.messages.is_empty());
+        let answer: HandleAnswer = from_binary(&response.data.unwrap()).unwrap();
+        assert_eq!(
+            answer,
+            HandleAnswer::ExecuteProposal {
+                status: ProposalStatus::Expired
+            }
+        );
+    }
+
+    #[test]
+    fn test_votes_are_weighted_by_the_snapshot() {
+        let mut deps = init_governance_helper();
+
+        // Tokens received after the snapshot don't count.
+        handle(&mut deps, env_at("alice", 101), transfer("dave", 600)).unwrap();
+        assert_generic_err(
+            vote(&mut deps, "dave", 102, VoteOption::Yes),
+            "No voting power",
+        );
+        vote(&mut deps, "alice", 102, VoteOption::Yes).unwrap();
+
+        // 100 out of 1000 doesn't reach the 40% quorum.
+        let mut deps = init_governance_helper();
+        vote(&mut deps, "carol", 101, VoteOption::Yes).unwrap();
+        let response = handle(
+            &mut deps,
+            env_at("anyone", 111),
+            HandleMsg::ExecuteProposal {
+                proposal_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert!(response.messages.is_empty());
+        let answer: HandleAnswer = from_binary(&response.data.unwrap()).unwrap();
+        assert_eq!(
+            answer,
+            HandleAnswer::ExecuteProposal {
+                status: ProposalStatus::Rejected
+            }
+        );
+    }
+
+    #[test]
+    fn test_balance_and_total_supply_at_height() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 1000)]);
+        msg.config = Some(
+            from_binary(&Binary::from(
+                r#"{ "public_total_supply": true, "enable_burn": true }"#.as_bytes(),
+            ))
+            .unwrap(),
+        );
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        handle(&mut deps, env_at("alice", 110), transfer("bob", 300)).unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 120),
+            HandleMsg::Burn {
+                amount: Uint128(200),
+                memo: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let balance_at = |height, key: &str| -> QueryAnswer {
+            from_binary(
+                &query(
+                    &deps,
+                    QueryMsg::BalanceAt {
+                        address: HumanAddr("alice".to_string()),
+                        key: key.to_string(),
+                        height,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            balance_at(99, "alice_key"),
+            QueryAnswer::BalanceAt {
+                amount: Uint128(0),
+                height: 99
+            }
+        );
+        assert_eq!(
+            balance_at(115, "alice_key"),
+            QueryAnswer::BalanceAt {
+                amount: Uint128(700),
+                height: 115
+            }
+        );
+        assert_eq!(
+            balance_at(120, "alice_key"),
+            QueryAnswer::BalanceAt {
+                amount: Uint128(500),
+                height: 120
+            }
+        );
+        match balance_at(120, "wrong_key") {
+            QueryAnswer::ViewingKeyError { .. } => {}
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        let total_supply_at = |height| -> QueryAnswer {
+            from_binary(&query(&deps, QueryMsg::TotalSupplyAt { height }).unwrap()).unwrap()
+        };
+        assert_eq!(
+            total_supply_at(119),
+            QueryAnswer::TotalSupplyAt {
+                total_supply: Uint128(1000),
+                height: 119
+            }
+        );
+        assert_eq!(
+            total_supply_at(120),
+            QueryAnswer::TotalSupplyAt {
+                total_supply: Uint128(800),
+                height: 120
+            }
+        );
+    }
+
+    #[test]
+    fn test_total_supply_at_respects_config() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+
+        assert_generic_err(
+            query(&deps, QueryMsg::TotalSupplyAt { height: 100 }),
+            "Total supply is not public",
+        );
+    }
+
+    #[test]
+    fn test_delegated_voting_power_follows_balances() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 600), ("bob", 300), ("carol", 100)]);
+        msg.governance = Some(GovernanceConfig {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            voting_period: 10,
+        });
+        assert_generic_err(
+            init(&mut deps, env_at("instantiator", 100), msg.clone()),
+            "Tokens under governance cannot have an admin",
+        );
+        msg.admin = None;
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+
+        handle(
+            &mut deps,
+            env_at("alice", 101),
+            HandleMsg::Delegate {
+                delegatee: HumanAddr("bob".to_string()),
+                padding: None,
+            },
+        )
+        .unwrap();
+        // Balance changes after delegating keep flowing to the delegatee.
+        handle(&mut deps, env_at("carol", 102), transfer("alice", 100)).unwrap();
+
+        handle(
+            &mut deps,
+            env_at("carol", 103),
+            HandleMsg::Propose {
+                title: "Nothing".to_string(),
+                description: "Just checking".to_string(),
+                msgs: None,
+                padding: None,
+            },
+        )
+        .unwrap_err();
+        let response = handle(
+            &mut deps,
+            env_at("bob", 103),
+            HandleMsg::Propose {
+                title: "Nothing".to_string(),
+                description: "Just checking".to_string(),
+                msgs: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let answer: HandleAnswer = from_binary(&response.data.unwrap()).unwrap();
+        assert_eq!(answer, HandleAnswer::Propose { proposal_id: 1 });
+
+        assert_generic_err(
+            vote(&mut deps, "alice", 104, VoteOption::No),
+            "No voting power",
+        );
+        vote(&mut deps, "bob", 104, VoteOption::Yes).unwrap();
+
+        let query_result: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::Proposal { proposal_id: 1 }).unwrap()).unwrap();
+        match query_result {
+            QueryAnswer::Proposal { proposal } => assert_eq!(proposal.yes_votes, Uint128(1000)),
+            _ => panic!("Impossible"),
+        }
+
+        // Undelegating takes the voting power back for later snapshots.
+        handle(
+            &mut deps,
+            env_at("alice", 105),
+            HandleMsg::Undelegate { padding: None },
+        )
+        .unwrap();
+        assert_eq!(
+            crate::delegation::voting_power_at(
+                &deps.storage,
+                &deps
+                    .api
+                    .canonical_address(&HumanAddr("alice".to_string()))
+                    .unwrap(),
+                105
+            )
+            .unwrap(),
+            700
+        );
+    }
+
+    #[test]
+    fn test_voting_power_readers() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 600), ("bob", 400)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 101),
+            HandleMsg::Delegate {
+                delegatee: HumanAddr("bob".to_string()),
+                padding: None,
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("dao", 101),
+            HandleMsg::SetViewingKey {
+                key: "dao_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let voting_power = |key: &str| QueryMsg::VotingPower {
+            address: HumanAddr("bob".to_string()),
+            height: 101,
+            reader: HumanAddr("dao".to_string()),
+            key: key.to_string(),
+        };
+        assert_generic_err(
+            query(&deps, voting_power("dao_key")),
+            "Only voting power readers can query the voting power of others",
+        );
+
+        let set_readers = HandleMsg::SetVotingPowerReaders {
+            readers: vec![HumanAddr("dao".to_string())],
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("dao", 102), set_readers.clone()),
+            "This is an admin command",
+        );
+        handle(&mut deps, env_at("admin", 102), set_readers).unwrap();
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, QueryMsg::VotingPowerReaders {}).unwrap())
+                .unwrap(),
+            QueryAnswer::VotingPowerReaders {
+                readers: vec![HumanAddr("dao".to_string())]
+            }
+        );
+
+        assert!(matches!(
+            from_binary(&query(&deps, voting_power("wrong_key")).unwrap()).unwrap(),
+            QueryAnswer::ViewingKeyError { .. }
+        ));
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, voting_power("dao_key")).unwrap()).unwrap(),
+            QueryAnswer::VotingPower {
+                address: HumanAddr("bob".to_string()),
+                voting_power: Uint128(1000),
+                height: 101,
+            }
+        );
+    }
+
+    #[test]
+    fn test_dividends_follow_balances_over_time() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 600), ("bob", 400)]),
+        )
+        .unwrap();
+
+        let deposit = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, height| {
+            let mut env = mock_env("carol", &coins(1000, "uscrt"));
+            env.block.height = height;
+            handle(deps, env, HandleMsg::DepositDividends { padding: None }).unwrap();
+        };
+
+        deposit(&mut deps, 101);
+        handle(&mut deps, env_at("alice", 102), transfer("bob", 300)).unwrap();
+        deposit(&mut deps, 103);
+
+        handle(
+            &mut deps,
+            env_at("bob", 104),
+            HandleMsg::SetViewingKey {
+                key: "bob_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let query_result: QueryAnswer = from_binary(
+            &query(
+                &deps,
+                QueryMsg::UnclaimedDividends {
+                    address: HumanAddr("bob".to_string()),
+                    key: "bob_key".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            query_result,
+            QueryAnswer::UnclaimedDividends {
+                dividends: coins(1100, "uscrt"),
+                token_dividends: vec![],
+            }
+        );
+
+        let response = handle(
+            &mut deps,
+            env_at("alice", 105),
+            HandleMsg::ClaimDividends { padding: None },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: mock_env("alice", &[]).contract.address,
+                to_address: HumanAddr("alice".to_string()),
+                amount: coins(900, "uscrt"),
+            })]
+        );
+        let answer: HandleAnswer = from_binary(&response.data.unwrap()).unwrap();
+        assert_eq!(
+            answer,
+            HandleAnswer::ClaimDividends {
+                amount: coins(900, "uscrt"),
+                token_amounts: vec![],
+            }
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 106),
+                HandleMsg::ClaimDividends { padding: None },
+            ),
+            "No dividends to claim",
+        );
+
+        // Deposits that holders could not settle are turned away, so transfers keep working.
+        let mut large_deposit = |amount: u128| {
+            let mut env = mock_env("carol", &coins(amount, "uscrt"));
+            env.block.height = 107;
+            handle(
+                &mut deps,
+                env,
+                HandleMsg::DepositDividends { padding: None },
+            )
+        };
+        assert_generic_err(
+            large_deposit(1_000_000_000_000_000_000),
+            "Dividend amount is too large",
+        );
+        large_deposit(100_000_000_000_000_000).unwrap();
+        handle(&mut deps, env_at("bob", 108), transfer("alice", 700)).unwrap();
+    }
+
+    #[test]
+    fn test_reward_token_dividends() {
+        let mut deps = mock_dependencies(20, &[]);
+        let stablecoin = RewardToken {
+            address: HumanAddr("stablecoin".to_string()),
+            code_hash: "stablecoin_hash".to_string(),
+        };
+        let mut msg = init_msg(&[("alice", 750), ("bob", 250)]);
+        msg.reward_tokens = Some(vec![stablecoin.clone()]);
+        let response = init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: stablecoin.address.clone(),
+                callback_code_hash: stablecoin.code_hash.clone(),
+                msg: to_binary(&HandleMsg::RegisterReceive {
+                    code_hash: mock_env("instantiator", &[]).contract_code_hash,
+                    padding: None,
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+
+        let receive = HandleMsg::Receive {
+            sender: HumanAddr("treasury".to_string()),
+            from: HumanAddr("treasury".to_string()),
+            amount: Uint128(400),
+            memo: None,
+            msg: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("fakecoin", 101), receive.clone()),
+            "Only reward tokens can be received",
+        );
+        handle(&mut deps, env_at("stablecoin", 101), receive).unwrap();
+
+        let response = handle(
+            &mut deps,
+            env_at("bob", 102),
+            HandleMsg::ClaimDividends { padding: None },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: stablecoin.address.clone(),
+                callback_code_hash: stablecoin.code_hash,
+                msg: to_binary(&HandleMsg::Transfer {
+                    recipient: HumanAddr("bob".to_string()),
+                    amount: Uint128(100),
+                    memo: None,
+                    padding: None,
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_gift_code_claims() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let create_claim = |secret: &str| HandleMsg::CreateClaim {
+            hash: Binary(Sha256::digest(secret.as_bytes()).to_vec()),
+            amount: Uint128(100),
+            expiration: 2_000,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at_time("alice", 101, 1_000),
+            create_claim("gift"),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at_time("alice", 101, 1_000),
+            create_claim("other"),
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "alice"), 800);
+
+        let claims = |deps: &Extern<MockStorage, MockApi, MockQuerier>| -> QueryAnswer {
+            let query_msg = QueryMsg::Claims {
+                address: HumanAddr("alice".to_string()),
+                key: "alice_key".to_string(),
+                page: None,
+                page_size: 10,
+            };
+            from_binary(&query(deps, query_msg).unwrap()).unwrap()
+        };
+        match claims(&deps) {
+            QueryAnswer::Claims { total, .. } => assert_eq!(total, 2),
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        let claim = HandleMsg::Claim {
+            secret: "gift".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, env_at_time("bob", 102, 1_500), claim.clone()).unwrap();
+        assert_eq!(balance(&deps, "bob"), 100);
+        assert_generic_err(
+            handle(&mut deps, env_at_time("carol", 102, 1_500), claim),
+            "Claim not found",
+        );
+
+        let reclaim = HandleMsg::Reclaim {
+            hash: Binary(Sha256::digest(b"other").to_vec()),
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at_time("alice", 103, 1_999), reclaim.clone()),
+            "Claim has not expired yet",
+        );
+        handle(&mut deps, env_at_time("alice", 104, 2_000), reclaim).unwrap();
+        assert_eq!(balance(&deps, "alice"), 900);
+        assert_eq!(
+            claims(&deps),
+            QueryAnswer::Claims {
+                claims: vec![],
+                total: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_contract_cannot_spend_escrow() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at_time("alice", 101, 1_000),
+            HandleMsg::CreateClaim {
+                hash: Binary(Sha256::digest(b"gift").to_vec()),
+                amount: Uint128(100),
+                expiration: 2_000,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let contract = mock_env("anyone", &[]).contract.address;
+        handle(
+            &mut deps,
+            env_at("alice", 102),
+            transfer(contract.as_str(), 30),
+        )
+        .unwrap();
+
+        // Only what the contract holds besides escrow can be spent as the contract.
+        handle(
+            &mut deps,
+            env_at(contract.as_str(), 103),
+            transfer("bob", 30),
+        )
+        .unwrap();
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at(contract.as_str(), 104),
+                transfer("bob", 1),
+            ),
+            "The contract cannot spend tokens held in escrow: balance=99, escrowed=100",
+        );
+    }
+
+    #[test]
+    fn test_hashed_time_lock_swaps() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("bob", 100),
+            HandleMsg::SetViewingKey {
+                key: "bob_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let create_swap = |timeout_height: u64| HandleMsg::CreateSwap {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(300),
+            hash_lock: Binary(Sha256::digest(b"preimage").to_vec()),
+            timeout_height,
+            padding: None,
+        };
+        let result = handle(&mut deps, env_at("alice", 101), create_swap(110)).unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::CreateSwap { swap_id: 1 }
+        );
+        handle(&mut deps, env_at("alice", 101), create_swap(110)).unwrap();
+        assert_eq!(balance(&deps, "alice"), 400);
+
+        let swap = |deps: &Extern<MockStorage, MockApi, MockQuerier>, address: Option<&str>| {
+            let query_msg = QueryMsg::Swap {
+                swap_id: 1,
+                address: address.map(|address| HumanAddr(address.to_string())),
+                key: address.map(|_| "bob_key".to_string()),
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::Swap { swap } => swap,
+                other => panic!("Unexpected answer {:?}", other),
+            }
+        };
+        assert_eq!(swap(&deps, None).amount, None);
+        assert_eq!(swap(&deps, Some("bob")).amount, Some(Uint128(300)));
+
+        let claim_swap = |swap_id: u64, preimage: &[u8]| HandleMsg::ClaimSwap {
+            swap_id,
+            preimage: Binary(preimage.to_vec()),
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("carol", 102), claim_swap(1, b"wrong")),
+            "Preimage does not match the hash lock",
+        );
+        let result = handle(&mut deps, env_at("carol", 102), claim_swap(1, b"preimage")).unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::ClaimSwap {
+                preimage: Binary(b"preimage".to_vec())
+            }
+        );
+        assert_eq!(balance(&deps, "bob"), 300);
+        let claimed = swap(&deps, None);
+        assert_eq!(claimed.status, SwapStatus::Claimed);
+        assert_eq!(claimed.preimage, Some(Binary(b"preimage".to_vec())));
+
+        let refund_swap = HandleMsg::RefundSwap {
+            swap_id: 2,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("alice", 109), refund_swap.clone()),
+            "Swap has not timed out yet",
+        );
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 110), claim_swap(2, b"preimage")),
+            "Swap has timed out",
+        );
+        handle(&mut deps, env_at("alice", 110), refund_swap).unwrap();
+        assert_eq!(balance(&deps, "alice"), 700);
+    }
+
+    #[test]
+    fn test_stream_of_the_whole_supply() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", u128::MAX)]),
+        )
+        .unwrap();
+        let create_stream = HandleMsg::CreateStream {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(u128::MAX),
+            start_height: 110,
+            end_height: 150,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 100), create_stream).unwrap();
+
+        let withdraw = HandleMsg::WithdrawStream {
+            stream_id: 1,
+            padding: None,
+        };
+        handle(&mut deps, env_at("bob", 120), withdraw).unwrap();
+        assert_eq!(balance(&deps, "bob"), u128::MAX / 4);
+    }
+
+    #[test]
+    fn test_streams_release_linearly() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("bob", 100),
+            HandleMsg::SetViewingKey {
+                key: "bob_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let create_stream = HandleMsg::CreateStream {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(400),
+            start_height: 110,
+            end_height: 150,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 100), create_stream.clone()).unwrap();
+        handle(&mut deps, env_at("alice", 100), create_stream).unwrap();
+        assert_eq!(balance(&deps, "alice"), 200);
+
+        let withdraw = |stream_id: u64| HandleMsg::WithdrawStream {
+            stream_id,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 110), withdraw(1)),
+            "Nothing to withdraw",
+        );
+        handle(&mut deps, env_at("bob", 120), withdraw(1)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 100);
+        handle(&mut deps, env_at("bob", 130), withdraw(1)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 200);
+
+        let cancel = HandleMsg::CancelStream {
+            stream_id: 2,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 140), cancel.clone()),
+            "Only the sender of a stream can cancel it",
+        );
+        let result = handle(&mut deps, env_at("alice", 140), cancel).unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::CancelStream {
+                recipient_amount: Uint128(300),
+                sender_amount: Uint128(100),
+            }
+        );
+        assert_eq!(balance(&deps, "bob"), 500);
+        assert_eq!(balance(&deps, "alice"), 300);
+
+        let query_msg = QueryMsg::Streams {
+            address: HumanAddr("bob".to_string()),
+            key: "bob_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Streams { streams, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(streams[0].id, 1);
+                assert_eq!(streams[0].withdrawn, Uint128(200));
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        handle(&mut deps, env_at("bob", 150), withdraw(1)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 700);
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 151), withdraw(1)),
+            "Stream not found",
+        );
+    }
+
+    #[test]
+    fn test_recurring_allowance_limits_spending() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::IncreaseAllowance {
+                spender: HumanAddr("bob".to_string()),
+                amount: Uint128(500),
+                expiration: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::SetRecurringAllowance {
+                spender: HumanAddr("bob".to_string()),
+                amount: Uint128(100),
+                period: AllowancePeriod::Blocks(10),
+                max_per_tx: Some(Uint128(60)),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let transfer_from = |amount: u128| HandleMsg::TransferFrom {
+            owner: HumanAddr("alice".to_string()),
+            recipient: HumanAddr("carol".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+        handle(&mut deps, env_at("bob", 101), transfer_from(60)).unwrap();
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 102), transfer_from(70)),
+            "Transaction exceeds the allowed maximum",
+        );
+        assert_generic_err(
+            handle(&mut deps, env_at("bob", 109), transfer_from(50)),
+            "Recurring allowance exceeded for this period",
+        );
+        handle(&mut deps, env_at("bob", 110), transfer_from(50)).unwrap();
+        assert_eq!(balance(&deps, "carol"), 110);
+
+        handle(
+            &mut deps,
+            env_at("alice", 111),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("alice".to_string()),
+            spender: HumanAddr("bob".to_string()),
+            key: "alice_key".to_string(),
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::Allowance { allowance, .. } => assert_eq!(allowance, Uint128(500)),
+            other => panic!("Unexpected answer {:?}", other),
+        }
+        let query_msg = QueryMsg::RecurringAllowance {
+            owner: HumanAddr("alice".to_string()),
+            spender: HumanAddr("bob".to_string()),
+            key: "alice_key".to_string(),
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::RecurringAllowance { allowance, .. } => {
+                let allowance = allowance.unwrap();
+                assert_eq!(allowance.period_start, 110);
+                assert_eq!(allowance.spent, Uint128(50));
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recurring_allowance_batch_spends() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 1000)]);
+        msg.config =
+            Some(from_binary(&Binary::from(r#"{ "enable_burn": true }"#.as_bytes())).unwrap());
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::SetRecurringAllowance {
+                spender: HumanAddr("subscription".to_string()),
+                amount: Uint128(100),
+                period: AllowancePeriod::Seconds(3_600),
+                max_per_tx: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let burn_from = HandleMsg::BatchBurnFrom {
+            actions: vec![
+                BurnFromAction {
+                    owner: HumanAddr("alice".to_string()),
+                    amount: Uint128(40),
+                    memo: None,
+                },
+                BurnFromAction {
+                    owner: HumanAddr("alice".to_string()),
+                    amount: Uint128(40),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        handle(&mut deps, env_at("subscription", 101), burn_from.clone()).unwrap();
+        assert_eq!(balance(&deps, "alice"), 920);
+        assert_generic_err(
+            handle(&mut deps, env_at("subscription", 102), burn_from),
+            "Recurring allowance exceeded for this period",
+        );
+    }
+
+    #[test]
+    fn test_allowance_enumeration() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000), ("bob", 1000)]),
+        )
+        .unwrap();
+        for (address, key) in &[("alice", "alice_key"), ("carol", "carol_key")] {
+            handle(
+                &mut deps,
+                env_at(address, 100),
+                HandleMsg::SetViewingKey {
+                    key: key.to_string(),
+                    padding: None,
+                },
+            )
+            .unwrap();
+        }
+
+        let increase_allowance = |spender: &str, amount: u128| HandleMsg::IncreaseAllowance {
+            spender: HumanAddr(spender.to_string()),
+            amount: Uint128(amount),
+            expiration: None,
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at("alice", 101),
+            increase_allowance("carol", 100),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 101),
+            increase_allowance("dave", 50),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("bob", 101),
+            increase_allowance("carol", 30),
+        )
+        .unwrap();
+
+        let allowances_given = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let query_msg = QueryMsg::AllowancesGiven {
+                owner: HumanAddr("alice".to_string()),
+                key: "alice_key".to_string(),
+                page: None,
+                page_size: 10,
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::AllowancesGiven { allowances, .. } => allowances
+                    .into_iter()
+                    .map(|allowance| (allowance.spender.0, allowance.allowance.u128()))
+                    .collect::<Vec<_>>(),
+                other => panic!("Unexpected answer {:?}", other),
+            }
+        };
+        assert_eq!(
+            allowances_given(&deps),
+            vec![("dave".to_string(), 50), ("carol".to_string(), 100)]
+        );
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("carol".to_string()),
+            key: "carol_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg).unwrap()).unwrap() {
+            QueryAnswer::AllowancesReceived { allowances, total } => {
+                assert_eq!(total, 2);
+                assert_eq!(allowances[0].owner, HumanAddr("bob".to_string()));
+                assert_eq!(allowances[1].owner, HumanAddr("alice".to_string()));
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        handle(
+            &mut deps,
+            env_at("carol", 102),
+            HandleMsg::TransferFrom {
+                owner: HumanAddr("alice".to_string()),
+                recipient: HumanAddr("carol".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(allowances_given(&deps), vec![("dave".to_string(), 50)]);
+    }
+
+    #[test]
+    fn test_transfer_permit_rejections() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+
+        let env = env_at_time("relayer", 101, 1_000);
+        let permit = |expiration: u64, chain_id: &str| HandleMsg::TransferWithPermit {
+            permit: TransferPermit {
+                params: TransferPermitParams {
+                    amount: Uint128(100),
+                    chain_id: chain_id.to_string(),
+                    contract: env.contract.address.clone(),
+                    expiration,
+                    memo: None,
+                    nonce: 1,
+                    recipient: HumanAddr("bob".to_string()),
+                    relayer_fee: Some(Uint128(5)),
+                },
+                signature: PermitSignature {
+                    pub_key: PubKey {
+                        r#type: "tendermint/PubKeySecp256k1".to_string(),
+                        value: Binary(vec![2; 33]),
+                    },
+                    signature: Binary(vec![1; 64]),
+                },
+            },
+            padding: None,
+        };
+
+        assert_generic_err(
+            handle(&mut deps, env.clone(), permit(2_000, "other-chain")),
+            "Permit is not valid for this chain",
+        );
+        assert_generic_err(
+            handle(&mut deps, env.clone(), permit(1_000, &env.block.chain_id)),
+            "Permit has expired",
+        );
+        // Signatures are checked by the toolkit's permit validation.
+        assert!(handle(&mut deps, env.clone(), permit(2_000, &env.block.chain_id)).is_err());
+        assert_eq!(balance(&deps, "alice"), 1000);
+        assert_eq!(balance(&deps, "bob"), 0);
+    }
+
+    /// Shows the accounts derived from public keys, which `MockApi` can't represent as text, in
+    /// hex.
+    #[derive(Copy, Clone)]
+    struct PubkeyApi(MockApi);
+
+    impl Api for PubkeyApi {
+        fn canonical_address(&self, human: &HumanAddr) -> StdResult<CanonicalAddr> {
+            match human.as_str().strip_prefix("account") {
+                Some(hex) => (0..hex.len())
+                    .step_by(2)
+                    .map(|index| {
+                        u8::from_str_radix(&hex[index..index + 2], 16)
+                            .map_err(|_| StdError::generic_err("Invalid account"))
+                    })
+                    .collect::<StdResult<Vec<_>>>()
+                    .map(|bytes| CanonicalAddr(Binary(bytes))),
+                None => self.0.canonical_address(human),
+            }
+        }
+
+        fn human_address(&self, canonical: &CanonicalAddr) -> StdResult<HumanAddr> {
+            self.0.human_address(canonical).or_else(|_| {
+                let hex: String = canonical
+                    .as_slice()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                Ok(HumanAddr(format!("account{}", hex)))
+            })
+        }
+    }
+
+    #[test]
+    fn test_transfer_with_permit() {
+        let mut deps = Extern {
+            storage: MockStorage::default(),
+            api: PubkeyApi(MockApi::new(20)),
+            querier: MockQuerier::new(&[]),
+        };
+        let secp = secp256k1::Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[7; 32]).unwrap();
+        let pubkey = Binary(
+            secp256k1::PublicKey::from_secret_key(&secp, &secret_key)
+                .serialize()
+                .to_vec(),
+        );
+        let owner = deps.api.human_address(&pubkey_to_account(&pubkey)).unwrap();
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[(owner.as_str(), 1000)]),
+        )
+        .unwrap();
+
+        let env = env_at_time("relayer", 101, 1_000);
+        let permit = |amount: u128, nonce: u64| {
+            let mut permit = TransferPermit {
+                params: TransferPermitParams {
+                    amount: Uint128(amount),
+                    chain_id: env.block.chain_id.clone(),
+                    contract: env.contract.address.clone(),
+                    expiration: 2_000,
+                    memo: None,
+                    nonce,
+                    recipient: HumanAddr("bob".to_string()),
+                    relayer_fee: Some(Uint128(5)),
+                },
+                signature: PermitSignature {
+                    pub_key: PubKey {
+                        r#type: "tendermint/PubKeySecp256k1".to_string(),
+                        value: pubkey.clone(),
+                    },
+                    signature: Binary::default(),
+                },
+            };
+            let signed = SignedPermit::from_params(
+                &crate::transfer_permits::query_permit(&permit)
+                    .unwrap()
+                    .params,
+            );
+            let hash = Sha256::digest(to_binary(&signed).unwrap().as_slice());
+            let message = secp256k1::Message::from_slice(&hash).unwrap();
+            permit.signature.signature = Binary(
+                secp.sign(&message, &secret_key)
+                    .serialize_compact()
+                    .to_vec(),
+            );
+            HandleMsg::TransferWithPermit {
+                permit,
+                padding: None,
+            }
+        };
+
+        let result = handle(&mut deps, env.clone(), permit(100, 1)).unwrap();
+        match from_binary(&result.data.unwrap()).unwrap() {
+            HandleAnswer::TransferWithPermit {
+                owner: signer,
+                relayer_fee,
+            } => {
+                assert_eq!(signer, owner);
+                assert_eq!(relayer_fee, Uint128(5));
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+        assert_eq!(balance(&deps, owner.as_str()), 895);
+        assert_eq!(balance(&deps, "bob"), 100);
+        assert_eq!(balance(&deps, "relayer"), 5);
+
+        // Nonces can't be reused, whether by the same permit or a different one.
+        assert_generic_err(
+            handle(&mut deps, env.clone(), permit(100, 1)),
+            "Permit nonce was already used",
+        );
+        assert_generic_err(
+            handle(&mut deps, env.clone(), permit(200, 1)),
+            "Permit nonce was already used",
+        );
+        assert_eq!(balance(&deps, "bob"), 100);
+
+        handle(&mut deps, env.clone(), permit(200, 2)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 300);
+    }
+
+    #[test]
+    fn test_invoices_track_payments_and_refunds() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000), ("carol", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("merchant", 100),
+            HandleMsg::SetViewingKey {
+                key: "merchant_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at_time("merchant", 100, 1_000),
+            HandleMsg::CreateInvoice {
+                invoice_id: "order-1".to_string(),
+                amount: Uint128(300),
+                payer: Some(HumanAddr("alice".to_string())),
+                expiration: Some(2_000),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let pay_invoice = |amount: u128| HandleMsg::PayInvoice {
+            payee: HumanAddr("merchant".to_string()),
+            invoice_id: "order-1".to_string(),
+            amount: Uint128(amount),
+            padding: None,
+        };
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at_time("carol", 101, 1_100),
+                pay_invoice(100),
+            ),
+            "This invoice can only be paid by its payer",
+        );
+        let result = handle(
+            &mut deps,
+            env_at_time("alice", 101, 1_100),
+            pay_invoice(100),
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::PayInvoice {
+                status: InvoiceStatus::PartiallyPaid
+            }
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at_time("alice", 102, 1_200),
+                pay_invoice(300),
+            ),
+            "Payment must not exceed the amount due",
+        );
+
+        handle(
+            &mut deps,
+            env_at_time("alice", 102, 1_200),
+            HandleMsg::Send {
+                recipient: HumanAddr("merchant".to_string()),
+                recipient_code_hash: None,
+                amount: Uint128(200),
+                msg: Some(
+                    to_binary(&InvoiceMsg::PayInvoice {
+                        invoice_id: "order-1".to_string(),
+                    })
+                    .unwrap(),
+                ),
+                memo: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "merchant"), 300);
+
+        handle(
+            &mut deps,
+            env_at_time("merchant", 103, 1_300),
+            HandleMsg::RefundInvoice {
+                invoice_id: "order-1".to_string(),
+                payer: HumanAddr("alice".to_string()),
+                amount: Uint128(50),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "alice"), 750);
+
+        let query_msg = |time| QueryMsg::Invoice {
+            payee: HumanAddr("merchant".to_string()),
+            key: "merchant_key".to_string(),
+            invoice_id: "order-1".to_string(),
+            time,
+        };
+        match from_binary(&query(&deps, query_msg(1_300)).unwrap()).unwrap() {
+            QueryAnswer::Invoice { invoice } => {
+                assert_eq!(invoice.status, InvoiceStatus::PartiallyPaid);
+                assert_eq!(
+                    invoice.payments,
+                    vec![InvoicePayment {
+                        payer: HumanAddr("alice".to_string()),
+                        paid: Uint128(300),
+                        refunded: Uint128(50),
+                    }]
+                );
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        assert_generic_err(
+            handle(&mut deps, env_at_time("alice", 104, 2_000), pay_invoice(50)),
+            "Invoice has expired",
+        );
+        match from_binary(&query(&deps, query_msg(2_000)).unwrap()).unwrap() {
+            QueryAnswer::Invoice { invoice } => assert_eq!(invoice.status, InvoiceStatus::Expired),
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        // Sends referencing some other invoice are none of the token's business.
+        handle(
+            &mut deps,
+            env_at_time("alice", 105, 2_100),
+            HandleMsg::Send {
+                recipient: HumanAddr("merchant".to_string()),
+                recipient_code_hash: None,
+                amount: Uint128(10),
+                msg: Some(
+                    to_binary(&InvoiceMsg::PayInvoice {
+                        invoice_id: "elsewhere".to_string(),
+                    })
+                    .unwrap(),
+                ),
+                memo: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "alice"), 740);
+    }
+
+    #[test]
+    fn test_scheduled_transfers_executed_by_keepers() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            HandleMsg::SetViewingKey {
+                key: "alice_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let create_schedule =
+            |amount: u128, interval: Option<u64>, count: Option<u32>| HandleMsg::CreateSchedule {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(amount),
+                start_height: 110,
+                interval,
+                count,
+                tip: Some(Uint128(1)),
+                memo: None,
+                escrow: None,
+                padding: None,
+            };
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            create_schedule(100, Some(10), Some(3)),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            create_schedule(50, None, None),
+        )
+        .unwrap();
+
+        let execute = |schedule_ids: Vec<u64>| HandleMsg::ExecuteSchedules {
+            schedule_ids,
+            padding: None,
+        };
+        let executed = |result: HandleResponse| match from_binary(&result.data.unwrap()).unwrap() {
+            HandleAnswer::ExecuteSchedules { executed, .. } => executed,
+            other => panic!("Unexpected answer {:?}", other),
+        };
+
+        let result = handle(&mut deps, env_at("keeper", 109), execute(vec![1, 2])).unwrap();
+        assert!(executed(result).is_empty());
+        let result = handle(&mut deps, env_at("keeper", 110), execute(vec![1, 2])).unwrap();
+        assert_eq!(executed(result), vec![1, 2]);
+        let result = handle(&mut deps, env_at("keeper", 115), execute(vec![1, 2])).unwrap();
+        assert!(executed(result).is_empty());
+        assert_eq!(balance(&deps, "bob"), 150);
+        assert_eq!(balance(&deps, "keeper"), 2);
+
+        let query_msg = QueryMsg::Schedules {
+            address: HumanAddr("alice".to_string()),
+            key: "alice_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg.clone()).unwrap()).unwrap() {
+            QueryAnswer::Schedules { schedules, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(schedules[0].next_height, 120);
+                assert_eq!(schedules[0].remaining, 2);
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        handle(&mut deps, env_at("keeper", 120), execute(vec![1])).unwrap();
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("bob", 121),
+                HandleMsg::CancelSchedule {
+                    schedule_id: 1,
+                    padding: None,
+                },
+            ),
+            "Only the owner of a schedule can cancel it",
+        );
+        handle(
+            &mut deps,
+            env_at("alice", 121),
+            HandleMsg::CancelSchedule {
+                schedule_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let result = handle(&mut deps, env_at("keeper", 130), execute(vec![1])).unwrap();
+        assert!(executed(result).is_empty());
+        assert_eq!(balance(&deps, "bob"), 250);
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, query_msg).unwrap()).unwrap(),
+            QueryAnswer::Schedules {
+                schedules: vec![],
+                total: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_escrowed_and_failing_schedules() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 300)]),
+        )
+        .unwrap();
+
+        let create_schedule =
+            |amount: u128, count: Option<u32>, escrow: Option<bool>| HandleMsg::CreateSchedule {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(amount),
+                start_height: 110,
+                interval: Some(10),
+                count,
+                tip: Some(Uint128(5)),
+                memo: None,
+                escrow,
+                padding: None,
+            };
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            create_schedule(200, None, None),
+        )
+        .unwrap();
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 100),
+                create_schedule(u128::MAX, None, Some(true)),
+            ),
+            "Schedule amount and tip overflow",
+        );
+        handle(
+            &mut deps,
+            env_at("alice", 100),
+            create_schedule(40, Some(2), Some(true)),
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "alice"), 210);
+        handle(&mut deps, env_at("alice", 101), transfer("carol", 100)).unwrap();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 101),
+                HandleMsg::CreateSchedule {
+                    interval: Some(u64::MAX),
+                    ..create_schedule(1, Some(2), None)
+                },
+            ),
+            "Schedule ends too far in the future",
+        );
+
+        // The escrowed schedule goes through whatever the balance, the other one fails for now.
+        let execute = HandleMsg::ExecuteSchedules {
+            schedule_ids: vec![1, 2],
+            padding: None,
+        };
+        let result = handle(&mut deps, env_at("keeper", 110), execute.clone()).unwrap();
+        match from_binary(&result.data.unwrap()).unwrap() {
+            HandleAnswer::ExecuteSchedules {
+                executed,
+                failed,
+                tips,
+            } => {
+                assert_eq!(executed, vec![2]);
+                assert_eq!(failed, vec![1]);
+                assert_eq!(tips, Uint128(5));
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+        assert_eq!(balance(&deps, "bob"), 40);
+        assert_eq!(balance(&deps, "keeper"), 5);
+
+        // Once the balance covers it again, a keeper can retry it.
+        handle(&mut deps, env_at("carol", 111), transfer("alice", 100)).unwrap();
+        let result = handle(&mut deps, env_at("keeper", 111), execute).unwrap();
+        match from_binary(&result.data.unwrap()).unwrap() {
+            HandleAnswer::ExecuteSchedules {
+                executed, failed, ..
+            } => {
+                assert_eq!(executed, vec![1]);
+                assert!(failed.is_empty());
+            }
+            other => panic!("Unexpected answer {:?}", other),
+        }
+        assert_eq!(balance(&deps, "alice"), 5);
+        assert_eq!(balance(&deps, "bob"), 240);
+
+        // Cancelling returns what is left in escrow.
+        let cancel = |schedule_id: u64| HandleMsg::CancelSchedule {
+            schedule_id,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 112), cancel(2)).unwrap();
+        assert_eq!(balance(&deps, "alice"), 50);
+    }
+
+    #[test]
+    fn test_escrow_with_arbiter() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("buyer", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("arbiter", 100),
+            HandleMsg::SetViewingKey {
+                key: "arbiter_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let create_escrow = HandleMsg::CreateEscrow {
+            seller: HumanAddr("seller".to_string()),
+            arbiter: HumanAddr("arbiter".to_string()),
+            amount: Uint128(300),
+            deadline: 200,
+            memo: None,
+            padding: None,
+        };
+        for _ in 0..3 {
+            handle(&mut deps, env_at("buyer", 100), create_escrow.clone()).unwrap();
+        }
+        assert_eq!(balance(&deps, "buyer"), 100);
+
+        let query_msg = QueryMsg::Escrows {
+            address: HumanAddr("arbiter".to_string()),
+            key: "arbiter_key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        match from_binary(&query(&deps, query_msg.clone()).unwrap()).unwrap() {
+            QueryAnswer::Escrows { total, .. } => assert_eq!(total, 3),
+            other => panic!("Unexpected answer {:?}", other),
+        }
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("seller", 101),
+                HandleMsg::ReleaseEscrow {
+                    escrow_id: 1,
+                    padding: None,
+                },
+            ),
+            "Only the buyer can release an escrow",
+        );
+        handle(
+            &mut deps,
+            env_at("buyer", 101),
+            HandleMsg::ReleaseEscrow {
+                escrow_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "seller"), 300);
+
+        let result = handle(
+            &mut deps,
+            env_at("arbiter", 150),
+            HandleMsg::ResolveEscrow {
+                escrow_id: 2,
+                seller_amount: Uint128(120),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::ResolveEscrow {
+                seller_amount: Uint128(120),
+                buyer_amount: Uint128(180),
+            }
+        );
+        assert_eq!(balance(&deps, "seller"), 420);
+        assert_eq!(balance(&deps, "buyer"), 280);
+
+        let expire_escrow = HandleMsg::ExpireEscrow {
+            escrow_id: 3,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("anyone", 199), expire_escrow.clone()),
+            "Escrow has not reached its deadline yet",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("arbiter", 200),
+                HandleMsg::ResolveEscrow {
+                    escrow_id: 3,
+                    seller_amount: Uint128(300),
+                    padding: None,
+                },
+            ),
+            "Escrow has passed its deadline",
+        );
+        handle(&mut deps, env_at("anyone", 200), expire_escrow).unwrap();
+        assert_eq!(balance(&deps, "buyer"), 580);
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, query_msg).unwrap()).unwrap(),
+            QueryAnswer::Escrows {
+                escrows: vec![],
+                total: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_inheritance_after_inactivity() {
+        let mut deps = mock_dependencies(20, &[]);
+        init(
+            &mut deps,
+            env_at("instantiator", 100),
+            init_msg(&[("alice", 1000)]),
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at_time("alice", 100, 1_000),
+            HandleMsg::SetBeneficiary {
+                beneficiary: HumanAddr("bob".to_string()),
+                inactivity_period: 500,
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        let claim = HandleMsg::ClaimInheritance {
+            owner: HumanAddr("alice".to_string()),
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at_time("carol", 101, 1_100), claim.clone()),
+            "You are not the beneficiary of this account",
+        );
+
+        handle(
+            &mut deps,
+            env_at_time("alice", 101, 1_200),
+            transfer("carol", 100),
+        )
+        .unwrap();
+        assert_generic_err(
+            handle(&mut deps, env_at_time("bob", 102, 1_600), claim.clone()),
+            "The owner has not been inactive for long enough",
+        );
+
+        let result = handle(&mut deps, env_at_time("bob", 103, 1_700), claim.clone()).unwrap();
+        assert_eq!(
+            from_binary::<HandleAnswer>(&result.data.unwrap()).unwrap(),
+            HandleAnswer::ClaimInheritance {
+                amount: Uint128(900)
+            }
+        );
+        assert_eq!(balance(&deps, "alice"), 0);
+        assert_eq!(balance(&deps, "bob"), 900);
+        assert_generic_err(
+            handle(&mut deps, env_at_time("bob", 104, 1_800), claim.clone()),
+            "You are not the beneficiary of this account",
+        );
+
+        // An inactivity period too long to ever end just never lets the beneficiary claim.
+        handle(
+            &mut deps,
+            env_at_time("bob", 105, 1_900),
+            HandleMsg::SetBeneficiary {
+                beneficiary: HumanAddr("alice".to_string()),
+                inactivity_period: u64::MAX,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at_time("alice", 106, 2_000),
+                HandleMsg::ClaimInheritance {
+                    owner: HumanAddr("bob".to_string()),
+                    padding: None,
+                },
+            ),
+            "The owner has not been inactive for long enough",
+        );
+    }
+
+    #[test]
+    fn test_init_callback_and_factory_notifications() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[]);
+        msg.init_callback = Some(InitCallback {
+            msg: Binary::from(r#"{"register_token":{}}"#.as_bytes()),
+            contract_addr: HumanAddr("factory".to_string()),
+            code_hash: "factory_hash".to_string(),
+        });
+
+        let response = init(&mut deps, env_at("factory", 100), msg).unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("factory".to_string()),
+                callback_code_hash: "factory_hash".to_string(),
+                msg: Binary::from(r#"{"register_token":{}}"#.as_bytes()),
+                send: vec![],
+            })]
+        );
+
+        // The factory is kept informed of marketing changes.
+        let response = handle(
+            &mut deps,
+            env_at("admin", 101),
+            HandleMsg::SetMarketingInfo {
+                marketing_info: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            response.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr("factory".to_string()),
+                callback_code_hash: "factory_hash".to_string(),
+                msg: Binary::from(
+                    r#"{"update_marketing_info":{"marketing_info":null}}"#.as_bytes()
+                ),
+                send: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn test_supply_cap_and_vesting() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 1000)]);
+        msg.supply_cap = Some(Uint128(999));
+        assert_generic_err(
+            init(&mut deps, env_at("instantiator", 100), msg.clone()),
+            "Total supply cannot exceed the supply cap",
+        );
+
+        let mut deps = mock_dependencies(20, &[]);
+        msg.config = Some(
+            from_binary(&Binary::from(
+                r#"{ "enable_mint": true, "enable_deposit": true }"#.as_bytes(),
+            ))
+            .unwrap(),
+        );
+        msg.supply_cap = Some(Uint128(1500));
+        msg.vesting_period = Some(100);
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+        assert_eq!(balance(&deps, "alice"), 0);
+
+        // Initial balances vest through streams only the token itself could cancel.
+        let withdraw = HandleMsg::WithdrawStream {
+            stream_id: 1,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 150), withdraw).unwrap();
+        assert_eq!(balance(&deps, "alice"), 500);
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("alice", 160),
+                HandleMsg::CancelStream {
+                    stream_id: 1,
+                    padding: None,
+                },
+            ),
+            "Only the sender of a stream can cancel it",
+        );
+
+        let mint = |amount: u128| HandleMsg::Mint {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("admin", 170), mint(501)),
+            "Total supply cannot exceed the supply cap",
+        );
+        handle(&mut deps, env_at("admin", 170), mint(500)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 500);
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, QueryMsg::SupplyCap {}).unwrap()).unwrap(),
+            QueryAnswer::SupplyCap {
+                supply_cap: Some(Uint128(1500))
+            }
+        );
+
+        // Deposits mint as well.
+        assert_generic_err(
+            handle(
+                &mut deps,
+                mock_env("bob", &coins(1, "uscrt")),
+                HandleMsg::Deposit { padding: None },
+            ),
+            "Total supply cannot exceed the supply cap",
+        );
+
+        // The token itself, e.g. through a governance proposal, takes back what hasn't vested.
+        let contract = mock_env("alice", &[]).contract.address;
+        handle(
+            &mut deps,
+            env_at(contract.as_str(), 160),
+            HandleMsg::CancelStream {
+                stream_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, contract.as_str()), 400);
+        assert_eq!(balance(&deps, "alice"), 600);
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 1000), ("treasury", 100)]);
+        msg.transfer_fee = Some(TransferFee {
+            rate: Decimal::percent(101),
+            collector: HumanAddr("treasury".to_string()),
+        });
+        assert_generic_err(
+            init(&mut deps, env_at("instantiator", 100), msg.clone()),
+            "Transfer fee rate cannot exceed 100%",
+        );
+
+        let mut deps = mock_dependencies(20, &[]);
+        let transfer_fee = TransferFee {
+            rate: Decimal::percent(1),
+            collector: HumanAddr("treasury".to_string()),
+        };
+        msg.transfer_fee = Some(transfer_fee.clone());
+        init(&mut deps, env_at("instantiator", 100), msg).unwrap();
+        assert_eq!(
+            from_binary::<QueryAnswer>(&query(&deps, QueryMsg::TransferFee {}).unwrap()).unwrap(),
+            QueryAnswer::TransferFee {
+                transfer_fee: Some(transfer_fee)
+            }
+        );
+
+        // The fee comes on top of what is transferred.
+        handle(&mut deps, env_at("alice", 101), transfer("bob", 500)).unwrap();
+        assert_eq!(balance(&deps, "alice"), 495);
+        assert_eq!(balance(&deps, "bob"), 500);
+        assert_eq!(balance(&deps, "treasury"), 105);
+
+        // Too small to be charged, or moved by the collector itself.
+        handle(&mut deps, env_at("bob", 102), transfer("carol", 99)).unwrap();
+        assert_eq!(balance(&deps, "bob"), 401);
+        handle(&mut deps, env_at("treasury", 103), transfer("carol", 100)).unwrap();
+        assert_eq!(balance(&deps, "treasury"), 5);
+        assert_eq!(balance(&deps, "carol"), 199);
+
+        // Transfers made outside of the reference implementation pay the fee too, e.g. paying
+        // an invoice to oneself.
+        handle(
+            &mut deps,
+            env_at("alice", 104),
+            HandleMsg::CreateInvoice {
+                invoice_id: "self".to_string(),
+                amount: Uint128(200),
+                payer: None,
+                expiration: None,
+                padding: None,
+            },
+        )
+        .unwrap();
+        handle(
+            &mut deps,
+            env_at("alice", 104),
+            HandleMsg::PayInvoice {
+                payee: HumanAddr("alice".to_string()),
+                invoice_id: "self".to_string(),
+                amount: Uint128(200),
+                padding: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(balance(&deps, "alice"), 493);
+        assert_eq!(balance(&deps, "treasury"), 7);
+    }
+
+    #[test]
+    fn test_migration_to_new_code_version() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 1000)]);
+        msg.supply_cap = Some(Uint128(5000));
+        msg.init_callback = Some(InitCallback {
+            msg: Binary::from(r#"{"register_token":{}}"#.as_bytes()),
+            contract_addr: HumanAddr("factory".to_string()),
+            code_hash: "factory_hash".to_string(),
+        });
+        init(&mut deps, env_at("factory", 100), msg).unwrap();
+
+        let migrate = HandleMsg::Migrate {
+            code_version: Some(2),
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("alice", 101), migrate.clone()),
+            "This is an admin command",
+        );
+
+        // Escrowed tokens would be lost to the new version.
+        let create_stream = HandleMsg::CreateStream {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(100),
+            start_height: 150,
+            end_height: 200,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 101), create_stream).unwrap();
+        assert_generic_err(
+            handle(&mut deps, env_at("admin", 101), migrate.clone()),
+            "Cannot migrate while tokens are held in escrow",
+        );
+        let cancel_stream = HandleMsg::CancelStream {
+            stream_id: 1,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 101), cancel_stream).unwrap();
+
+        let response = handle(&mut deps, env_at("admin", 101), migrate.clone()).unwrap();
+        let (code_version, init_msg) = match &response.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            })] => {
+                assert_eq!(contract_addr, &HumanAddr("factory".to_string()));
+                match from_binary(msg).unwrap() {
+                    FactoryMsg::MigrateToken {
+                        code_version,
+                        init_msg,
+                    } => (code_version, *init_msg),
+                    other => panic!("Unexpected message {:?}", other),
+                }
+            }
+            other => panic!("Unexpected messages {:?}", other),
+        };
+        let old_token = env_at("admin", 101).contract.address;
+        assert_eq!(code_version, Some(2));
+        assert_eq!(init_msg.symbol, "SECSEC");
+        assert_eq!(init_msg.supply_cap, Some(Uint128(5000)));
+        assert_eq!(init_msg.initial_balances, None);
+        assert_eq!(init_msg.migrated_from.as_ref().unwrap().address, old_token);
+
+        // The old version is frozen until the factory reports the new one, and then only lets
+        // holders send their balances over.
+        let transfer_to_bob = HandleMsg::Transfer {
+            recipient: HumanAddr("bob".to_string()),
+            amount: Uint128(100),
+            memo: None,
+            padding: None,
+        };
+        assert_generic_err(
+            handle(&mut deps, env_at("alice", 102), transfer_to_bob.clone()),
+            "This token is migrating to a new version",
+        );
+        assert_generic_err(
+            handle(&mut deps, env_at("admin", 102), migrate),
+            "This token is migrating to a new version",
+        );
+        let complete_migration = HandleMsg::CompleteMigration {
+            successor: MigrationSource {
+                address: HumanAddr("new_token".to_string()),
+                code_hash: "new_token_hash".to_string(),
+            },
+            padding: None,
+        };
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("mallory", 102),
+                complete_migration.clone(),
+            ),
+            "Only the factory can complete a migration",
+        );
+        handle(&mut deps, env_at("factory", 102), complete_migration).unwrap();
+        assert_generic_err(
+            handle(&mut deps, env_at("alice", 102), transfer_to_bob),
+            "This token migrated to new_token",
+        );
+        let send_to_new_token = HandleMsg::Send {
+            recipient: HumanAddr("new_token".to_string()),
+            recipient_code_hash: None,
+            amount: Uint128(400),
+            msg: None,
+            memo: None,
+            padding: None,
+        };
+        handle(&mut deps, env_at("alice", 102), send_to_new_token).unwrap();
+        assert_eq!(balance(&deps, "alice"), 600);
+
+        // The new version starts empty, and is credited what holders send it on the old one.
+        let mut new_deps = mock_dependencies(20, &[]);
+        let response = init(&mut new_deps, env_at("factory", 102), init_msg).unwrap();
+        match &response.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            })] => {
+                assert_eq!(contract_addr, &old_token);
+                assert!(matches!(
+                    from_binary(msg).unwrap(),
+                    HandleMsg::RegisterReceive { .. }
+                ));
+            }
+            other => panic!("Unexpected messages {:?}", other),
+        }
+        let receive = |amount: u128| HandleMsg::Receive {
+            sender: HumanAddr("alice".to_string()),
+            from: HumanAddr("alice".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            msg: None,
+        };
+        assert_generic_err(
+            handle(&mut new_deps, env_at("mallory", 103), receive(400)),
+            "Only reward tokens can be received",
+        );
+        assert_generic_err(
+            handle(
+                &mut new_deps,
+                env_at(old_token.as_str(), 103),
+                receive(5001),
+            ),
+            "Total supply cannot exceed the supply cap",
+        );
+        handle(&mut new_deps, env_at(old_token.as_str(), 103), receive(400)).unwrap();
+        assert_eq!(balance(&new_deps, "alice"), 400);
+    }
+
+    #[test]
+    fn test_governance_token_migrates_through_a_proposal() {
+        let mut deps = mock_dependencies(20, &[]);
+        let mut msg = init_msg(&[("alice", 600), ("bob", 300)]);
+        msg.admin = None;
+        msg.governance = Some(GovernanceConfig {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            voting_period: 10,
+        });
+        msg.init_callback = Some(InitCallback {
+            msg: Binary::from(r#"{"register_token":{}}"#.as_bytes()),
+            contract_addr: HumanAddr("factory".to_string()),
+            code_hash: "factory_hash".to_string(),
+        });
+        init(&mut deps, env_at("factory", 100), msg).unwrap();
+
+        let migrate = HandleMsg::Migrate {
+            code_version: Some(2),
+            padding: None,
+        };
+        handle(
+            &mut deps,
+            env_at("bob", 100),
+            HandleMsg::Propose {
+                title: "Upgrade".to_string(),
+                description: "Move to the next code version".to_string(),
+                msgs: Some(vec![migrate.clone()]),
+                padding: None,
+            },
+        )
+        .unwrap();
+        vote(&mut deps, "alice", 101, VoteOption::Yes).unwrap();
+        let response = handle(
+            &mut deps,
+            env_at("anyone", 111),
+            HandleMsg::ExecuteProposal {
+                proposal_id: 1,
+                padding: None,
+            },
+        )
+        .unwrap();
+        let token = env_at("anyone", 111).contract.address;
+        match &response.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            })] => {
+                assert_eq!(contract_addr, &token);
+                assert_eq!(from_binary::<HandleMsg>(msg).unwrap(), migrate);
+            }
+            other => panic!("Unexpected messages {:?}", other),
+        }
+
+        // The proposal runs as the token itself, which governance made the admin.
+        let response = handle(&mut deps, env_at(token.as_str(), 111), migrate).unwrap();
+        match &response.messages[..] {
+            [CosmosMsg::Wasm(WasmMsg::Execute { msg, .. })] => match from_binary(msg).unwrap() {
+                FactoryMsg::MigrateToken { init_msg, .. } => {
+                    assert!(init_msg.governance.is_some());
+                    assert_eq!(init_msg.admin, None);
+                }
+                other => panic!("Unexpected message {:?}", other),
+            },
+            other => panic!("Unexpected messages {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,238 @@
+use crate::{
+    balances::balance_of,
+    checkpoints::{find_checkpoint, push_checkpoint},
+    msg::{HandleAnswer, QueryAnswer},
+};
+use atl_snip20_reference_impl::{msg::ResponseStatus, state::ReadonlyConfig};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, PrefixedStorage, ReadonlyBucket,
+    ReadonlyPrefixedStorage, ReadonlySingleton, Singleton,
+};
+
+pub fn handle_delegate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    delegatee: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let delegator = deps.api.canonical_address(&env.message.sender)?;
+    let delegatee = deps.api.canonical_address(&delegatee)?;
+
+    set_delegate(deps, &env, &delegator, &delegatee)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Delegate {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_undelegate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let delegator = deps.api.canonical_address(&env.message.sender)?;
+
+    set_delegate(deps, &env, &delegator, &delegator)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Undelegate {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_set_voting_power_readers<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    readers: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
+    if constants.admin != env.message.sender {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+
+    let readers = readers
+        .iter()
+        .map(|reader| deps.api.canonical_address(reader))
+        .collect::<StdResult<Vec<_>>>()?;
+    write_voting_power_readers(&mut deps.storage).save(&readers)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetVotingPowerReaders {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Whether `reader` was allowed by the admin to query anyone's voting power.
+pub fn is_voting_power_reader<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    reader: &HumanAddr,
+) -> StdResult<bool> {
+    let reader = deps.api.canonical_address(reader)?;
+    let readers = read_voting_power_readers(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+
+    Ok(readers.contains(&reader))
+}
+
+pub fn query_voting_power_readers<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let readers = read_voting_power_readers(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        .iter()
+        .map(|reader| deps.api.human_address(reader))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::VotingPowerReaders { readers })
+}
+
+pub fn query_voting_power<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    height: u64,
+) -> StdResult<Binary> {
+    let account = deps.api.canonical_address(address)?;
+    let voting_power = voting_power_at(&deps.storage, &account, height)?;
+
+    to_binary(&QueryAnswer::VotingPower {
+        address: address.clone(),
+        voting_power: Uint128(voting_power),
+        height,
+    })
+}
+
+/// Moves the voting power of an account whose balance went from `previous` to `current`.
+pub fn on_balance_change<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    height: u64,
+    previous: u128,
+    current: u128,
+) -> StdResult<()> {
+    if previous == current {
+        return Ok(());
+    }
+
+    let representative = delegate_of(&*storage, account)?;
+    let voting_power = voting_power_at(&*storage, &representative, height)?;
+    let voting_power = if current > previous {
+        voting_power + (current - previous)
+    } else {
+        voting_power.saturating_sub(previous - current)
+    };
+
+    checkpoint_voting_power(storage, &representative, height, voting_power)
+}
+
+/// Accounts which didn't delegate vote with their own balance.
+pub fn delegate_of<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<CanonicalAddr> {
+    Ok(read_delegates(storage)
+        .may_load(account.as_slice())?
+        .unwrap_or_else(|| account.clone()))
+}
+
+pub fn voting_power_at<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    height: u64,
+) -> StdResult<u128> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_VOTING_POWER_CHECKPOINTS, account.as_slice()],
+        storage,
+    );
+
+    find_checkpoint(&store, height)
+}
+
+fn set_delegate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    delegator: &CanonicalAddr,
+    delegatee: &CanonicalAddr,
+) -> StdResult<()> {
+    let previous = delegate_of(&deps.storage, delegator)?;
+    if &previous == delegatee {
+        return Ok(());
+    }
+
+    let height = env.block.height;
+    let balance = balance_of(&deps.storage, delegator);
+
+    let previous_power = voting_power_at(&deps.storage, &previous, height)?;
+    checkpoint_voting_power(
+        &mut deps.storage,
+        &previous,
+        height,
+        previous_power.saturating_sub(balance),
+    )?;
+    let delegatee_power = voting_power_at(&deps.storage, delegatee, height)?;
+    checkpoint_voting_power(
+        &mut deps.storage,
+        delegatee,
+        height,
+        delegatee_power + balance,
+    )?;
+
+    if delegatee == delegator {
+        write_delegates(&mut deps.storage).remove(delegator.as_slice());
+        Ok(())
+    } else {
+        write_delegates(&mut deps.storage).save(delegator.as_slice(), delegatee)
+    }
+}
+
+fn checkpoint_voting_power<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    height: u64,
+    amount: u128,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(
+        &[PREFIX_VOTING_POWER_CHECKPOINTS, account.as_slice()],
+        storage,
+    );
+
+    push_checkpoint(&mut store, height, amount)
+}
+
+pub const PREFIX_DELEGATES: &[u8] = b"delegates";
+pub const PREFIX_VOTING_POWER_CHECKPOINTS: &[u8] = b"voting_power_checkpoints";
+
+pub fn read_delegates<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, CanonicalAddr> {
+    bucket_read(PREFIX_DELEGATES, storage)
+}
+
+pub fn write_delegates<S: Storage>(storage: &mut S) -> Bucket<S, CanonicalAddr> {
+    bucket(PREFIX_DELEGATES, storage)
+}
+
+pub const PREFIX_VOTING_POWER_READERS: &[u8] = b"voting_power_readers";
+
+pub fn read_voting_power_readers<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Vec<CanonicalAddr>> {
+    singleton_read(storage, PREFIX_VOTING_POWER_READERS)
+}
+
+pub fn write_voting_power_readers<S: Storage>(storage: &mut S) -> Singleton<S, Vec<CanonicalAddr>> {
+    singleton(storage, PREFIX_VOTING_POWER_READERS)
+}
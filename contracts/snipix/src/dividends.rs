@@ -0,0 +1,446 @@
+use crate::{
+    balances::balance_of,
+    msg::{HandleAnswer, HandleMsg, QueryAnswer, RewardToken, TokenAmount},
+};
+use atl_snip20_reference_impl::{msg::ResponseStatus, state::ReadonlyConfig};
+use cosmwasm_std::{
+    to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern, HandleResponse,
+    HumanAddr, Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+use serde::{Deserialize, Serialize};
+
+/// Dividends per token are kept with this many extra units of precision.
+pub const DIVIDEND_SCALE: u128 = 1_000_000_000_000;
+
+/// What an account is owed in one dividend asset.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DividendAccount {
+    /// Dividends per token at the last time the account was settled.
+    pub paid_per_token: Uint128,
+    pub unclaimed: Uint128,
+}
+
+/// Dividends are paid either in native coins or in one of the configured SNIP-20 reward tokens.
+enum DividendAsset {
+    Native(String),
+    Token(RewardToken),
+}
+
+impl DividendAsset {
+    fn key(&self) -> Vec<u8> {
+        match self {
+            DividendAsset::Native(denom) => [b"native/".as_ref(), denom.as_bytes()].concat(),
+            DividendAsset::Token(token) => {
+                [b"token/".as_ref(), token.address.0.as_bytes()].concat()
+            }
+        }
+    }
+}
+
+/// Registers snipix as a receiver of the reward tokens, so that tokens sent to it become dividends.
+pub fn init_reward_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    reward_tokens: Vec<RewardToken>,
+) -> StdResult<Vec<CosmosMsg>> {
+    let messages = reward_tokens
+        .iter()
+        .map(|token| register_receive_msg(env, token))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    write_reward_tokens(&mut deps.storage).save(&reward_tokens)?;
+
+    Ok(messages)
+}
+
+pub fn handle_add_reward_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    token: RewardToken,
+) -> StdResult<HandleResponse> {
+    let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
+    if constants.admin != env.message.sender {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+
+    let mut reward_tokens = read_reward_tokens(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    if reward_tokens
+        .iter()
+        .any(|reward_token| reward_token.address == token.address)
+    {
+        return Err(StdError::generic_err("Reward token is already registered"));
+    }
+
+    let message = register_receive_msg(&env, &token)?;
+    reward_tokens.push(token);
+    write_reward_tokens(&mut deps.storage).save(&reward_tokens)?;
+
+    Ok(HandleResponse {
+        messages: vec![message],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AddRewardToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Shares the sent coins between holders pro rata to their current balance.
+pub fn handle_deposit_dividends<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let coins: Vec<Coin> = env
+        .message
+        .sent_funds
+        .iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .cloned()
+        .collect();
+    if coins.is_empty() {
+        return Err(StdError::generic_err("No funds were sent to distribute"));
+    }
+
+    let eligible_supply = eligible_supply(deps, &env)?;
+    let mut denoms = read_dividend_denoms(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+
+    for coin in coins {
+        distribute(
+            &mut deps.storage,
+            &DividendAsset::Native(coin.denom.clone()),
+            coin.amount.u128(),
+            eligible_supply,
+        )?;
+
+        if !denoms.contains(&coin.denom) {
+            denoms.push(coin.denom);
+        }
+    }
+
+    write_dividend_denoms(&mut deps.storage).save(&denoms)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DepositDividends {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Handles the `Receive` callback of a reward token, sharing the received amount between holders.
+pub fn handle_receive_dividends<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let token = read_reward_tokens(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        .into_iter()
+        .find(|token| token.address == env.message.sender)
+        .ok_or_else(|| StdError::generic_err("Only reward tokens can be received"))?;
+    if amount.is_zero() {
+        return Err(StdError::generic_err("No funds were sent to distribute"));
+    }
+
+    let eligible_supply = eligible_supply(deps, &env)?;
+    distribute(
+        &mut deps.storage,
+        &DividendAsset::Token(token),
+        amount.u128(),
+        eligible_supply,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Receive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_claim_dividends<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+    let balance = balance_of(&deps.storage, &account);
+
+    let mut amount = vec![];
+    let mut token_amounts = vec![];
+    let mut messages = vec![];
+    for asset in dividend_assets(&deps.storage)? {
+        let key = asset.key();
+        let mut dividend_account =
+            settled_account(&deps.storage, &account, &contract, &key, balance)?;
+        let unclaimed = dividend_account.unclaimed;
+        dividend_account.unclaimed = Uint128::zero();
+        write_dividend_accounts(&mut deps.storage, &account).save(&key, &dividend_account)?;
+
+        if unclaimed.is_zero() {
+            continue;
+        }
+        match asset {
+            DividendAsset::Native(denom) => amount.push(Coin {
+                denom,
+                amount: unclaimed,
+            }),
+            DividendAsset::Token(token) => {
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: token.address.clone(),
+                    callback_code_hash: token.code_hash,
+                    msg: to_binary(&HandleMsg::Transfer {
+                        recipient: env.message.sender.clone(),
+                        amount: unclaimed,
+                        memo: None,
+                        padding: None,
+                    })?,
+                    send: vec![],
+                }));
+                token_amounts.push(TokenAmount {
+                    token: token.address,
+                    amount: unclaimed,
+                });
+            }
+        }
+    }
+
+    if amount.is_empty() && token_amounts.is_empty() {
+        return Err(StdError::generic_err("No dividends to claim"));
+    }
+    if !amount.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender,
+            amount: amount.clone(),
+        }));
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClaimDividends {
+            amount,
+            token_amounts,
+        })?),
+    })
+}
+
+pub fn query_unclaimed_dividends<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let account = deps.api.canonical_address(address)?;
+    let contract = deps.api.canonical_address(
+        &ReadonlyConfig::from_storage(&deps.storage)
+            .constants()?
+            .contract_address,
+    )?;
+    let balance = balance_of(&deps.storage, &account);
+
+    let mut dividends = vec![];
+    let mut token_dividends = vec![];
+    for asset in dividend_assets(&deps.storage)? {
+        let unclaimed =
+            settled_account(&deps.storage, &account, &contract, &asset.key(), balance)?.unclaimed;
+        match asset {
+            DividendAsset::Native(denom) => dividends.push(Coin {
+                denom,
+                amount: unclaimed,
+            }),
+            DividendAsset::Token(token) => token_dividends.push(TokenAmount {
+                token: token.address,
+                amount: unclaimed,
+            }),
+        }
+    }
+
+    to_binary(&QueryAnswer::UnclaimedDividends {
+        dividends,
+        token_dividends,
+    })
+}
+
+pub fn query_reward_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let reward_tokens = read_reward_tokens(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+
+    to_binary(&QueryAnswer::RewardTokens { reward_tokens })
+}
+
+/// Credits the dividends an account earned with its `previous` balance, before that balance changes.
+pub fn on_balance_change<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    contract: &CanonicalAddr,
+    previous: u128,
+) -> StdResult<()> {
+    for asset in dividend_assets(&*storage)? {
+        let key = asset.key();
+        let dividend_account = settled_account(&*storage, account, contract, &key, previous)?;
+        write_dividend_accounts(storage, account).save(&key, &dividend_account)?;
+    }
+
+    Ok(())
+}
+
+/// The contract's own balance doesn't earn dividends.
+fn eligible_supply<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<u128> {
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+    let eligible_supply = ReadonlyConfig::from_storage(&deps.storage)
+        .total_supply()
+        .saturating_sub(balance_of(&deps.storage, &contract));
+
+    if eligible_supply == 0 {
+        return Err(StdError::generic_err(
+            "Cannot distribute dividends without token holders",
+        ));
+    }
+
+    Ok(eligible_supply)
+}
+
+fn distribute<S: Storage>(
+    storage: &mut S,
+    asset: &DividendAsset,
+    amount: u128,
+    eligible_supply: u128,
+) -> StdResult<()> {
+    let increase = amount
+        .checked_mul(DIVIDEND_SCALE)
+        .ok_or_else(|| StdError::generic_err("Dividend amount is too large"))?
+        / eligible_supply;
+
+    // Keeping the total under `u128::MAX / DIVIDEND_SCALE` lets holders always settle, whatever
+    // their balance.
+    let key = asset.key();
+    let per_token = dividends_per_token(&*storage, &key)?
+        .checked_add(increase)
+        .filter(|per_token| *per_token <= u128::MAX / DIVIDEND_SCALE)
+        .ok_or_else(|| StdError::generic_err("Dividend amount is too large"))?;
+
+    write_dividends_per_token(storage).save(&key, &Uint128(per_token))
+}
+
+fn dividend_assets<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<DividendAsset>> {
+    let denoms = read_dividend_denoms(storage)
+        .may_load()?
+        .unwrap_or_default();
+    let reward_tokens = read_reward_tokens(storage).may_load()?.unwrap_or_default();
+
+    Ok(denoms
+        .into_iter()
+        .map(DividendAsset::Native)
+        .chain(reward_tokens.into_iter().map(DividendAsset::Token))
+        .collect())
+}
+
+fn settled_account<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    contract: &CanonicalAddr,
+    asset_key: &[u8],
+    balance: u128,
+) -> StdResult<DividendAccount> {
+    let per_token = dividends_per_token(storage, asset_key)?;
+    let mut dividend_account = read_dividend_accounts(storage, account)
+        .may_load(asset_key)?
+        .unwrap_or_default();
+
+    if account != contract {
+        // Split so that nothing overflows: the remainder is below `DIVIDEND_SCALE`, and the
+        // increase below `u128::MAX / DIVIDEND_SCALE`. What is earned is backed by deposited
+        // assets, so the sums can't actually saturate.
+        let increase = per_token - dividend_account.paid_per_token.u128();
+        let earned = (balance / DIVIDEND_SCALE)
+            .saturating_mul(increase)
+            .saturating_add(balance % DIVIDEND_SCALE * increase / DIVIDEND_SCALE);
+        dividend_account.unclaimed =
+            Uint128(dividend_account.unclaimed.u128().saturating_add(earned));
+    }
+    dividend_account.paid_per_token = Uint128(per_token);
+
+    Ok(dividend_account)
+}
+
+fn dividends_per_token<S: ReadonlyStorage>(storage: &S, asset_key: &[u8]) -> StdResult<u128> {
+    Ok(read_dividends_per_token(storage)
+        .may_load(asset_key)?
+        .unwrap_or_default()
+        .u128())
+}
+
+fn register_receive_msg(env: &Env, token: &RewardToken) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(&HandleMsg::RegisterReceive {
+            code_hash: env.contract_code_hash.clone(),
+            padding: None,
+        })?,
+        send: vec![],
+    }))
+}
+
+pub const PREFIX_DIVIDEND_DENOMS: &[u8] = b"dividend_denoms";
+pub const PREFIX_REWARD_TOKENS: &[u8] = b"reward_tokens";
+pub const PREFIX_DIVIDENDS_PER_TOKEN: &[u8] = b"dividends_per_token";
+pub const PREFIX_DIVIDEND_ACCOUNTS: &[u8] = b"dividend_accounts";
+
+pub fn read_dividend_denoms<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, Vec<String>> {
+    singleton_read(storage, PREFIX_DIVIDEND_DENOMS)
+}
+
+pub fn write_dividend_denoms<S: Storage>(storage: &mut S) -> Singleton<S, Vec<String>> {
+    singleton(storage, PREFIX_DIVIDEND_DENOMS)
+}
+
+pub fn read_reward_tokens<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Vec<RewardToken>> {
+    singleton_read(storage, PREFIX_REWARD_TOKENS)
+}
+
+pub fn write_reward_tokens<S: Storage>(storage: &mut S) -> Singleton<S, Vec<RewardToken>> {
+    singleton(storage, PREFIX_REWARD_TOKENS)
+}
+
+pub fn read_dividends_per_token<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Uint128> {
+    bucket_read(PREFIX_DIVIDENDS_PER_TOKEN, storage)
+}
+
+pub fn write_dividends_per_token<S: Storage>(storage: &mut S) -> Bucket<S, Uint128> {
+    bucket(PREFIX_DIVIDENDS_PER_TOKEN, storage)
+}
+
+pub fn read_dividend_accounts<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    account: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, DividendAccount> {
+    ReadonlyBucket::multilevel(&[PREFIX_DIVIDEND_ACCOUNTS, account.as_slice()], storage)
+}
+
+pub fn write_dividend_accounts<'a, S: Storage>(
+    storage: &'a mut S,
+    account: &CanonicalAddr,
+) -> Bucket<'a, S, DividendAccount> {
+    Bucket::multilevel(&[PREFIX_DIVIDEND_ACCOUNTS, account.as_slice()], storage)
+}
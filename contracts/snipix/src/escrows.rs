@@ -0,0 +1,330 @@
+use crate::{
+    balances::{lock_escrow, release_escrow},
+    lists::{page_ids, push_id, remove_id},
+    msg::{EscrowInfo, HandleAnswer, QueryAnswer},
+};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// Tokens locked by `buyer` for `seller`, with `arbiter` settling disputes until `deadline`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Escrow {
+    pub buyer: CanonicalAddr,
+    pub seller: CanonicalAddr,
+    pub arbiter: CanonicalAddr,
+    pub amount: Uint128,
+    /// Block height from which the funds can only return to the buyer.
+    pub deadline: u64,
+    pub memo: Option<String>,
+    /// Positions of the escrow in the buyer's, seller's and arbiter's lists of escrows.
+    pub buyer_index: u32,
+    pub seller_index: u32,
+    pub arbiter_index: u32,
+}
+
+pub fn handle_create_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    seller: HumanAddr,
+    arbiter: HumanAddr,
+    amount: Uint128,
+    deadline: u64,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot escrow an empty amount"));
+    }
+    if deadline <= env.block.height {
+        return Err(StdError::generic_err("Deadline must be in the future"));
+    }
+    let buyer = &env.message.sender;
+    if buyer == &seller || buyer == &arbiter || seller == arbiter {
+        return Err(StdError::generic_err(
+            "Buyer, seller and arbiter must be different accounts",
+        ));
+    }
+
+    lock_escrow(
+        deps,
+        &env,
+        buyer,
+        amount.u128(),
+        Some("Create escrow".to_string()),
+    )?;
+
+    let escrow_id = read_escrow_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    let id = escrow_id.to_be_bytes();
+    let buyer = deps.api.canonical_address(buyer)?;
+    let seller = deps.api.canonical_address(&seller)?;
+    let arbiter = deps.api.canonical_address(&arbiter)?;
+    let buyer_index = push_id(&mut deps.storage, PREFIX_ACCOUNT_ESCROWS, &buyer, &id)?;
+    let seller_index = push_id(&mut deps.storage, PREFIX_ACCOUNT_ESCROWS, &seller, &id)?;
+    let arbiter_index = push_id(&mut deps.storage, PREFIX_ACCOUNT_ESCROWS, &arbiter, &id)?;
+
+    write_escrow_count(&mut deps.storage).save(&escrow_id)?;
+    write_escrows(&mut deps.storage).save(
+        &id,
+        &Escrow {
+            buyer,
+            seller,
+            arbiter,
+            amount,
+            deadline,
+            memo,
+            buyer_index,
+            seller_index,
+            arbiter_index,
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateEscrow { escrow_id })?),
+    })
+}
+
+/// Pays the whole escrow to the seller. Only the buyer can release it.
+pub fn handle_release_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    escrow_id: u64,
+) -> StdResult<HandleResponse> {
+    let escrow = load_escrow(&deps.storage, escrow_id)?;
+    if escrow.buyer != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the buyer can release an escrow",
+        ));
+    }
+
+    settle_escrow(deps, &env, escrow_id, &escrow, escrow.amount.u128())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ReleaseEscrow {
+            amount: escrow.amount,
+        })?),
+    })
+}
+
+/// Returns the whole escrow to the buyer. Only the seller can refund it.
+pub fn handle_refund_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    escrow_id: u64,
+) -> StdResult<HandleResponse> {
+    let escrow = load_escrow(&deps.storage, escrow_id)?;
+    if escrow.seller != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the seller can refund an escrow",
+        ));
+    }
+
+    settle_escrow(deps, &env, escrow_id, &escrow, 0)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RefundEscrow {
+            amount: escrow.amount,
+        })?),
+    })
+}
+
+/// Splits the escrow between the seller and the buyer. Only the arbiter can resolve it, and only
+/// before the deadline.
+pub fn handle_resolve_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    escrow_id: u64,
+    seller_amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let escrow = load_escrow(&deps.storage, escrow_id)?;
+    if escrow.arbiter != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the arbiter can resolve an escrow",
+        ));
+    }
+    if env.block.height >= escrow.deadline {
+        return Err(StdError::generic_err("Escrow has passed its deadline"));
+    }
+    if seller_amount > escrow.amount {
+        return Err(StdError::generic_err(
+            "Cannot award the seller more than the escrowed amount",
+        ));
+    }
+
+    settle_escrow(deps, &env, escrow_id, &escrow, seller_amount.u128())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ResolveEscrow {
+            seller_amount,
+            buyer_amount: Uint128(escrow.amount.u128() - seller_amount.u128()),
+        })?),
+    })
+}
+
+/// Returns an untouched escrow to the buyer once its deadline has passed. Anyone can trigger it.
+pub fn handle_expire_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    escrow_id: u64,
+) -> StdResult<HandleResponse> {
+    let escrow = load_escrow(&deps.storage, escrow_id)?;
+    if env.block.height < escrow.deadline {
+        return Err(StdError::generic_err(
+            "Escrow has not reached its deadline yet",
+        ));
+    }
+
+    settle_escrow(deps, &env, escrow_id, &escrow, 0)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ExpireEscrow {
+            amount: escrow.amount,
+        })?),
+    })
+}
+
+/// Lists the open escrows `address` is a buyer, seller or arbiter of, most recent first.
+pub fn query_escrows<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let account = deps.api.canonical_address(address)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        PREFIX_ACCOUNT_ESCROWS,
+        &account,
+        page,
+        page_size,
+    )?;
+
+    let escrows = ids
+        .into_iter()
+        .map(|id| {
+            let escrow_id = escrow_id_from_bytes(&id)?;
+            let escrow = load_escrow(&deps.storage, escrow_id)?;
+            Ok(EscrowInfo {
+                id: escrow_id,
+                buyer: deps.api.human_address(&escrow.buyer)?,
+                seller: deps.api.human_address(&escrow.seller)?,
+                arbiter: deps.api.human_address(&escrow.arbiter)?,
+                amount: escrow.amount,
+                deadline: escrow.deadline,
+                memo: escrow.memo,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Escrows { escrows, total })
+}
+
+/// Closes the escrow, paying `seller_amount` to the seller and the rest back to the buyer.
+fn settle_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    escrow_id: u64,
+    escrow: &Escrow,
+    seller_amount: u128,
+) -> StdResult<()> {
+    remove_escrow(&mut deps.storage, escrow_id, escrow)?;
+
+    let buyer_amount = escrow.amount.u128() - seller_amount;
+    let payouts = [
+        (&escrow.seller, seller_amount),
+        (&escrow.buyer, buyer_amount),
+    ];
+    for &(account, amount) in payouts.iter() {
+        if amount > 0 {
+            let account = deps.api.human_address(account)?;
+            release_escrow(
+                deps,
+                env,
+                &account,
+                amount,
+                Some("Settle escrow".to_string()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn load_escrow<S: ReadonlyStorage>(storage: &S, escrow_id: u64) -> StdResult<Escrow> {
+    read_escrows(storage)
+        .may_load(&escrow_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Escrow not found"))
+}
+
+fn escrow_id_from_bytes(bytes: &[u8]) -> StdResult<u64> {
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| StdError::generic_err("Corrupted escrow id"))?;
+
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Drops the escrow, along with its place in each party's list.
+fn remove_escrow<S: Storage>(storage: &mut S, escrow_id: u64, escrow: &Escrow) -> StdResult<()> {
+    write_escrows(storage).remove(&escrow_id.to_be_bytes());
+
+    let lists = [
+        (&escrow.buyer, escrow.buyer_index),
+        (&escrow.seller, escrow.seller_index),
+        (&escrow.arbiter, escrow.arbiter_index),
+    ];
+    for &(account, index) in lists.iter() {
+        if let Some(moved) = remove_id(storage, PREFIX_ACCOUNT_ESCROWS, account, index)? {
+            let mut moved_escrow = load_escrow(&*storage, escrow_id_from_bytes(&moved)?)?;
+            if &moved_escrow.buyer == account {
+                moved_escrow.buyer_index = index;
+            } else if &moved_escrow.seller == account {
+                moved_escrow.seller_index = index;
+            } else {
+                moved_escrow.arbiter_index = index;
+            }
+            write_escrows(storage).save(&moved, &moved_escrow)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub const PREFIX_ESCROW_COUNT: &[u8] = b"escrow_count";
+pub const PREFIX_ESCROWS: &[u8] = b"escrows";
+pub const PREFIX_ACCOUNT_ESCROWS: &[u8] = b"account_escrows";
+
+pub fn read_escrow_count<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, PREFIX_ESCROW_COUNT)
+}
+
+pub fn write_escrow_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, PREFIX_ESCROW_COUNT)
+}
+
+pub fn read_escrows<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Escrow> {
+    bucket_read(PREFIX_ESCROWS, storage)
+}
+
+pub fn write_escrows<S: Storage>(storage: &mut S) -> Bucket<S, Escrow> {
+    bucket(PREFIX_ESCROWS, storage)
+}
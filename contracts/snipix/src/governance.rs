@@ -0,0 +1,331 @@
+use crate::{
+    balances::balance_of,
+    checkpoints::total_supply_at,
+    delegation::voting_power_at,
+    msg::{
+        GovernanceConfig, HandleAnswer, HandleMsg, Proposal, ProposalStatus, QueryAnswer,
+        VoteOption,
+    },
+};
+use atl_snip20_reference_impl::{msg::ResponseStatus, state::Config};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CosmosMsg, Decimal, Env, Extern, HandleResponse, Querier, StdError,
+    StdResult, Storage, Uint128, WasmMsg,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+
+pub fn init_governance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    config: GovernanceConfig,
+) -> StdResult<()> {
+    if config.quorum > Decimal::one() || config.threshold > Decimal::one() {
+        return Err(StdError::generic_err(
+            "Quorum and threshold cannot be greater than 1",
+        ));
+    }
+    if config.voting_period == 0 {
+        return Err(StdError::generic_err(
+            "Voting period must be at least 1 block",
+        ));
+    }
+
+    write_governance_config(&mut deps.storage).save(&Some(config))?;
+
+    // Passed proposals are executed by the contract itself, so it has to be the admin.
+    let mut token_config = Config::from_storage(&mut deps.storage);
+    let mut constants = token_config.constants()?;
+    constants.admin = env.contract.address.clone();
+    token_config.set_constants(&constants)
+}
+
+pub fn handle_propose<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    title: String,
+    description: String,
+    msgs: Vec<HandleMsg>,
+) -> StdResult<HandleResponse> {
+    let config = load_governance_config(&deps.storage)?;
+
+    if !msgs.iter().all(is_admin_msg) {
+        return Err(StdError::generic_err(
+            "Proposals can only contain admin messages",
+        ));
+    }
+
+    let proposer = deps.api.canonical_address(&env.message.sender)?;
+    if balance_of(&deps.storage, &proposer) == 0 {
+        return Err(StdError::generic_err(
+            "Only token holders can create proposals",
+        ));
+    }
+
+    let proposal_id = read_proposal_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    let proposal = Proposal {
+        id: proposal_id,
+        proposer: env.message.sender,
+        title,
+        description,
+        msgs,
+        start_height: env.block.height,
+        end_height: env.block.height + config.voting_period,
+        yes_votes: Uint128::zero(),
+        no_votes: Uint128::zero(),
+        abstain_votes: Uint128::zero(),
+        status: ProposalStatus::Open,
+    };
+
+    write_proposal_count(&mut deps.storage).save(&proposal_id)?;
+    write_proposals(&mut deps.storage).save(&proposal_id.to_be_bytes(), &proposal)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Propose { proposal_id })?),
+    })
+}
+
+pub fn handle_cast_vote<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    proposal_id: u64,
+    vote: VoteOption,
+) -> StdResult<HandleResponse> {
+    load_governance_config(&deps.storage)?;
+    let mut proposal = load_proposal(&deps.storage, proposal_id)?;
+
+    // Voting opens the block after the snapshot, so that no balance is counted twice.
+    if proposal.status != ProposalStatus::Open
+        || env.block.height <= proposal.start_height
+        || env.block.height > proposal.end_height
+    {
+        return Err(StdError::generic_err("Proposal is not open for voting"));
+    }
+
+    let voter = deps.api.canonical_address(&env.message.sender)?;
+    let key = proposal_id.to_be_bytes();
+    if read_votes(&deps.storage, &key)
+        .may_load(voter.as_slice())?
+        .is_some()
+    {
+        return Err(StdError::generic_err("Already voted on this proposal"));
+    }
+
+    let weight = voting_power_at(&deps.storage, &voter, proposal.start_height)?;
+    if weight == 0 {
+        return Err(StdError::generic_err(
+            "No voting power at the proposal's snapshot height",
+        ));
+    }
+
+    let tally = match vote {
+        VoteOption::Yes => &mut proposal.yes_votes,
+        VoteOption::No => &mut proposal.no_votes,
+        VoteOption::Abstain => &mut proposal.abstain_votes,
+    };
+    *tally = Uint128(tally.u128() + weight);
+
+    write_votes(&mut deps.storage, &key).save(voter.as_slice(), &vote)?;
+    write_proposals(&mut deps.storage).save(&key, &proposal)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CastVote {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Closes a proposal whose voting period has ended. Anyone can trigger it; a passed
+/// proposal has its messages sent by the contract to itself. A passed proposal that is not
+/// executed within another voting period expires instead, so that one whose messages fail
+/// does not stay open forever.
+pub fn handle_execute_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    proposal_id: u64,
+) -> StdResult<HandleResponse> {
+    let config = load_governance_config(&deps.storage)?;
+    let mut proposal = load_proposal(&deps.storage, proposal_id)?;
+
+    if proposal.status != ProposalStatus::Open {
+        return Err(StdError::generic_err("Proposal has already been closed"));
+    }
+    if env.block.height <= proposal.end_height {
+        return Err(StdError::generic_err("Voting period has not ended yet"));
+    }
+
+    let total_supply = total_supply_at(&deps.storage, proposal.start_height)?;
+
+    let messages = if !is_passed(&config, &proposal, total_supply) {
+        proposal.status = ProposalStatus::Rejected;
+        vec![]
+    } else if env.block.height > proposal.end_height + config.voting_period {
+        proposal.status = ProposalStatus::Expired;
+        vec![]
+    } else {
+        proposal.status = ProposalStatus::Executed;
+        proposal
+            .msgs
+            .iter()
+            .map(|msg| {
+                Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: env.contract.address.clone(),
+                    callback_code_hash: env.contract_code_hash.clone(),
+                    msg: to_binary(msg)?,
+                    send: vec![],
+                }))
+            })
+            .collect::<StdResult<Vec<_>>>()?
+    };
+
+    write_proposals(&mut deps.storage).save(&proposal_id.to_be_bytes(), &proposal)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ExecuteProposal {
+            status: proposal.status,
+        })?),
+    })
+}
+
+pub fn query_governance_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config = read_governance_config(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+
+    to_binary(&QueryAnswer::GovernanceConfig { config })
+}
+
+pub fn query_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    proposal_id: u64,
+) -> StdResult<Binary> {
+    let proposal = load_proposal(&deps.storage, proposal_id)?;
+
+    to_binary(&QueryAnswer::Proposal { proposal })
+}
+
+/// Lists proposals from the most recent one.
+pub fn query_proposals<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let total = read_proposal_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    let skip = page as u64 * page_size as u64;
+
+    let proposals = (1..=total.saturating_sub(skip))
+        .rev()
+        .take(page_size as usize)
+        .map(|proposal_id| load_proposal(&deps.storage, proposal_id))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Proposals { proposals, total })
+}
+
+fn is_passed(config: &GovernanceConfig, proposal: &Proposal, total_supply: u128) -> bool {
+    let yes = proposal.yes_votes.u128();
+    let no = proposal.no_votes.u128();
+    let turnout = yes + no + proposal.abstain_votes.u128();
+
+    if turnout == 0 || Uint128(turnout) < Uint128(total_supply) * config.quorum {
+        return false;
+    }
+
+    Uint128(yes) > Uint128(yes + no) * config.threshold
+}
+
+/// Proposals run as the contract, whose balance holds everything escrowed by claims, swaps,
+/// streams and escrows, so they are limited to messages that cannot move it. Cancelling a stream
+/// the contract created, i.e. a vesting stream, only returns the unvested tokens to it.
+fn is_admin_msg(msg: &HandleMsg) -> bool {
+    matches!(
+        msg,
+        HandleMsg::ChangeAdmin { .. }
+            | HandleMsg::SetContractStatus { .. }
+            | HandleMsg::AddMinters { .. }
+            | HandleMsg::RemoveMinters { .. }
+            | HandleMsg::SetMinters { .. }
+            | HandleMsg::Mint { .. }
+            | HandleMsg::BatchMint { .. }
+            | HandleMsg::SetMarketingInfo { .. }
+            | HandleMsg::AddRewardToken { .. }
+            | HandleMsg::SetVotingPowerReaders { .. }
+            | HandleMsg::CancelStream { .. }
+            | HandleMsg::Migrate { .. }
+    )
+}
+
+fn load_governance_config<S: Storage>(storage: &S) -> StdResult<GovernanceConfig> {
+    read_governance_config(storage)
+        .may_load()?
+        .flatten()
+        .ok_or_else(|| StdError::generic_err("Governance is not enabled for this token"))
+}
+
+fn load_proposal<S: Storage>(storage: &S, proposal_id: u64) -> StdResult<Proposal> {
+    read_proposals(storage)
+        .may_load(&proposal_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Proposal not found"))
+}
+
+pub const PREFIX_GOVERNANCE_CONFIG: &[u8] = b"governance_config";
+pub const PREFIX_PROPOSAL_COUNT: &[u8] = b"proposal_count";
+pub const PREFIX_PROPOSALS: &[u8] = b"proposals";
+pub const PREFIX_VOTES: &[u8] = b"votes";
+
+pub fn read_governance_config<S: Storage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Option<GovernanceConfig>> {
+    singleton_read(storage, PREFIX_GOVERNANCE_CONFIG)
+}
+
+pub fn write_governance_config<S: Storage>(
+    storage: &mut S,
+) -> Singleton<S, Option<GovernanceConfig>> {
+    singleton(storage, PREFIX_GOVERNANCE_CONFIG)
+}
+
+pub fn read_proposal_count<S: Storage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, PREFIX_PROPOSAL_COUNT)
+}
+
+pub fn write_proposal_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, PREFIX_PROPOSAL_COUNT)
+}
+
+pub fn read_proposals<S: Storage>(storage: &S) -> ReadonlyBucket<S, Proposal> {
+    bucket_read(PREFIX_PROPOSALS, storage)
+}
+
+pub fn write_proposals<S: Storage>(storage: &mut S) -> Bucket<S, Proposal> {
+    bucket(PREFIX_PROPOSALS, storage)
+}
+
+pub fn read_votes<'a, S: Storage>(
+    storage: &'a S,
+    proposal_key: &[u8],
+) -> ReadonlyBucket<'a, S, VoteOption> {
+    ReadonlyBucket::multilevel(&[PREFIX_VOTES, proposal_key], storage)
+}
+
+pub fn write_votes<'a, S: Storage>(
+    storage: &'a mut S,
+    proposal_key: &[u8],
+) -> Bucket<'a, S, VoteOption> {
+    Bucket::multilevel(&[PREFIX_VOTES, proposal_key], storage)
+}
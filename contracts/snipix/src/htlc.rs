@@ -0,0 +1,219 @@
+use crate::{
+    balances::{lock_escrow, release_escrow},
+    msg::{HandleAnswer, QueryAnswer, SwapInfo, SwapStatus},
+};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, LogAttribute,
+    Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Tokens locked for `recipient` until the preimage of `hash_lock` is revealed, or refunded to
+/// `sender` once `timeout_height` is reached.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Swap {
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub amount: Uint128,
+    pub hash_lock: Binary,
+    pub timeout_height: u64,
+    pub status: SwapStatus,
+    pub preimage: Option<Binary>,
+}
+
+pub fn handle_create_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    amount: Uint128,
+    hash_lock: Binary,
+    timeout_height: u64,
+) -> StdResult<HandleResponse> {
+    if hash_lock.len() != 32 {
+        return Err(StdError::generic_err("Hash lock must be a SHA-256 digest"));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot lock an empty amount"));
+    }
+    if timeout_height <= env.block.height {
+        return Err(StdError::generic_err("Timeout must be in the future"));
+    }
+
+    lock_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        amount.u128(),
+        Some("Lock swap".to_string()),
+    )?;
+
+    let swap_id = read_swap_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    let swap = Swap {
+        sender: deps.api.canonical_address(&env.message.sender)?,
+        recipient: deps.api.canonical_address(&recipient)?,
+        amount,
+        hash_lock,
+        timeout_height,
+        status: SwapStatus::Open,
+        preimage: None,
+    };
+
+    write_swap_count(&mut deps.storage).save(&swap_id)?;
+    write_swaps(&mut deps.storage).save(&swap_id.to_be_bytes(), &swap)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateSwap { swap_id })?),
+    })
+}
+
+/// Releases the tokens to the recipient. Anyone knowing the preimage can trigger it.
+pub fn handle_claim_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    swap_id: u64,
+    preimage: Binary,
+) -> StdResult<HandleResponse> {
+    let mut swap = load_open_swap(&deps.storage, swap_id)?;
+    if env.block.height >= swap.timeout_height {
+        return Err(StdError::generic_err("Swap has timed out"));
+    }
+    if Sha256::digest(preimage.as_slice()).as_slice() != swap.hash_lock.as_slice() {
+        return Err(StdError::generic_err(
+            "Preimage does not match the hash lock",
+        ));
+    }
+
+    swap.status = SwapStatus::Claimed;
+    swap.preimage = Some(preimage.clone());
+    write_swaps(&mut deps.storage).save(&swap_id.to_be_bytes(), &swap)?;
+
+    let recipient = deps.api.human_address(&swap.recipient)?;
+    release_escrow(
+        deps,
+        &env,
+        &recipient,
+        swap.amount.u128(),
+        Some("Claim swap".to_string()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![LogAttribute {
+            key: "preimage".to_string(),
+            value: preimage.to_base64(),
+            encrypted: false,
+        }],
+        data: Some(to_binary(&HandleAnswer::ClaimSwap { preimage })?),
+    })
+}
+
+/// Returns the tokens to the sender once the swap has timed out. Anyone can trigger it.
+pub fn handle_refund_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    swap_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut swap = load_open_swap(&deps.storage, swap_id)?;
+    if env.block.height < swap.timeout_height {
+        return Err(StdError::generic_err("Swap has not timed out yet"));
+    }
+
+    swap.status = SwapStatus::Refunded;
+    write_swaps(&mut deps.storage).save(&swap_id.to_be_bytes(), &swap)?;
+
+    let sender = deps.api.human_address(&swap.sender)?;
+    release_escrow(
+        deps,
+        &env,
+        &sender,
+        swap.amount.u128(),
+        Some("Refund swap".to_string()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RefundSwap {
+            amount: swap.amount,
+        })?),
+    })
+}
+
+/// Parties and amount are only shown when `viewer` is the sender or the recipient.
+pub fn query_swap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    swap_id: u64,
+    viewer: Option<&HumanAddr>,
+) -> StdResult<Binary> {
+    let swap = load_swap(&deps.storage, swap_id)?;
+
+    let is_party = match viewer {
+        Some(viewer) => {
+            let viewer = deps.api.canonical_address(viewer)?;
+            viewer == swap.sender || viewer == swap.recipient
+        }
+        None => false,
+    };
+
+    let mut swap_info = SwapInfo {
+        id: swap_id,
+        hash_lock: swap.hash_lock,
+        timeout_height: swap.timeout_height,
+        status: swap.status,
+        preimage: swap.preimage,
+        sender: None,
+        recipient: None,
+        amount: None,
+    };
+    if is_party {
+        swap_info.sender = Some(deps.api.human_address(&swap.sender)?);
+        swap_info.recipient = Some(deps.api.human_address(&swap.recipient)?);
+        swap_info.amount = Some(swap.amount);
+    }
+
+    to_binary(&QueryAnswer::Swap { swap: swap_info })
+}
+
+fn load_swap<S: ReadonlyStorage>(storage: &S, swap_id: u64) -> StdResult<Swap> {
+    read_swaps(storage)
+        .may_load(&swap_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Swap not found"))
+}
+
+fn load_open_swap<S: ReadonlyStorage>(storage: &S, swap_id: u64) -> StdResult<Swap> {
+    let swap = load_swap(storage, swap_id)?;
+    if swap.status != SwapStatus::Open {
+        return Err(StdError::generic_err("Swap has already been settled"));
+    }
+
+    Ok(swap)
+}
+
+pub const PREFIX_SWAP_COUNT: &[u8] = b"swap_count";
+pub const PREFIX_SWAPS: &[u8] = b"swaps";
+
+pub fn read_swap_count<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, PREFIX_SWAP_COUNT)
+}
+
+pub fn write_swap_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, PREFIX_SWAP_COUNT)
+}
+
+pub fn read_swaps<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Swap> {
+    bucket_read(PREFIX_SWAPS, storage)
+}
+
+pub fn write_swaps<S: Storage>(storage: &mut S) -> Bucket<S, Swap> {
+    bucket(PREFIX_SWAPS, storage)
+}
@@ -0,0 +1,177 @@
+use crate::{
+    balances::{balance_of, move_balance},
+    msg::{HandleAnswer, InheritanceInfo, QueryAnswer},
+};
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{bucket, bucket_read, Bucket, ReadonlyBucket};
+use serde::{Deserialize, Serialize};
+
+/// Lets `beneficiary` claim the owner's balance once the owner has been inactive for
+/// `inactivity_period` seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Inheritance {
+    pub beneficiary: CanonicalAddr,
+    pub inactivity_period: u64,
+    /// Time of the owner's last action.
+    pub last_active: u64,
+}
+
+pub fn handle_set_beneficiary<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    beneficiary: HumanAddr,
+    inactivity_period: u64,
+) -> StdResult<HandleResponse> {
+    if inactivity_period == 0 {
+        return Err(StdError::generic_err("Inactivity period cannot be empty"));
+    }
+    if beneficiary == env.message.sender {
+        return Err(StdError::generic_err("Cannot be your own beneficiary"));
+    }
+
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    write_inheritances(&mut deps.storage).save(
+        owner.as_slice(),
+        &Inheritance {
+            beneficiary: deps.api.canonical_address(&beneficiary)?,
+            inactivity_period,
+            last_active: env.block.time,
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetBeneficiary {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_remove_beneficiary<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    write_inheritances(&mut deps.storage).remove(owner.as_slice());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveBeneficiary {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Moves the whole balance of an inactive `owner` to the sender, if they are its beneficiary.
+pub fn handle_claim_inheritance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    owner: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let owner_raw = deps.api.canonical_address(&owner)?;
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let inheritance = match read_inheritances(&deps.storage).may_load(owner_raw.as_slice())? {
+        Some(inheritance) if inheritance.beneficiary == sender => inheritance,
+        _ => {
+            return Err(StdError::generic_err(
+                "You are not the beneficiary of this account",
+            ))
+        }
+    };
+    if env.block.time
+        < inheritance
+            .last_active
+            .saturating_add(inheritance.inactivity_period)
+    {
+        return Err(StdError::generic_err(
+            "The owner has not been inactive for long enough",
+        ));
+    }
+
+    write_inheritances(&mut deps.storage).remove(owner_raw.as_slice());
+    let amount = balance_of(&deps.storage, &owner_raw);
+    if amount > 0 {
+        move_balance(
+            deps,
+            &env,
+            &owner,
+            &env.message.sender,
+            amount,
+            Some("Inheritance".to_string()),
+        )?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClaimInheritance {
+            amount: Uint128(amount),
+        })?),
+    })
+}
+
+/// Resets the inactivity timer of `account`, if it has a beneficiary.
+pub fn record_activity<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    account: &HumanAddr,
+) -> StdResult<()> {
+    let account = deps.api.canonical_address(account)?;
+    if let Some(mut inheritance) = read_inheritances(&deps.storage).may_load(account.as_slice())? {
+        inheritance.last_active = env.block.time;
+        write_inheritances(&mut deps.storage).save(account.as_slice(), &inheritance)?;
+    }
+
+    Ok(())
+}
+
+/// `viewer` has to be either the owner or the beneficiary.
+pub fn query_inheritance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &HumanAddr,
+    viewer: &HumanAddr,
+) -> StdResult<Binary> {
+    let owner_raw = deps.api.canonical_address(owner)?;
+    let inheritance = read_inheritances(&deps.storage).may_load(owner_raw.as_slice())?;
+
+    let inheritance = match inheritance {
+        Some(inheritance) => {
+            let beneficiary = deps.api.human_address(&inheritance.beneficiary)?;
+            if viewer != owner && viewer != &beneficiary {
+                return Err(StdError::generic_err(
+                    "Inheritance can only be queried by the owner or the beneficiary",
+                ));
+            }
+            Some(InheritanceInfo {
+                owner: owner.clone(),
+                beneficiary,
+                inactivity_period: inheritance.inactivity_period,
+                last_active: inheritance.last_active,
+            })
+        }
+        None if viewer != owner => {
+            return Err(StdError::generic_err(
+                "Inheritance can only be queried by the owner or the beneficiary",
+            ));
+        }
+        None => None,
+    };
+
+    to_binary(&QueryAnswer::Inheritance { inheritance })
+}
+
+pub const PREFIX_INHERITANCES: &[u8] = b"inheritances";
+
+pub fn read_inheritances<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Inheritance> {
+    bucket_read(PREFIX_INHERITANCES, storage)
+}
+
+pub fn write_inheritances<S: Storage>(storage: &mut S) -> Bucket<S, Inheritance> {
+    bucket(PREFIX_INHERITANCES, storage)
+}
@@ -0,0 +1,373 @@
+use crate::{
+    balances::move_balance,
+    lists::{page_ids, push_id},
+    msg::{
+        HandleAnswer, HandleMsg, InvoiceInfo, InvoiceMsg, InvoicePayment, InvoiceStatus,
+        QueryAnswer,
+    },
+};
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{
+    from_binary, to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr,
+    Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+use serde::{Deserialize, Serialize};
+
+/// An amount requested by a payee, identified by an id of the payee's choosing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Invoice {
+    pub amount: Uint128,
+    pub payer: Option<CanonicalAddr>,
+    /// Seconds since epoch after which the invoice can no longer be paid.
+    pub expiration: Option<u64>,
+    pub payments: Vec<Payment>,
+}
+
+/// What a single payer has paid towards an invoice, and got refunded.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Payment {
+    pub payer: CanonicalAddr,
+    pub paid: Uint128,
+    pub refunded: Uint128,
+}
+
+impl Invoice {
+    /// Amount paid and not refunded.
+    fn settled(&self) -> u128 {
+        self.payments
+            .iter()
+            .map(|payment| payment.paid.u128() - payment.refunded.u128())
+            .sum()
+    }
+
+    fn status(&self, time: u64) -> InvoiceStatus {
+        let settled = self.settled();
+        let refunded: u128 = self
+            .payments
+            .iter()
+            .map(|payment| payment.refunded.u128())
+            .sum();
+        let expired = matches!(self.expiration, Some(expiration) if time >= expiration);
+
+        if settled >= self.amount.u128() {
+            InvoiceStatus::Paid
+        } else if expired {
+            InvoiceStatus::Expired
+        } else if settled > 0 {
+            InvoiceStatus::PartiallyPaid
+        } else if refunded > 0 {
+            InvoiceStatus::Refunded
+        } else {
+            InvoiceStatus::Open
+        }
+    }
+}
+
+pub fn handle_create_invoice<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    invoice_id: String,
+    amount: Uint128,
+    payer: Option<HumanAddr>,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot invoice an empty amount"));
+    }
+    if expiration.map_or(false, |expiration| expiration <= env.block.time) {
+        return Err(StdError::generic_err("Expiration must be in the future"));
+    }
+
+    let payee = deps.api.canonical_address(&env.message.sender)?;
+    if read_invoices(&deps.storage, &payee)
+        .may_load(invoice_id.as_bytes())?
+        .is_some()
+    {
+        return Err(StdError::generic_err(
+            "An invoice with this id already exists",
+        ));
+    }
+
+    let payer = match payer {
+        Some(payer) => Some(deps.api.canonical_address(&payer)?),
+        None => None,
+    };
+    push_id(
+        &mut deps.storage,
+        PREFIX_PAYEE_INVOICES,
+        &payee,
+        invoice_id.as_bytes(),
+    )?;
+    write_invoices(&mut deps.storage, &payee).save(
+        invoice_id.as_bytes(),
+        &Invoice {
+            amount,
+            payer,
+            expiration,
+            payments: vec![],
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateInvoice {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn handle_pay_invoice<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    payee: HumanAddr,
+    invoice_id: String,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let status = record_payment(deps, &env, &payee, &invoice_id, amount)?;
+    move_balance(
+        deps,
+        &env,
+        &env.message.sender,
+        &payee,
+        amount.u128(),
+        Some(format!("Invoice {}", invoice_id)),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::PayInvoice { status })?),
+    })
+}
+
+/// Returns part of what `payer` paid towards one of the sender's invoices.
+pub fn handle_refund_invoice<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    invoice_id: String,
+    payer: HumanAddr,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    let payee = deps.api.canonical_address(&env.message.sender)?;
+    let payer_raw = deps.api.canonical_address(&payer)?;
+    let mut invoice = load_invoice(&deps.storage, &payee, &invoice_id)?;
+
+    let payment = invoice
+        .payments
+        .iter_mut()
+        .find(|payment| payment.payer == payer_raw)
+        .ok_or_else(|| StdError::generic_err("This payer has not paid the invoice"))?;
+    let refundable = payment.paid.u128() - payment.refunded.u128();
+    if amount.u128() > refundable {
+        return Err(StdError::generic_err(format!(
+            "Refund exceeds what was paid: refundable={}, required={}",
+            refundable, amount
+        )));
+    }
+    payment.refunded = Uint128(payment.refunded.u128() + amount.u128());
+
+    let status = invoice.status(env.block.time);
+    write_invoices(&mut deps.storage, &payee).save(invoice_id.as_bytes(), &invoice)?;
+    move_balance(
+        deps,
+        &env,
+        &env.message.sender,
+        &payer,
+        amount.u128(),
+        Some(format!("Refund of invoice {}", invoice_id)),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RefundInvoice { status })?),
+    })
+}
+
+/// Records the payment when `msg` is a `Send` to a payee with a message referencing one of their
+/// invoices. The reference implementation then carries out the transfer. Messages referencing an
+/// invoice the payee doesn't have are left to the recipient.
+pub fn record_invoice_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    msg: &HandleMsg,
+) -> StdResult<()> {
+    if let HandleMsg::Send {
+        recipient,
+        amount,
+        msg: Some(msg),
+        ..
+    } = msg
+    {
+        if let Ok(InvoiceMsg::PayInvoice { invoice_id }) = from_binary(msg) {
+            let payee = deps.api.canonical_address(recipient)?;
+            if read_invoices(&deps.storage, &payee)
+                .may_load(invoice_id.as_bytes())?
+                .is_some()
+            {
+                record_payment(deps, env, recipient, &invoice_id, *amount)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reports the status of the invoice at `time`, as queries don't know the current time.
+pub fn query_invoice<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    payee: &HumanAddr,
+    invoice_id: String,
+    time: u64,
+) -> StdResult<Binary> {
+    let payee_raw = deps.api.canonical_address(payee)?;
+    let invoice = load_invoice(&deps.storage, &payee_raw, &invoice_id)?;
+
+    to_binary(&QueryAnswer::Invoice {
+        invoice: invoice_info(deps, payee, invoice_id, invoice, time)?,
+    })
+}
+
+/// Lists the invoices created by `payee`, most recent first, with their status at `time`.
+pub fn query_invoices<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    payee: &HumanAddr,
+    page: u32,
+    page_size: u32,
+    time: u64,
+) -> StdResult<Binary> {
+    let payee_raw = deps.api.canonical_address(payee)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        PREFIX_PAYEE_INVOICES,
+        &payee_raw,
+        page,
+        page_size,
+    )?;
+
+    let invoices = ids
+        .into_iter()
+        .map(|id| {
+            let invoice_id =
+                String::from_utf8(id).map_err(|_| StdError::generic_err("Corrupted invoice id"))?;
+            let invoice = load_invoice(&deps.storage, &payee_raw, &invoice_id)?;
+            invoice_info(deps, payee, invoice_id, invoice, time)
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Invoices { invoices, total })
+}
+
+fn record_payment<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    payee: &HumanAddr,
+    invoice_id: &str,
+    amount: Uint128,
+) -> StdResult<InvoiceStatus> {
+    let payee = deps.api.canonical_address(payee)?;
+    let payer = deps.api.canonical_address(&env.message.sender)?;
+    let mut invoice = load_invoice(&deps.storage, &payee, invoice_id)?;
+
+    if invoice
+        .payer
+        .as_ref()
+        .map_or(false, |expected| expected != &payer)
+    {
+        return Err(StdError::generic_err(
+            "This invoice can only be paid by its payer",
+        ));
+    }
+    if invoice.status(env.block.time) == InvoiceStatus::Expired {
+        return Err(StdError::generic_err("Invoice has expired"));
+    }
+    let due = invoice.amount.u128().saturating_sub(invoice.settled());
+    if amount.is_zero() || amount.u128() > due {
+        return Err(StdError::generic_err(format!(
+            "Payment must not exceed the amount due: due={}, paid={}",
+            due, amount
+        )));
+    }
+
+    match invoice
+        .payments
+        .iter_mut()
+        .find(|payment| payment.payer == payer)
+    {
+        Some(payment) => payment.paid = Uint128(payment.paid.u128() + amount.u128()),
+        None => invoice.payments.push(Payment {
+            payer,
+            paid: amount,
+            refunded: Uint128::zero(),
+        }),
+    }
+
+    let status = invoice.status(env.block.time);
+    write_invoices(&mut deps.storage, &payee).save(invoice_id.as_bytes(), &invoice)?;
+
+    Ok(status)
+}
+
+fn invoice_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    payee: &HumanAddr,
+    invoice_id: String,
+    invoice: Invoice,
+    time: u64,
+) -> StdResult<InvoiceInfo> {
+    let status = invoice.status(time);
+    let payer = match &invoice.payer {
+        Some(payer) => Some(deps.api.human_address(payer)?),
+        None => None,
+    };
+    let payments = invoice
+        .payments
+        .iter()
+        .map(|payment| {
+            Ok(InvoicePayment {
+                payer: deps.api.human_address(&payment.payer)?,
+                paid: payment.paid,
+                refunded: payment.refunded,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(InvoiceInfo {
+        invoice_id,
+        payee: payee.clone(),
+        amount: invoice.amount,
+        payer,
+        expiration: invoice.expiration,
+        status,
+        payments,
+    })
+}
+
+fn load_invoice<S: ReadonlyStorage>(
+    storage: &S,
+    payee: &CanonicalAddr,
+    invoice_id: &str,
+) -> StdResult<Invoice> {
+    read_invoices(storage, payee)
+        .may_load(invoice_id.as_bytes())?
+        .ok_or_else(|| StdError::generic_err("Invoice not found"))
+}
+
+pub const PREFIX_INVOICES: &[u8] = b"invoices";
+pub const PREFIX_PAYEE_INVOICES: &[u8] = b"payee_invoices";
+
+pub fn read_invoices<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    payee: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, Invoice> {
+    ReadonlyBucket::multilevel(&[PREFIX_INVOICES, payee.as_slice()], storage)
+}
+
+pub fn write_invoices<'a, S: Storage>(
+    storage: &'a mut S,
+    payee: &CanonicalAddr,
+) -> Bucket<'a, S, Invoice> {
+    Bucket::multilevel(&[PREFIX_INVOICES, payee.as_slice()], storage)
+}
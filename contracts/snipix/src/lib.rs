@@ -1,6 +1,25 @@
+mod allowances;
+mod auth;
+mod balances;
+mod checkpoints;
+mod claims;
 pub mod contract;
+mod delegation;
+mod dividends;
+mod escrows;
+mod governance;
+mod htlc;
+mod inheritance;
+mod invoices;
+mod lists;
 mod marketing_info;
+mod migration;
 pub mod msg;
+mod schedules;
+mod streams;
+mod supply_cap;
+mod transfer_fee;
+mod transfer_permits;
 
 #[cfg(not(feature = "library"))]
 #[cfg(target_arch = "wasm32")]
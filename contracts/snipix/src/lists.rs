@@ -0,0 +1,62 @@
+use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+/// Appends `id` to the account's list and returns its position. Per-account lists let items be
+/// enumerated without iterating over storage.
+pub fn push_id<S: Storage>(
+    storage: &mut S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    id: &[u8],
+) -> StdResult<u32> {
+    let mut store = PrefixedStorage::multilevel(&[prefix, account.as_slice()], storage);
+    let mut store = AppendStoreMut::<Vec<u8>, _, _>::attach_or_create(&mut store)?;
+    store.push(&id.to_vec())?;
+
+    Ok(store.len() - 1)
+}
+
+/// Removes the id at `index` by moving the last id into its place. Returns the moved id, if any,
+/// whose item then has to have its stored position updated.
+pub fn remove_id<S: Storage>(
+    storage: &mut S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    index: u32,
+) -> StdResult<Option<Vec<u8>>> {
+    let mut store = PrefixedStorage::multilevel(&[prefix, account.as_slice()], storage);
+    let mut store = AppendStoreMut::<Vec<u8>, _, _>::attach_or_create(&mut store)?;
+
+    let last = store.pop()?;
+    if index < store.len() {
+        store.set_at(index, &last)?;
+        Ok(Some(last))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns a page of ids, most recently added first, along with the length of the list.
+pub fn page_ids<S: ReadonlyStorage>(
+    storage: &S,
+    prefix: &[u8],
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Vec<u8>>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[prefix, account.as_slice()], storage);
+    let store = match AppendStore::<Vec<u8>, _, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok((vec![], 0)),
+    };
+
+    let ids = store
+        .iter()
+        .rev()
+        .skip(page as usize * page_size as usize)
+        .take(page_size as usize)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok((ids, store.len() as u64))
+}
@@ -1,7 +1,18 @@
-use crate::msg::{HandleAnswer, MarketingInfo, QueryAnswer};
+use crate::msg::{FactoryMsg, HandleAnswer, InitCallback, MarketingInfo, QueryAnswer};
 use atl_snip20_reference_impl::msg::ResponseStatus;
-use cosmwasm_std::{to_binary, Api, Binary, Extern, HandleResponse, Querier, StdResult, Storage};
+use cosmwasm_std::{
+    to_binary, Api, Binary, CosmosMsg, Extern, HandleResponse, HumanAddr, Querier, StdResult,
+    Storage, WasmMsg,
+};
 use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use serde::{Deserialize, Serialize};
+
+/// The contract that instantiated the token through its init callback.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Factory {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
 
 pub fn init_marketing_info<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -10,14 +21,35 @@ pub fn init_marketing_info<S: Storage, A: Api, Q: Querier>(
     write_marketing_info(&mut deps.storage).save(&Some(marketing_info))
 }
 
+/// Remembers the contract behind the init callback, so it gets notified of marketing changes.
+pub fn init_factory<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    callback: &InitCallback,
+) -> StdResult<()> {
+    write_factory(&mut deps.storage).save(&Factory {
+        address: callback.contract_addr.clone(),
+        code_hash: callback.code_hash.clone(),
+    })
+}
+
 pub fn handle_set_marketing_info<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     marketing_info: Option<MarketingInfo>,
 ) -> StdResult<HandleResponse> {
     write_marketing_info(&mut deps.storage).save(&marketing_info)?;
 
+    let mut messages = vec![];
+    if let Some(factory) = read_factory(&deps.storage).may_load()? {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: factory.address,
+            callback_code_hash: factory.code_hash,
+            msg: to_binary(&FactoryMsg::UpdateMarketingInfo { marketing_info })?,
+            send: vec![],
+        }));
+    }
+
     Ok(HandleResponse {
-        messages: vec![],
+        messages,
         log: vec![],
         data: Some(to_binary(&HandleAnswer::SetMarketingInfo {
             status: ResponseStatus::Success,
@@ -36,6 +68,7 @@ pub fn query_marketing_info<S: Storage, A: Api, Q: Querier>(
 }
 
 pub const PREFIX_MARKETING_INFO: &[u8] = b"marketing_info";
+pub const PREFIX_FACTORY: &[u8] = b"factory";
 
 pub fn read_marketing_info<S: Storage>(storage: &S) -> ReadonlySingleton<S, Option<MarketingInfo>> {
     singleton_read(storage, PREFIX_MARKETING_INFO)
@@ -44,3 +77,11 @@ pub fn read_marketing_info<S: Storage>(storage: &S) -> ReadonlySingleton<S, Opti
 pub fn write_marketing_info<S: Storage>(storage: &mut S) -> Singleton<S, Option<MarketingInfo>> {
     singleton(storage, PREFIX_MARKETING_INFO)
 }
+
+pub fn read_factory<S: Storage>(storage: &S) -> ReadonlySingleton<S, Factory> {
+    singleton_read(storage, PREFIX_FACTORY)
+}
+
+pub fn write_factory<S: Storage>(storage: &mut S) -> Singleton<S, Factory> {
+    singleton(storage, PREFIX_FACTORY)
+}
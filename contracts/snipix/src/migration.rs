@@ -0,0 +1,289 @@
+use crate::{
+    balances::{after_balance_change, read_escrowed},
+    dividends::read_reward_tokens,
+    governance::read_governance_config,
+    marketing_info::{read_factory, read_marketing_info},
+    msg::{FactoryMsg, HandleAnswer, HandleMsg, InitMsg, MigrationSource},
+    supply_cap::{check_supply_cap, read_supply_cap},
+    transfer_fee::read_transfer_fee,
+};
+use atl_snip20_reference_impl::{
+    msg::{ContractStatusLevel, ResponseStatus},
+    state::{Balances, Config, ReadonlyConfig},
+    transaction_history::store_mint,
+};
+use cosmwasm_std::{
+    from_binary, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use serde::Serialize;
+
+/// Mirrors `InitConfig`, whose fields the reference implementation keeps private.
+#[derive(Serialize)]
+struct InitConfigFlags {
+    public_total_supply: bool,
+    enable_deposit: bool,
+    enable_redeem: bool,
+    enable_mint: bool,
+    enable_burn: bool,
+}
+
+/// Remembers the token this one replaces, and has it notify this one of the balances sent over.
+pub fn init_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    source: MigrationSource,
+) -> StdResult<CosmosMsg> {
+    write_migration_source(&mut deps.storage).save(&Some(source.clone()))?;
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: source.address,
+        callback_code_hash: source.code_hash,
+        msg: to_binary(&HandleMsg::RegisterReceive {
+            code_hash: env.contract_code_hash.clone(),
+            padding: None,
+        })?,
+        send: vec![],
+    }))
+}
+
+/// Sends the factory the settings of the token, for it to launch them again with another code
+/// version, and freezes the token. Balances are not moved: once the new version is launched,
+/// each holder sends theirs over.
+///
+/// Only the settings and balances carry over. Allowances, checkpoints, proposals and delegations
+/// start afresh on the new version, and unclaimed dividends stay claimable here. Tokens holding
+/// anything in escrow (streams, vesting, claims, swaps...) or backed by deposits cannot migrate,
+/// as neither would be honoured by the new version.
+pub fn handle_migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_version: Option<u32>,
+) -> StdResult<HandleResponse> {
+    let constants = ReadonlyConfig::from_storage(&deps.storage).constants()?;
+    if constants.admin != env.message.sender {
+        return Err(StdError::generic_err(
+            "This is an admin command. Admin commands can only be run from admin address",
+        ));
+    }
+    let factory = read_factory(&deps.storage)
+        .may_load()?
+        .ok_or_else(|| StdError::generic_err("Only tokens launched by a factory can migrate"))?;
+    if constants.deposit_is_enabled {
+        return Err(StdError::generic_err(
+            "Tokens backed by deposits cannot migrate",
+        ));
+    }
+    let escrowed = read_escrowed(&deps.storage).may_load()?.unwrap_or_default();
+    if !escrowed.is_zero() {
+        return Err(StdError::generic_err(format!(
+            "Cannot migrate while tokens are held in escrow: escrowed={}",
+            escrowed
+        )));
+    }
+    write_migration_target(&mut deps.storage).save(&None)?;
+
+    let config = from_binary(&to_binary(&InitConfigFlags {
+        public_total_supply: constants.total_supply_is_public,
+        enable_deposit: constants.deposit_is_enabled,
+        enable_redeem: constants.redeem_is_enabled,
+        enable_mint: constants.mint_is_enabled,
+        enable_burn: constants.burn_is_enabled,
+    })?)?;
+    let reward_tokens = read_reward_tokens(&deps.storage)
+        .may_load()?
+        .filter(|reward_tokens| !reward_tokens.is_empty());
+    let governance = read_governance_config(&deps.storage).may_load()?.flatten();
+    let init_msg = InitMsg {
+        name: constants.name,
+        // A new version under governance makes itself the admin again.
+        admin: Some(constants.admin).filter(|_| governance.is_none()),
+        symbol: constants.symbol,
+        decimals: constants.decimals,
+        initial_balances: None,
+        // The factory derives a fresh seed for the new version.
+        prng_seed: Binary::default(),
+        config: Some(config),
+        marketing_info: read_marketing_info(&deps.storage).may_load()?.flatten(),
+        governance,
+        reward_tokens,
+        supply_cap: read_supply_cap(&deps.storage).may_load()?.flatten(),
+        transfer_fee: read_transfer_fee(&deps.storage).may_load()?.flatten(),
+        vesting_period: None,
+        migrated_from: Some(MigrationSource {
+            address: env.contract.address,
+            code_hash: env.contract_code_hash,
+        }),
+        init_callback: None,
+    };
+
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: factory.address,
+            callback_code_hash: factory.code_hash,
+            msg: to_binary(&FactoryMsg::MigrateToken {
+                code_version,
+                init_msg: Box::new(init_msg),
+            })?,
+            send: vec![],
+        })],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Migrate {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Records the new version of the token, launched by the factory.
+pub fn handle_complete_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    successor: MigrationSource,
+) -> StdResult<HandleResponse> {
+    let factory = read_factory(&deps.storage).may_load()?;
+    if factory.map_or(true, |factory| factory.address != env.message.sender) {
+        return Err(StdError::generic_err(
+            "Only the factory can complete a migration",
+        ));
+    }
+    match read_migration_target(&deps.storage).may_load()? {
+        Some(None) => write_migration_target(&mut deps.storage).save(&Some(successor))?,
+        Some(Some(_)) => return Err(StdError::generic_err("Token was already migrated")),
+        None => return Err(StdError::generic_err("Token is not migrating")),
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CompleteMigration {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Fails unless `msg` is still allowed on a token that is migrating: managing viewing keys and
+/// permits, claiming dividends, and, once the new version is launched, sending balances to it.
+pub fn check_not_migrating<S: ReadonlyStorage>(storage: &S, msg: &HandleMsg) -> StdResult<()> {
+    let target = match read_migration_target(storage).may_load()? {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+
+    let allowed = match msg {
+        HandleMsg::CreateViewingKey { .. }
+        | HandleMsg::SetViewingKey { .. }
+        | HandleMsg::RevokePermit { .. }
+        | HandleMsg::RegisterReceive { .. }
+        | HandleMsg::ClaimDividends { .. }
+        | HandleMsg::CompleteMigration { .. } => true,
+        HandleMsg::Send { recipient, .. } => target
+            .as_ref()
+            .map_or(false, |successor| &successor.address == recipient),
+        _ => false,
+    };
+    if allowed {
+        return Ok(());
+    }
+
+    Err(match target {
+        Some(successor) => StdError::generic_err(format!(
+            "This token migrated to {}, send your balance there",
+            successor.address
+        )),
+        None => StdError::generic_err("This token is migrating to a new version"),
+    })
+}
+
+/// Whether `sender` is the previous version of the token.
+pub fn is_migration_source<S: ReadonlyStorage>(storage: &S, sender: &HumanAddr) -> StdResult<bool> {
+    Ok(read_migration_source(storage)
+        .may_load()?
+        .flatten()
+        .map_or(false, |source| &source.address == sender))
+}
+
+/// Mints `from` the balance they sent to this token on the previous version. What was sent stays
+/// with this token there, out of circulation.
+pub fn handle_receive_migration<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+) -> StdResult<HandleResponse> {
+    if ReadonlyConfig::from_storage(&deps.storage).contract_status()
+        != ContractStatusLevel::NormalRun
+    {
+        return Err(StdError::generic_err(
+            "This contract is stopped and this action is not allowed",
+        ));
+    }
+    check_supply_cap(&deps.storage, amount.u128())?;
+
+    let account = deps.api.canonical_address(&from)?;
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+
+    let mut config = Config::from_storage(&mut deps.storage);
+    let total_supply = config
+        .total_supply()
+        .checked_add(amount.u128())
+        .ok_or_else(|| {
+            StdError::generic_err("This migration would overflow the currency's total supply")
+        })?;
+    config.set_total_supply(total_supply);
+    let symbol = config.constants()?.symbol;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let balance = balances
+        .balance(&account)
+        .checked_add(amount.u128())
+        .ok_or_else(|| StdError::generic_err("This migration would overflow the balance"))?;
+    balances.set_account_balance(&account, balance);
+
+    store_mint(
+        &mut deps.storage,
+        &contract,
+        &account,
+        amount,
+        symbol,
+        Some("Migration".to_string()),
+        &env.block,
+    )?;
+    after_balance_change(deps, &env, &[from])?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Receive {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub const PREFIX_MIGRATION_SOURCE: &[u8] = b"migration_source";
+/// Set once the token starts migrating, to the new version once it is launched.
+pub const PREFIX_MIGRATION_TARGET: &[u8] = b"migration_target";
+
+pub fn read_migration_source<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Option<MigrationSource>> {
+    singleton_read(storage, PREFIX_MIGRATION_SOURCE)
+}
+
+pub fn write_migration_source<S: Storage>(
+    storage: &mut S,
+) -> Singleton<S, Option<MigrationSource>> {
+    singleton(storage, PREFIX_MIGRATION_SOURCE)
+}
+
+pub fn read_migration_target<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Option<MigrationSource>> {
+    singleton_read(storage, PREFIX_MIGRATION_TARGET)
+}
+
+pub fn write_migration_target<S: Storage>(
+    storage: &mut S,
+) -> Singleton<S, Option<MigrationSource>> {
+    singleton(storage, PREFIX_MIGRATION_TARGET)
+}
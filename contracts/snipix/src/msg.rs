@@ -1,9 +1,9 @@
 use std::convert::TryInto;
 
 use atl_snip20_reference_impl::{batch, msg, transaction_history, viewing_key};
-use cosmwasm_std::{Binary, HumanAddr, StdError, Uint128};
+use cosmwasm_std::{Binary, Coin, Decimal, HumanAddr, StdError, Uint128};
 use schemars::JsonSchema;
-use secret_toolkit::permit::Permit;
+use secret_toolkit::permit::{Permit, PermitSignature};
 use serde::{Deserialize, Serialize};
 
 /// This is used for uploading logo data, or setting it in InstantiateData
@@ -38,6 +38,282 @@ pub struct MarketingInfo {
     pub logo: Option<Logo>,
 }
 
+/// A message the token sends to `contract_addr` once initialized, letting the contract that
+/// instantiated it learn its address.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct InitCallback {
+    pub msg: Binary,
+    pub contract_addr: HumanAddr,
+    pub code_hash: String,
+}
+
+/// Sent to the contract that instantiated the token, which is expected to be a factory keeping
+/// track of the tokens it launched.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FactoryMsg {
+    UpdateMarketingInfo {
+        marketing_info: Option<MarketingInfo>,
+    },
+    /// Asks the factory to instantiate `init_msg` with another code version, replacing the
+    /// sending token.
+    MigrateToken {
+        code_version: Option<u32>,
+        init_msg: Box<InitMsg>,
+    },
+}
+
+/// The previous version of a token. Holders move their balances over by sending them to the new
+/// version.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct MigrationSource {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+/// Charged on top of each transfer and send, and paid to `collector`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TransferFee {
+    pub rate: Decimal,
+    pub collector: HumanAddr,
+}
+
+/// A SNIP-20 token whose transfers to snipix are shared between holders as dividends.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RewardToken {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TokenAmount {
+    pub token: HumanAddr,
+    pub amount: Uint128,
+}
+
+/// An outstanding claim, as seen by its creator.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ClaimInfo {
+    pub hash: Binary,
+    pub amount: Uint128,
+    pub expiration: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapStatus {
+    Open,
+    Claimed,
+    Refunded,
+}
+
+/// A hashed time-lock swap. Parties and amount are only visible to the sender and the recipient.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct SwapInfo {
+    pub id: u64,
+    pub hash_lock: Binary,
+    pub timeout_height: u64,
+    pub status: SwapStatus,
+    /// Revealed once the swap has been claimed.
+    pub preimage: Option<Binary>,
+    pub sender: Option<HumanAddr>,
+    pub recipient: Option<HumanAddr>,
+    pub amount: Option<Uint128>,
+}
+
+/// An active stream, as seen by its sender or recipient.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub id: u64,
+    pub sender: HumanAddr,
+    pub recipient: HumanAddr,
+    pub amount: Uint128,
+    pub withdrawn: Uint128,
+    pub start_height: u64,
+    pub end_height: u64,
+}
+
+/// How often a recurring allowance resets.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AllowancePeriod {
+    Blocks(u64),
+    Seconds(u64),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct RecurringAllowanceInfo {
+    pub amount: Uint128,
+    pub period: AllowancePeriod,
+    pub max_per_tx: Option<Uint128>,
+    pub period_start: u64,
+    pub spent: Uint128,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct AllowanceInfo {
+    pub owner: HumanAddr,
+    pub spender: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
+    pub recurring: Option<RecurringAllowanceInfo>,
+}
+
+/// A transfer authorized off-chain by the signer, which anyone can submit on their behalf.
+///
+/// It is signed as a query permit for `contract` on `chain_id` that grants no permissions, named
+/// after the JSON of its params. Revoking it by name cancels it.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TransferPermit {
+    pub params: TransferPermitParams,
+    pub signature: PermitSignature,
+}
+
+/// Fields are kept in alphabetical order, as they are signed as canonical JSON.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct TransferPermitParams {
+    pub amount: Uint128,
+    pub chain_id: String,
+    /// Address of the token the permit is valid for.
+    pub contract: HumanAddr,
+    /// Seconds since epoch.
+    pub expiration: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Each nonce can only be used once per signer.
+    pub nonce: u64,
+    pub recipient: HumanAddr,
+    /// Paid by the signer to whoever submits the permit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relayer_fee: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Open,
+    PartiallyPaid,
+    Paid,
+    Refunded,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct InvoicePayment {
+    pub payer: HumanAddr,
+    pub paid: Uint128,
+    pub refunded: Uint128,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct InvoiceInfo {
+    pub invoice_id: String,
+    pub payee: HumanAddr,
+    pub amount: Uint128,
+    pub payer: Option<HumanAddr>,
+    pub expiration: Option<u64>,
+    /// As of the time given in the query.
+    pub status: InvoiceStatus,
+    pub payments: Vec<InvoicePayment>,
+}
+
+/// Attach to a `Send` to the payee to have it count towards one of their invoices.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceMsg {
+    PayInvoice { invoice_id: String },
+}
+
+/// An active schedule, as seen by its owner.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ScheduleInfo {
+    pub id: u64,
+    pub recipient: HumanAddr,
+    pub amount: Uint128,
+    pub next_height: u64,
+    pub interval: Option<u64>,
+    pub remaining: u32,
+    pub tip: Uint128,
+    pub memo: Option<String>,
+    pub escrowed: bool,
+}
+
+/// An active schedule, as seen by keepers.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct DueSchedule {
+    pub id: u64,
+    pub next_height: u64,
+    pub tip: Uint128,
+}
+
+/// An open escrow, as seen by its buyer, seller or arbiter.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct EscrowInfo {
+    pub id: u64,
+    pub buyer: HumanAddr,
+    pub seller: HumanAddr,
+    pub arbiter: HumanAddr,
+    pub amount: Uint128,
+    pub deadline: u64,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct InheritanceInfo {
+    pub owner: HumanAddr,
+    pub beneficiary: HumanAddr,
+    pub inactivity_period: u64,
+    /// Seconds since epoch. The balance can be claimed from `last_active + inactivity_period`.
+    pub last_active: u64,
+}
+
+/// Rules applied to every governance proposal.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct GovernanceConfig {
+    /// Minimum share of the total supply at the snapshot height that has to take part in the vote.
+    pub quorum: Decimal,
+    /// Minimum share of `yes` among the `yes` and `no` votes for a proposal to pass.
+    pub threshold: Decimal,
+    /// Number of blocks a proposal stays open for voting.
+    pub voting_period: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteOption {
+    Yes,
+    No,
+    Abstain,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Open,
+    Rejected,
+    Executed,
+    /// Passed, but was not executed in time.
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: HumanAddr,
+    pub title: String,
+    pub description: String,
+    /// Messages the contract sends to itself once the proposal passes. Only admin messages,
+    /// which cannot move the contract's balance, are allowed.
+    pub msgs: Vec<HandleMsg>,
+    /// Votes are weighted by the voting power held at this height.
+    pub start_height: u64,
+    pub end_height: u64,
+    pub yes_votes: Uint128,
+    pub no_votes: Uint128,
+    pub abstain_votes: Uint128,
+    pub status: ProposalStatus,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
 pub struct InitMsg {
     pub name: String,
@@ -48,6 +324,18 @@ pub struct InitMsg {
     pub prng_seed: Binary,
     pub config: Option<msg::InitConfig>,
     pub marketing_info: Option<MarketingInfo>,
+    /// Enables governance. The contract becomes its own admin, so admin-only messages
+    /// can only be run through passed proposals, and `admin` must be left unset.
+    pub governance: Option<GovernanceConfig>,
+    pub reward_tokens: Option<Vec<RewardToken>>,
+    /// Caps the total supply. Mints that would go over it fail.
+    pub supply_cap: Option<Uint128>,
+    pub transfer_fee: Option<TransferFee>,
+    /// Locks the initial balances into streams that release them linearly over this many
+    /// blocks.
+    pub vesting_period: Option<u64>,
+    pub migrated_from: Option<MigrationSource>,
+    pub init_callback: Option<InitCallback>,
 }
 
 impl Into<msg::InitMsg> for InitMsg {
@@ -211,6 +499,243 @@ pub enum HandleMsg {
         marketing_info: Option<MarketingInfo>,
         padding: Option<String>,
     },
+
+    // Governance
+    Propose {
+        title: String,
+        description: String,
+        msgs: Option<Vec<HandleMsg>>,
+        padding: Option<String>,
+    },
+    CastVote {
+        proposal_id: u64,
+        vote: VoteOption,
+        padding: Option<String>,
+    },
+    ExecuteProposal {
+        proposal_id: u64,
+        padding: Option<String>,
+    },
+
+    // Delegation
+    Delegate {
+        delegatee: HumanAddr,
+        padding: Option<String>,
+    },
+    Undelegate {
+        padding: Option<String>,
+    },
+    /// Lets the listed contracts, such as governance contracts, query anyone's voting power with
+    /// their own viewing key. Replaces the previous list.
+    SetVotingPowerReaders {
+        readers: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
+
+    // Dividends
+    DepositDividends {
+        padding: Option<String>,
+    },
+    ClaimDividends {
+        padding: Option<String>,
+    },
+    AddRewardToken {
+        token: RewardToken,
+        padding: Option<String>,
+    },
+    /// Called by reward tokens when they are sent to snipix.
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
+    },
+
+    // Claims
+    /// Escrows `amount` for whoever presents the secret whose SHA-256 digest is `hash`,
+    /// until `expiration` (seconds since epoch).
+    CreateClaim {
+        hash: Binary,
+        amount: Uint128,
+        expiration: u64,
+        padding: Option<String>,
+    },
+    Claim {
+        secret: String,
+        padding: Option<String>,
+    },
+    Reclaim {
+        hash: Binary,
+        padding: Option<String>,
+    },
+
+    // Hashed time-lock swaps
+    /// Locks `amount` for `recipient` until the SHA-256 preimage of `hash_lock` is presented,
+    /// or refunds it once `timeout_height` is reached.
+    CreateSwap {
+        recipient: HumanAddr,
+        amount: Uint128,
+        hash_lock: Binary,
+        timeout_height: u64,
+        padding: Option<String>,
+    },
+    ClaimSwap {
+        swap_id: u64,
+        preimage: Binary,
+        padding: Option<String>,
+    },
+    RefundSwap {
+        swap_id: u64,
+        padding: Option<String>,
+    },
+
+    // Streams
+    /// Escrows `amount` to be released linearly to `recipient` between `start_height` and
+    /// `end_height`.
+    CreateStream {
+        recipient: HumanAddr,
+        amount: Uint128,
+        start_height: u64,
+        end_height: u64,
+        padding: Option<String>,
+    },
+    WithdrawStream {
+        stream_id: u64,
+        padding: Option<String>,
+    },
+    CancelStream {
+        stream_id: u64,
+        padding: Option<String>,
+    },
+
+    // Recurring allowances
+    /// Lets `spender` spend up to `amount` every `period`, in place of its flat allowance.
+    SetRecurringAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        period: AllowancePeriod,
+        max_per_tx: Option<Uint128>,
+        padding: Option<String>,
+    },
+    RemoveRecurringAllowance {
+        spender: HumanAddr,
+        padding: Option<String>,
+    },
+
+    // Transfer permits
+    TransferWithPermit {
+        permit: TransferPermit,
+        padding: Option<String>,
+    },
+
+    // Invoices
+    /// Creates an invoice payable to the sender, with an id unique among the sender's invoices.
+    CreateInvoice {
+        invoice_id: String,
+        amount: Uint128,
+        payer: Option<HumanAddr>,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    PayInvoice {
+        payee: HumanAddr,
+        invoice_id: String,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    RefundInvoice {
+        invoice_id: String,
+        payer: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+
+    // Scheduled transfers
+    /// Schedules `count` transfers (one by default), the first at `start_height` and the next
+    /// ones every `interval` blocks. With `escrow`, every transfer and tip is locked up front.
+    /// Otherwise each is pulled from the sender's balance when executed, and skipped until a
+    /// later run whenever the balance cannot cover it.
+    CreateSchedule {
+        recipient: HumanAddr,
+        amount: Uint128,
+        start_height: u64,
+        interval: Option<u64>,
+        count: Option<u32>,
+        tip: Option<Uint128>,
+        memo: Option<String>,
+        escrow: Option<bool>,
+        padding: Option<String>,
+    },
+    /// Executes the due schedules among `schedule_ids`, collecting their tips. Due schedules
+    /// whose owner cannot currently cover them fail, and stay due.
+    ExecuteSchedules {
+        schedule_ids: Vec<u64>,
+        padding: Option<String>,
+    },
+    CancelSchedule {
+        schedule_id: u64,
+        padding: Option<String>,
+    },
+
+    // Escrows
+    /// Locks `amount` for `seller` until the buyer releases it, the seller refunds it or the
+    /// arbiter splits it. Past `deadline` (a block height), it can only return to the buyer.
+    CreateEscrow {
+        seller: HumanAddr,
+        arbiter: HumanAddr,
+        amount: Uint128,
+        deadline: u64,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    ReleaseEscrow {
+        escrow_id: u64,
+        padding: Option<String>,
+    },
+    RefundEscrow {
+        escrow_id: u64,
+        padding: Option<String>,
+    },
+    ResolveEscrow {
+        escrow_id: u64,
+        seller_amount: Uint128,
+        padding: Option<String>,
+    },
+    ExpireEscrow {
+        escrow_id: u64,
+        padding: Option<String>,
+    },
+
+    // Inheritance
+    /// Lets `beneficiary` claim the sender's balance once the sender hasn't sent any message
+    /// for `inactivity_period` seconds.
+    SetBeneficiary {
+        beneficiary: HumanAddr,
+        inactivity_period: u64,
+        padding: Option<String>,
+    },
+    RemoveBeneficiary {
+        padding: Option<String>,
+    },
+    ClaimInheritance {
+        owner: HumanAddr,
+        padding: Option<String>,
+    },
+
+    // Migration
+    /// Has the factory launch the token again from `code_version`, or from its latest code
+    /// version.
+    Migrate {
+        code_version: Option<u32>,
+        padding: Option<String>,
+    },
+    /// Sent by the factory once the new version of the token is launched. Holders can then only
+    /// send their balances to `successor`.
+    CompleteMigration {
+        successor: MigrationSource,
+        padding: Option<String>,
+    },
 }
 
 impl TryInto<msg::HandleMsg> for HandleMsg {
@@ -492,6 +1017,152 @@ pub enum HandleAnswer {
     SetMarketingInfo {
         status: msg::ResponseStatus,
     },
+
+    // Governance
+    Propose {
+        proposal_id: u64,
+    },
+    CastVote {
+        status: msg::ResponseStatus,
+    },
+    ExecuteProposal {
+        status: ProposalStatus,
+    },
+
+    // Delegation
+    Delegate {
+        status: msg::ResponseStatus,
+    },
+    Undelegate {
+        status: msg::ResponseStatus,
+    },
+    SetVotingPowerReaders {
+        status: msg::ResponseStatus,
+    },
+
+    // Dividends
+    DepositDividends {
+        status: msg::ResponseStatus,
+    },
+    ClaimDividends {
+        amount: Vec<Coin>,
+        token_amounts: Vec<TokenAmount>,
+    },
+    AddRewardToken {
+        status: msg::ResponseStatus,
+    },
+    Receive {
+        status: msg::ResponseStatus,
+    },
+
+    // Claims
+    CreateClaim {
+        status: msg::ResponseStatus,
+    },
+    Claim {
+        amount: Uint128,
+    },
+    Reclaim {
+        amount: Uint128,
+    },
+
+    // Hashed time-lock swaps
+    CreateSwap {
+        swap_id: u64,
+    },
+    ClaimSwap {
+        preimage: Binary,
+    },
+    RefundSwap {
+        amount: Uint128,
+    },
+
+    // Streams
+    CreateStream {
+        stream_id: u64,
+    },
+    WithdrawStream {
+        amount: Uint128,
+    },
+    CancelStream {
+        recipient_amount: Uint128,
+        sender_amount: Uint128,
+    },
+
+    // Recurring allowances
+    SetRecurringAllowance {
+        status: msg::ResponseStatus,
+    },
+    RemoveRecurringAllowance {
+        status: msg::ResponseStatus,
+    },
+
+    // Transfer permits
+    TransferWithPermit {
+        owner: HumanAddr,
+        relayer_fee: Uint128,
+    },
+
+    // Invoices
+    CreateInvoice {
+        status: msg::ResponseStatus,
+    },
+    PayInvoice {
+        status: InvoiceStatus,
+    },
+    RefundInvoice {
+        status: InvoiceStatus,
+    },
+
+    // Scheduled transfers
+    CreateSchedule {
+        schedule_id: u64,
+    },
+    ExecuteSchedules {
+        executed: Vec<u64>,
+        failed: Vec<u64>,
+        tips: Uint128,
+    },
+    CancelSchedule {
+        status: msg::ResponseStatus,
+    },
+
+    // Escrows
+    CreateEscrow {
+        escrow_id: u64,
+    },
+    ReleaseEscrow {
+        amount: Uint128,
+    },
+    RefundEscrow {
+        amount: Uint128,
+    },
+    ResolveEscrow {
+        seller_amount: Uint128,
+        buyer_amount: Uint128,
+    },
+    ExpireEscrow {
+        amount: Uint128,
+    },
+
+    // Inheritance
+    SetBeneficiary {
+        status: msg::ResponseStatus,
+    },
+    RemoveBeneficiary {
+        status: msg::ResponseStatus,
+    },
+    ClaimInheritance {
+        amount: Uint128,
+    },
+
+    // Migration
+    Migrate {
+        status: msg::ResponseStatus,
+    },
+    CompleteMigration {
+        status: msg::ResponseStatus,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
@@ -525,9 +1196,107 @@ pub enum QueryMsg {
     Minters {},
     WithPermit {
         permit: Permit,
-        query: msg::QueryWithPermit,
+        query: QueryWithPermit,
     },
     MarketingInfo {},
+    SupplyCap {},
+    TransferFee {},
+    GovernanceConfig {},
+    Proposal {
+        proposal_id: u64,
+    },
+    Proposals {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    BalanceAt {
+        address: HumanAddr,
+        key: String,
+        height: u64,
+    },
+    TotalSupplyAt {
+        height: u64,
+    },
+    /// `reader` has to be one of the voting power readers, and `key` its viewing key.
+    VotingPower {
+        address: HumanAddr,
+        height: u64,
+        reader: HumanAddr,
+        key: String,
+    },
+    VotingPowerReaders {},
+    UnclaimedDividends {
+        address: HumanAddr,
+        key: String,
+    },
+    RewardTokens {},
+    Claims {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// Parties and amount are only included when queried by one of them.
+    Swap {
+        swap_id: u64,
+        address: Option<HumanAddr>,
+        key: Option<String>,
+    },
+    Streams {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// `key` can be the viewing key of either the owner or the spender.
+    RecurringAllowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        key: String,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// `time` is the current time in seconds since epoch, which queries don't know, to tell
+    /// whether the invoice has expired.
+    Invoice {
+        payee: HumanAddr,
+        key: String,
+        invoice_id: String,
+        time: u64,
+    },
+    Invoices {
+        payee: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+        time: u64,
+    },
+    Schedules {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    DueSchedules {
+        start_after: Option<u64>,
+        limit: u32,
+    },
+    Escrows {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
 }
 
 impl TryInto<msg::QueryMsg> for QueryMsg {
@@ -572,8 +1341,105 @@ impl TryInto<msg::QueryMsg> for QueryMsg {
                 page_size,
             }),
             QueryMsg::Minters {} => Ok(msg::QueryMsg::Minters {}),
-            QueryMsg::WithPermit { permit, query } => {
-                Ok(msg::QueryMsg::WithPermit { permit, query })
+            QueryMsg::WithPermit { permit, query } => Ok(msg::QueryMsg::WithPermit {
+                permit,
+                query: query.try_into()?,
+            }),
+            _ => Err(StdError::generic_err("Cannot convert into the target type")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Allowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    Balance {},
+    TransferHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    TransactionHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    BalanceAt {
+        height: u64,
+    },
+    /// `address` has to be the signer of the permit.
+    VotingPower {
+        address: HumanAddr,
+        height: u64,
+    },
+    UnclaimedDividends {},
+    Claims {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Swap {
+        swap_id: u64,
+    },
+    Streams {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    RecurringAllowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+    },
+    /// `owner` has to be the signer of the permit.
+    AllowancesGiven {
+        owner: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// `spender` has to be the signer of the permit.
+    AllowancesReceived {
+        spender: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// `time` is the current time in seconds since epoch.
+    Invoice {
+        invoice_id: String,
+        time: u64,
+    },
+    Invoices {
+        page: Option<u32>,
+        page_size: u32,
+        time: u64,
+    },
+    Schedules {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Escrows {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// The signer has to be the owner or the beneficiary. `owner` defaults to the signer.
+    Inheritance {
+        owner: Option<HumanAddr>,
+    },
+}
+
+impl TryInto<msg::QueryWithPermit> for QueryWithPermit {
+    type Error = StdError;
+
+    fn try_into(self) -> Result<msg::QueryWithPermit, Self::Error> {
+        match self {
+            QueryWithPermit::Allowance { owner, spender } => {
+                Ok(msg::QueryWithPermit::Allowance { owner, spender })
+            }
+            QueryWithPermit::Balance {} => Ok(msg::QueryWithPermit::Balance {}),
+            QueryWithPermit::TransferHistory { page, page_size } => {
+                Ok(msg::QueryWithPermit::TransferHistory { page, page_size })
+            }
+            QueryWithPermit::TransactionHistory { page, page_size } => {
+                Ok(msg::QueryWithPermit::TransactionHistory { page, page_size })
             }
             _ => Err(StdError::generic_err("Cannot convert into the target type")),
         }
@@ -629,4 +1495,88 @@ pub enum QueryAnswer {
     MarketingInfo {
         marketing_info: Option<MarketingInfo>,
     },
+    SupplyCap {
+        supply_cap: Option<Uint128>,
+    },
+    TransferFee {
+        transfer_fee: Option<TransferFee>,
+    },
+    GovernanceConfig {
+        config: Option<GovernanceConfig>,
+    },
+    Proposal {
+        proposal: Proposal,
+    },
+    Proposals {
+        proposals: Vec<Proposal>,
+        total: u64,
+    },
+    BalanceAt {
+        amount: Uint128,
+        height: u64,
+    },
+    TotalSupplyAt {
+        total_supply: Uint128,
+        height: u64,
+    },
+    VotingPower {
+        address: HumanAddr,
+        voting_power: Uint128,
+        height: u64,
+    },
+    VotingPowerReaders {
+        readers: Vec<HumanAddr>,
+    },
+    UnclaimedDividends {
+        dividends: Vec<Coin>,
+        token_dividends: Vec<TokenAmount>,
+    },
+    RewardTokens {
+        reward_tokens: Vec<RewardToken>,
+    },
+    Claims {
+        claims: Vec<ClaimInfo>,
+        total: u64,
+    },
+    Swap {
+        swap: SwapInfo,
+    },
+    Streams {
+        streams: Vec<StreamInfo>,
+        total: u64,
+    },
+    RecurringAllowance {
+        owner: HumanAddr,
+        spender: HumanAddr,
+        allowance: Option<RecurringAllowanceInfo>,
+    },
+    AllowancesGiven {
+        allowances: Vec<AllowanceInfo>,
+        total: u64,
+    },
+    AllowancesReceived {
+        allowances: Vec<AllowanceInfo>,
+        total: u64,
+    },
+    Invoice {
+        invoice: InvoiceInfo,
+    },
+    Invoices {
+        invoices: Vec<InvoiceInfo>,
+        total: u64,
+    },
+    Schedules {
+        schedules: Vec<ScheduleInfo>,
+        total: u64,
+    },
+    DueSchedules {
+        schedules: Vec<DueSchedule>,
+    },
+    Escrows {
+        escrows: Vec<EscrowInfo>,
+        total: u64,
+    },
+    Inheritance {
+        inheritance: Option<InheritanceInfo>,
+    },
 }
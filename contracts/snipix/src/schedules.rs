@@ -0,0 +1,417 @@
+use crate::{
+    balances::{balance_of, lock_escrow, move_balance, release_escrow},
+    lists::{page_ids, push_id, remove_id},
+    msg::{DueSchedule, HandleAnswer, QueryAnswer, ScheduleInfo},
+    transfer_fee::transfer_fee_for,
+};
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// A transfer from `owner` to `recipient`, made whenever a keeper executes it at or after
+/// `next_height`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub owner: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub amount: Uint128,
+    pub next_height: u64,
+    pub interval: Option<u64>,
+    /// Executions left before the schedule is done.
+    pub remaining: u32,
+    /// Paid by the owner to the keeper on each execution.
+    pub tip: Uint128,
+    pub memo: Option<String>,
+    /// Whether the remaining executions are held in escrow rather than pulled from the owner's
+    /// balance.
+    pub escrowed: bool,
+    /// Position of the schedule in the owner's list of schedules.
+    pub index: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_create_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    amount: Uint128,
+    start_height: u64,
+    interval: Option<u64>,
+    count: Option<u32>,
+    tip: Option<Uint128>,
+    memo: Option<String>,
+    escrow: bool,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot schedule an empty amount"));
+    }
+    if start_height <= env.block.height {
+        return Err(StdError::generic_err("Schedule must start in the future"));
+    }
+    let count = count.unwrap_or(1);
+    if count == 0 {
+        return Err(StdError::generic_err("Schedule must execute at least once"));
+    }
+    if interval == Some(0) {
+        return Err(StdError::generic_err("Interval cannot be empty"));
+    }
+    if interval.is_none() && count > 1 {
+        return Err(StdError::generic_err(
+            "Recurring schedules require an interval",
+        ));
+    }
+    if let Some(interval) = interval {
+        interval
+            .checked_mul(count as u64 - 1)
+            .and_then(|span| span.checked_add(start_height))
+            .ok_or_else(|| StdError::generic_err("Schedule ends too far in the future"))?;
+    }
+    let tip = tip.unwrap_or_default();
+    let required = execution_cost(amount, tip)?;
+    if escrow {
+        let total = required
+            .checked_mul(count as u128)
+            .ok_or_else(|| StdError::generic_err("Schedule total overflows"))?;
+        lock_escrow(
+            deps,
+            &env,
+            &env.message.sender,
+            total,
+            Some("Create schedule".to_string()),
+        )?;
+    }
+
+    let schedule_id = read_schedule_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let index = push_id(
+        &mut deps.storage,
+        PREFIX_OWNER_SCHEDULES,
+        &owner,
+        &schedule_id.to_be_bytes(),
+    )?;
+
+    write_schedule_count(&mut deps.storage).save(&schedule_id)?;
+    write_schedules(&mut deps.storage).save(
+        &schedule_id.to_be_bytes(),
+        &Schedule {
+            owner,
+            recipient: deps.api.canonical_address(&recipient)?,
+            amount,
+            next_height: start_height,
+            interval,
+            remaining: count,
+            tip,
+            memo,
+            escrowed: escrow,
+            index,
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateSchedule { schedule_id })?),
+    })
+}
+
+/// Executes whichever of `schedule_ids` are due, tipping the sender. Schedules that are not due
+/// are skipped, as are due schedules pulling from a balance that cannot currently cover the amount,
+/// the tip and their transfer fees. Those are reported as failed, and left for keepers to retry;
+/// only their owner can cancel them.
+pub fn handle_execute_schedules<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    schedule_ids: Vec<u64>,
+) -> StdResult<HandleResponse> {
+    let mut executed = vec![];
+    let mut failed = vec![];
+    let mut tips = 0u128;
+
+    for schedule_id in schedule_ids {
+        let mut schedule =
+            match read_schedules(&deps.storage).may_load(&schedule_id.to_be_bytes())? {
+                Some(schedule) => schedule,
+                None => continue,
+            };
+        if schedule.next_height > env.block.height {
+            continue;
+        }
+        let owner = deps.api.human_address(&schedule.owner)?;
+        let recipient = deps.api.human_address(&schedule.recipient)?;
+        if !schedule.escrowed
+            && balance_of(&deps.storage, &schedule.owner)
+                < pulled_amount(deps, &env, &schedule, &owner, &recipient)?
+        {
+            failed.push(schedule_id);
+            continue;
+        }
+
+        schedule.remaining -= 1;
+        match schedule.interval {
+            Some(interval) if schedule.remaining > 0 => {
+                schedule.next_height = schedule
+                    .next_height
+                    .checked_add(interval)
+                    .ok_or_else(|| StdError::generic_err("Schedule height overflow"))?;
+                write_schedules(&mut deps.storage).save(&schedule_id.to_be_bytes(), &schedule)?;
+            }
+            _ => remove_schedule(&mut deps.storage, schedule_id, &schedule)?,
+        }
+
+        if schedule.escrowed {
+            release_escrow(
+                deps,
+                &env,
+                &recipient,
+                schedule.amount.u128(),
+                schedule.memo.clone(),
+            )?;
+            if !schedule.tip.is_zero() {
+                release_escrow(
+                    deps,
+                    &env,
+                    &env.message.sender,
+                    schedule.tip.u128(),
+                    Some("Keeper tip".to_string()),
+                )?;
+            }
+        } else {
+            move_balance(
+                deps,
+                &env,
+                &owner,
+                &recipient,
+                schedule.amount.u128(),
+                schedule.memo.clone(),
+            )?;
+            if !schedule.tip.is_zero() {
+                move_balance(
+                    deps,
+                    &env,
+                    &owner,
+                    &env.message.sender,
+                    schedule.tip.u128(),
+                    Some("Keeper tip".to_string()),
+                )?;
+            }
+        }
+
+        executed.push(schedule_id);
+        tips = tips
+            .checked_add(schedule.tip.u128())
+            .ok_or_else(|| StdError::generic_err("Keeper tips overflow"))?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ExecuteSchedules {
+            executed,
+            failed,
+            tips: Uint128(tips),
+        })?),
+    })
+}
+
+/// Amount and tip an execution of a schedule takes from its owner.
+fn execution_cost(amount: Uint128, tip: Uint128) -> StdResult<u128> {
+    amount
+        .u128()
+        .checked_add(tip.u128())
+        .ok_or_else(|| StdError::generic_err("Schedule amount and tip overflow"))
+}
+
+/// Everything an execution of a schedule pulls from its owner's balance, transfer fees included.
+fn pulled_amount<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    schedule: &Schedule,
+    owner: &HumanAddr,
+    recipient: &HumanAddr,
+) -> StdResult<u128> {
+    let transfers = [
+        (recipient, schedule.amount.u128()),
+        (&env.message.sender, schedule.tip.u128()),
+    ];
+
+    let mut pulled = 0u128;
+    for (to, amount) in transfers.iter() {
+        if *amount == 0 {
+            continue;
+        }
+        let fee = transfer_fee_for(&deps.storage, owner, to, *amount)?.map_or(0, |(_, fee)| fee);
+        pulled = pulled
+            .checked_add(*amount)
+            .and_then(|pulled| pulled.checked_add(fee))
+            .ok_or_else(|| StdError::generic_err("Schedule amount and tip overflow"))?;
+    }
+
+    Ok(pulled)
+}
+
+pub fn handle_cancel_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    schedule_id: u64,
+) -> StdResult<HandleResponse> {
+    let schedule = load_schedule(&deps.storage, schedule_id)?;
+    if schedule.owner != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the owner of a schedule can cancel it",
+        ));
+    }
+
+    remove_schedule(&mut deps.storage, schedule_id, &schedule)?;
+    if schedule.escrowed {
+        // Cannot overflow, as it was locked up front.
+        let refund = execution_cost(schedule.amount, schedule.tip)? * schedule.remaining as u128;
+        release_escrow(
+            deps,
+            &env,
+            &env.message.sender,
+            refund,
+            Some("Cancel schedule".to_string()),
+        )?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CancelSchedule {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+/// Lists the active schedules of `address`, most recent first.
+pub fn query_schedules<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let owner = deps.api.canonical_address(address)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        PREFIX_OWNER_SCHEDULES,
+        &owner,
+        page,
+        page_size,
+    )?;
+
+    let schedules = ids
+        .into_iter()
+        .map(|id| {
+            let schedule_id = schedule_id_from_bytes(&id)?;
+            let schedule = load_schedule(&deps.storage, schedule_id)?;
+            Ok(ScheduleInfo {
+                id: schedule_id,
+                recipient: deps.api.human_address(&schedule.recipient)?,
+                amount: schedule.amount,
+                next_height: schedule.next_height,
+                interval: schedule.interval,
+                remaining: schedule.remaining,
+                tip: schedule.tip,
+                memo: schedule.memo,
+                escrowed: schedule.escrowed,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Schedules { schedules, total })
+}
+
+/// Lists active schedules by id, along with when they are next due, so keepers can find work
+/// without learning who pays whom.
+pub fn query_due_schedules<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: u32,
+) -> StdResult<Binary> {
+    let count = read_schedule_count(&deps.storage)
+        .may_load()?
+        .unwrap_or_default();
+    let schedules = read_schedules(&deps.storage);
+
+    let mut due = vec![];
+    let mut schedule_id = start_after.unwrap_or(0);
+    while schedule_id < count && due.len() < limit as usize {
+        schedule_id += 1;
+        if let Some(schedule) = schedules.may_load(&schedule_id.to_be_bytes())? {
+            due.push(DueSchedule {
+                id: schedule_id,
+                next_height: schedule.next_height,
+                tip: schedule.tip,
+            });
+        }
+    }
+
+    to_binary(&QueryAnswer::DueSchedules { schedules: due })
+}
+
+fn load_schedule<S: ReadonlyStorage>(storage: &S, schedule_id: u64) -> StdResult<Schedule> {
+    read_schedules(storage)
+        .may_load(&schedule_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Schedule not found"))
+}
+
+fn schedule_id_from_bytes(bytes: &[u8]) -> StdResult<u64> {
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| StdError::generic_err("Corrupted schedule id"))?;
+
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Drops the schedule, along with its place in the owner's list.
+fn remove_schedule<S: Storage>(
+    storage: &mut S,
+    schedule_id: u64,
+    schedule: &Schedule,
+) -> StdResult<()> {
+    write_schedules(storage).remove(&schedule_id.to_be_bytes());
+
+    if let Some(moved) = remove_id(
+        storage,
+        PREFIX_OWNER_SCHEDULES,
+        &schedule.owner,
+        schedule.index,
+    )? {
+        let mut moved_schedule = load_schedule(&*storage, schedule_id_from_bytes(&moved)?)?;
+        moved_schedule.index = schedule.index;
+        write_schedules(storage).save(&moved, &moved_schedule)?;
+    }
+
+    Ok(())
+}
+
+pub const PREFIX_SCHEDULE_COUNT: &[u8] = b"schedule_count";
+pub const PREFIX_SCHEDULES: &[u8] = b"schedules";
+pub const PREFIX_OWNER_SCHEDULES: &[u8] = b"owner_schedules";
+
+pub fn read_schedule_count<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, PREFIX_SCHEDULE_COUNT)
+}
+
+pub fn write_schedule_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, PREFIX_SCHEDULE_COUNT)
+}
+
+pub fn read_schedules<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Schedule> {
+    bucket_read(PREFIX_SCHEDULES, storage)
+}
+
+pub fn write_schedules<S: Storage>(storage: &mut S) -> Bucket<S, Schedule> {
+    bucket(PREFIX_SCHEDULES, storage)
+}
@@ -0,0 +1,365 @@
+use crate::{
+    balances::{lock_escrow, release_escrow},
+    lists::{page_ids, push_id, remove_id},
+    msg::{HandleAnswer, QueryAnswer, StreamInfo},
+};
+use atl_snip20_reference_impl::msg::InitialBalance;
+use cosmwasm_std::{
+    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// Tokens escrowed by `sender` and released linearly to `recipient` between `start_height` and
+/// `end_height`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stream {
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub amount: Uint128,
+    pub withdrawn: Uint128,
+    pub start_height: u64,
+    pub end_height: u64,
+    /// Positions of the stream in the sender's and the recipient's lists of streams.
+    pub sender_index: u32,
+    pub recipient_index: u32,
+}
+
+impl Stream {
+    /// Amount released to the recipient as of `height`, including what was already withdrawn.
+    fn accrued(&self, height: u64) -> u128 {
+        if height <= self.start_height {
+            0
+        } else if height >= self.end_height {
+            self.amount.u128()
+        } else {
+            // Splits `amount * elapsed / duration` so that no product can overflow, which
+            // `Uint128::multiply_ratio` does not guard against either.
+            let elapsed = (height - self.start_height) as u128;
+            let duration = (self.end_height - self.start_height) as u128;
+            let amount = self.amount.u128();
+            amount / duration * elapsed + amount % duration * elapsed / duration
+        }
+    }
+}
+
+pub fn handle_create_stream<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    amount: Uint128,
+    start_height: u64,
+    end_height: u64,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot stream an empty amount"));
+    }
+    if start_height < env.block.height {
+        return Err(StdError::generic_err("Stream cannot start in the past"));
+    }
+    if end_height <= start_height {
+        return Err(StdError::generic_err(
+            "Stream must end after its start height",
+        ));
+    }
+    if recipient == env.message.sender {
+        return Err(StdError::generic_err("Cannot stream to yourself"));
+    }
+
+    lock_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        amount.u128(),
+        Some("Create stream".to_string()),
+    )?;
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&recipient)?;
+    let stream_id = save_stream(
+        &mut deps.storage,
+        sender,
+        recipient,
+        amount,
+        start_height,
+        end_height,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateStream { stream_id })?),
+    })
+}
+
+/// Locks the initial balances into streams from the token itself, released linearly over
+/// `vesting_period` blocks from launch. Only the token, e.g. through a governance proposal, can
+/// cancel them.
+pub fn init_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    initial_balances: &[InitialBalance],
+    vesting_period: u64,
+) -> StdResult<()> {
+    if vesting_period == 0 {
+        return Err(StdError::generic_err(
+            "Vesting period must be at least 1 block",
+        ));
+    }
+
+    let contract = deps.api.canonical_address(&env.contract.address)?;
+    for balance in initial_balances
+        .iter()
+        .filter(|balance| !balance.amount.is_zero())
+    {
+        lock_escrow(
+            deps,
+            env,
+            &balance.address,
+            balance.amount.u128(),
+            Some("Vesting".to_string()),
+        )?;
+        let recipient = deps.api.canonical_address(&balance.address)?;
+        save_stream(
+            &mut deps.storage,
+            contract.clone(),
+            recipient,
+            balance.amount,
+            env.block.height,
+            env.block.height + vesting_period,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Sends the recipient everything accrued since their last withdrawal.
+pub fn handle_withdraw_stream<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stream_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut stream = load_stream(&deps.storage, stream_id)?;
+    if stream.recipient != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the recipient of a stream can withdraw from it",
+        ));
+    }
+
+    let amount = stream.accrued(env.block.height) - stream.withdrawn.u128();
+    if amount == 0 {
+        return Err(StdError::generic_err("Nothing to withdraw"));
+    }
+
+    stream.withdrawn = Uint128(stream.withdrawn.u128() + amount);
+    if stream.withdrawn == stream.amount {
+        remove_stream(&mut deps.storage, stream_id, &stream)?;
+    } else {
+        write_streams(&mut deps.storage).save(&stream_id.to_be_bytes(), &stream)?;
+    }
+
+    release_escrow(
+        deps,
+        &env,
+        &env.message.sender,
+        amount,
+        Some("Withdraw stream".to_string()),
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::WithdrawStream {
+            amount: Uint128(amount),
+        })?),
+    })
+}
+
+/// Pays out what has accrued to the recipient and returns the rest to the sender.
+pub fn handle_cancel_stream<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stream_id: u64,
+) -> StdResult<HandleResponse> {
+    let stream = load_stream(&deps.storage, stream_id)?;
+    if stream.sender != deps.api.canonical_address(&env.message.sender)? {
+        return Err(StdError::generic_err(
+            "Only the sender of a stream can cancel it",
+        ));
+    }
+
+    let accrued = stream.accrued(env.block.height);
+    let recipient_amount = accrued - stream.withdrawn.u128();
+    let sender_amount = stream.amount.u128() - accrued;
+
+    remove_stream(&mut deps.storage, stream_id, &stream)?;
+
+    if recipient_amount > 0 {
+        let recipient = deps.api.human_address(&stream.recipient)?;
+        release_escrow(
+            deps,
+            &env,
+            &recipient,
+            recipient_amount,
+            Some("Cancel stream".to_string()),
+        )?;
+    }
+    if sender_amount > 0 {
+        release_escrow(
+            deps,
+            &env,
+            &env.message.sender,
+            sender_amount,
+            Some("Cancel stream".to_string()),
+        )?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CancelStream {
+            recipient_amount: Uint128(recipient_amount),
+            sender_amount: Uint128(sender_amount),
+        })?),
+    })
+}
+
+/// Lists the active streams `address` is sending or receiving, most recent first.
+pub fn query_streams<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let account = deps.api.canonical_address(address)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        PREFIX_ACCOUNT_STREAMS,
+        &account,
+        page,
+        page_size,
+    )?;
+
+    let streams = ids
+        .into_iter()
+        .map(|id| {
+            let stream_id = stream_id_from_bytes(&id)?;
+            let stream = load_stream(&deps.storage, stream_id)?;
+            Ok(StreamInfo {
+                id: stream_id,
+                sender: deps.api.human_address(&stream.sender)?,
+                recipient: deps.api.human_address(&stream.recipient)?,
+                amount: stream.amount,
+                withdrawn: stream.withdrawn,
+                start_height: stream.start_height,
+                end_height: stream.end_height,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::Streams { streams, total })
+}
+
+fn load_stream<S: ReadonlyStorage>(storage: &S, stream_id: u64) -> StdResult<Stream> {
+    read_streams(storage)
+        .may_load(&stream_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err("Stream not found"))
+}
+
+fn stream_id_from_bytes(bytes: &[u8]) -> StdResult<u64> {
+    let bytes = bytes
+        .try_into()
+        .map_err(|_| StdError::generic_err("Corrupted stream id"))?;
+
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Stores a new stream and adds it to the sender's and the recipient's lists. Returns its id.
+fn save_stream<S: Storage>(
+    storage: &mut S,
+    sender: CanonicalAddr,
+    recipient: CanonicalAddr,
+    amount: Uint128,
+    start_height: u64,
+    end_height: u64,
+) -> StdResult<u64> {
+    let stream_id = read_stream_count(storage).may_load()?.unwrap_or_default() + 1;
+    let sender_index = push_id(
+        storage,
+        PREFIX_ACCOUNT_STREAMS,
+        &sender,
+        &stream_id.to_be_bytes(),
+    )?;
+    let recipient_index = push_id(
+        storage,
+        PREFIX_ACCOUNT_STREAMS,
+        &recipient,
+        &stream_id.to_be_bytes(),
+    )?;
+
+    write_stream_count(storage).save(&stream_id)?;
+    write_streams(storage).save(
+        &stream_id.to_be_bytes(),
+        &Stream {
+            sender,
+            recipient,
+            amount,
+            withdrawn: Uint128::zero(),
+            start_height,
+            end_height,
+            sender_index,
+            recipient_index,
+        },
+    )?;
+
+    Ok(stream_id)
+}
+
+/// Drops the stream, along with its place in the sender's and the recipient's lists.
+fn remove_stream<S: Storage>(storage: &mut S, stream_id: u64, stream: &Stream) -> StdResult<()> {
+    write_streams(storage).remove(&stream_id.to_be_bytes());
+
+    let lists = [
+        (&stream.sender, stream.sender_index),
+        (&stream.recipient, stream.recipient_index),
+    ];
+    for &(account, index) in lists.iter() {
+        if let Some(moved) = remove_id(storage, PREFIX_ACCOUNT_STREAMS, account, index)? {
+            let moved_id = stream_id_from_bytes(&moved)?;
+            let mut moved_stream = load_stream(&*storage, moved_id)?;
+            if &moved_stream.sender == account {
+                moved_stream.sender_index = index;
+            } else {
+                moved_stream.recipient_index = index;
+            }
+            write_streams(storage).save(&moved, &moved_stream)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub const PREFIX_STREAM_COUNT: &[u8] = b"stream_count";
+pub const PREFIX_STREAMS: &[u8] = b"streams";
+pub const PREFIX_ACCOUNT_STREAMS: &[u8] = b"account_streams";
+
+pub fn read_stream_count<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, PREFIX_STREAM_COUNT)
+}
+
+pub fn write_stream_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, PREFIX_STREAM_COUNT)
+}
+
+pub fn read_streams<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, Stream> {
+    bucket_read(PREFIX_STREAMS, storage)
+}
+
+pub fn write_streams<S: Storage>(storage: &mut S) -> Bucket<S, Stream> {
+    bucket(PREFIX_STREAMS, storage)
+}
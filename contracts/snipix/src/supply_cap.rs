@@ -0,0 +1,64 @@
+use crate::msg::{HandleMsg, QueryAnswer};
+use atl_snip20_reference_impl::state::ReadonlyConfig;
+use cosmwasm_std::{
+    to_binary, Api, Binary, Env, Extern, Querier, ReadonlyStorage, StdError, StdResult, Storage,
+    Uint128,
+};
+use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+
+pub fn init_supply_cap<S: Storage>(storage: &mut S, supply_cap: Uint128) -> StdResult<()> {
+    write_supply_cap(storage).save(&Some(supply_cap))?;
+    check_supply_cap(storage, 0)
+}
+
+/// Fails if minting `amount` would take the total supply over the cap.
+pub fn check_supply_cap<S: ReadonlyStorage>(storage: &S, amount: u128) -> StdResult<()> {
+    if let Some(supply_cap) = read_supply_cap(storage).may_load()?.flatten() {
+        let total_supply = ReadonlyConfig::from_storage(storage)
+            .total_supply()
+            .saturating_add(amount);
+        if total_supply > supply_cap.u128() {
+            return Err(StdError::generic_err(format!(
+                "Total supply cannot exceed the supply cap: total_supply={}, supply_cap={}",
+                total_supply, supply_cap
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Amount `msg` mints when handled by the reference implementation, deposits included.
+pub fn minted_amount(env: &Env, msg: &HandleMsg) -> u128 {
+    match msg {
+        HandleMsg::Deposit { .. } => env
+            .message
+            .sent_funds
+            .iter()
+            .filter(|coin| coin.denom == "uscrt")
+            .fold(0, |total, coin| total.saturating_add(coin.amount.u128())),
+        HandleMsg::Mint { amount, .. } => amount.u128(),
+        HandleMsg::BatchMint { actions, .. } => actions.iter().fold(0, |total, action| {
+            total.saturating_add(action.amount.u128())
+        }),
+        _ => 0,
+    }
+}
+
+pub fn query_supply_cap<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let supply_cap = read_supply_cap(&deps.storage).may_load()?.flatten();
+
+    to_binary(&QueryAnswer::SupplyCap { supply_cap })
+}
+
+pub const PREFIX_SUPPLY_CAP: &[u8] = b"supply_cap";
+
+pub fn read_supply_cap<S: ReadonlyStorage>(storage: &S) -> ReadonlySingleton<S, Option<Uint128>> {
+    singleton_read(storage, PREFIX_SUPPLY_CAP)
+}
+
+pub fn write_supply_cap<S: Storage>(storage: &mut S) -> Singleton<S, Option<Uint128>> {
+    singleton(storage, PREFIX_SUPPLY_CAP)
+}
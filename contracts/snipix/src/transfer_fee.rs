@@ -0,0 +1,155 @@
+use crate::{
+    balances::transfer_balance,
+    msg::{HandleMsg, QueryAnswer, TransferFee},
+};
+use cosmwasm_std::{
+    to_binary, Api, Binary, Decimal, Env, Extern, HumanAddr, Querier, ReadonlyStorage, StdError,
+    StdResult, Storage, Uint128,
+};
+use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+
+pub fn init_transfer_fee<S: Storage>(storage: &mut S, transfer_fee: TransferFee) -> StdResult<()> {
+    if transfer_fee.rate > Decimal::one() {
+        return Err(StdError::generic_err(
+            "Transfer fee rate cannot exceed 100%",
+        ));
+    }
+
+    write_transfer_fee(storage).save(&Some(transfer_fee))
+}
+
+/// Charges the owner of each transfer or send in `msg`, once handled by the reference
+/// implementation, the fee on top of what they moved. Transfers made outside of the reference
+/// implementation are charged by `move_balance`.
+pub fn charge_transfer_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    msg: &HandleMsg,
+) -> StdResult<()> {
+    for (owner, recipient, amount) in transfers(env, msg) {
+        if let Some((collector, fee)) = transfer_fee_for(&deps.storage, &owner, &recipient, amount)?
+        {
+            transfer_balance(
+                deps,
+                env,
+                &owner,
+                &collector,
+                fee,
+                Some("Transfer fee".to_string()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the collector and the fee owed on a transfer of `amount`, if any. Transfers from or to
+/// the collector are free.
+pub fn transfer_fee_for<S: ReadonlyStorage>(
+    storage: &S,
+    from: &HumanAddr,
+    to: &HumanAddr,
+    amount: u128,
+) -> StdResult<Option<(HumanAddr, u128)>> {
+    let transfer_fee = match read_transfer_fee(storage).may_load()?.flatten() {
+        Some(transfer_fee) => transfer_fee,
+        None => return Ok(None),
+    };
+    if from == &transfer_fee.collector || to == &transfer_fee.collector {
+        return Ok(None);
+    }
+
+    let fee = Uint128(amount) * transfer_fee.rate;
+    if fee.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some((transfer_fee.collector, fee.u128())))
+}
+
+/// Lists the owner, recipient and amount of each transfer or send in `msg`.
+fn transfers(env: &Env, msg: &HandleMsg) -> Vec<(HumanAddr, HumanAddr, u128)> {
+    let sender = &env.message.sender;
+
+    match msg {
+        HandleMsg::Transfer {
+            recipient, amount, ..
+        }
+        | HandleMsg::Send {
+            recipient, amount, ..
+        } => vec![(sender.clone(), recipient.clone(), amount.u128())],
+        HandleMsg::BatchTransfer { actions, .. } => actions
+            .iter()
+            .map(|action| {
+                (
+                    sender.clone(),
+                    action.recipient.clone(),
+                    action.amount.u128(),
+                )
+            })
+            .collect(),
+        HandleMsg::BatchSend { actions, .. } => actions
+            .iter()
+            .map(|action| {
+                (
+                    sender.clone(),
+                    action.recipient.clone(),
+                    action.amount.u128(),
+                )
+            })
+            .collect(),
+        HandleMsg::TransferFrom {
+            owner,
+            recipient,
+            amount,
+            ..
+        }
+        | HandleMsg::SendFrom {
+            owner,
+            recipient,
+            amount,
+            ..
+        } => vec![(owner.clone(), recipient.clone(), amount.u128())],
+        HandleMsg::BatchTransferFrom { actions, .. } => actions
+            .iter()
+            .map(|action| {
+                (
+                    action.owner.clone(),
+                    action.recipient.clone(),
+                    action.amount.u128(),
+                )
+            })
+            .collect(),
+        HandleMsg::BatchSendFrom { actions, .. } => actions
+            .iter()
+            .map(|action| {
+                (
+                    action.owner.clone(),
+                    action.recipient.clone(),
+                    action.amount.u128(),
+                )
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+pub fn query_transfer_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let transfer_fee = read_transfer_fee(&deps.storage).may_load()?.flatten();
+
+    to_binary(&QueryAnswer::TransferFee { transfer_fee })
+}
+
+pub const PREFIX_TRANSFER_FEE: &[u8] = b"transfer_fee";
+
+pub fn read_transfer_fee<S: ReadonlyStorage>(
+    storage: &S,
+) -> ReadonlySingleton<S, Option<TransferFee>> {
+    singleton_read(storage, PREFIX_TRANSFER_FEE)
+}
+
+pub fn write_transfer_fee<S: Storage>(storage: &mut S) -> Singleton<S, Option<TransferFee>> {
+    singleton(storage, PREFIX_TRANSFER_FEE)
+}
@@ -0,0 +1,119 @@
+use crate::{
+    balances::move_balance,
+    inheritance::record_activity,
+    msg::{HandleAnswer, TransferPermit},
+};
+use atl_snip20_reference_impl::contract::PREFIX_REVOKED_PERMITS;
+use cosmwasm_std::{
+    to_binary, Api, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+    ReadonlyStorage, StdError, StdResult, Storage,
+};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+use secret_toolkit::permit::{validate, Permit, PermitParams};
+
+/// Transfers from the signer of `permit` on their behalf, paying the relayer fee to the sender.
+pub fn handle_transfer_with_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit: TransferPermit,
+) -> StdResult<HandleResponse> {
+    let params = &permit.params;
+    if params.contract != env.contract.address {
+        return Err(StdError::generic_err("Permit is not valid for this token"));
+    }
+    if params.chain_id != env.block.chain_id {
+        return Err(StdError::generic_err("Permit is not valid for this chain"));
+    }
+    if env.block.time >= params.expiration {
+        return Err(StdError::generic_err("Permit has expired"));
+    }
+
+    let owner = verify_signature(deps, &permit)?;
+    let owner_raw = deps.api.canonical_address(&owner)?;
+    if read_used_nonces(&deps.storage, &owner_raw)
+        .may_load(&params.nonce.to_be_bytes())?
+        .is_some()
+    {
+        return Err(StdError::generic_err("Permit nonce was already used"));
+    }
+    write_used_nonces(&mut deps.storage, &owner_raw).save(&params.nonce.to_be_bytes(), &true)?;
+
+    record_activity(deps, &env, &owner)?;
+    move_balance(
+        deps,
+        &env,
+        &owner,
+        &params.recipient,
+        params.amount.u128(),
+        params.memo.clone(),
+    )?;
+    let relayer_fee = params.relayer_fee.unwrap_or_default();
+    if !relayer_fee.is_zero() {
+        move_balance(
+            deps,
+            &env,
+            &owner,
+            &env.message.sender,
+            relayer_fee.u128(),
+            Some("Relayer fee".to_string()),
+        )?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::TransferWithPermit {
+            owner,
+            relayer_fee,
+        })?),
+    })
+}
+
+/// Returns the address of the signer, if the signature covers the permit's params and the signer
+/// hasn't revoked it.
+///
+/// Transfer permits are signed as query permits for this token that grant no permissions, named
+/// after their params, so that wallets sign them like any other permit.
+fn verify_signature<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: &TransferPermit,
+) -> StdResult<HumanAddr> {
+    validate(
+        deps,
+        PREFIX_REVOKED_PERMITS,
+        &query_permit(permit)?,
+        permit.params.contract.clone(),
+    )
+}
+
+/// The query permit a transfer permit is signed as.
+pub fn query_permit(permit: &TransferPermit) -> StdResult<Permit> {
+    let permit_name = String::from_utf8(to_binary(&permit.params)?.0)
+        .map_err(|err| StdError::generic_err(format!("Failed to name permit: {:?}", err)))?;
+
+    Ok(Permit {
+        params: PermitParams {
+            allowed_tokens: vec![permit.params.contract.clone()],
+            permit_name,
+            chain_id: permit.params.chain_id.clone(),
+            permissions: vec![],
+        },
+        signature: permit.signature.clone(),
+    })
+}
+
+pub const PREFIX_USED_NONCES: &[u8] = b"used_nonces";
+
+pub fn read_used_nonces<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    owner: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, bool> {
+    ReadonlyBucket::multilevel(&[PREFIX_USED_NONCES, owner.as_slice()], storage)
+}
+
+pub fn write_used_nonces<'a, S: Storage>(
+    storage: &'a mut S,
+    owner: &CanonicalAddr,
+) -> Bucket<'a, S, bool> {
+    Bucket::multilevel(&[PREFIX_USED_NONCES, owner.as_slice()], storage)
+}
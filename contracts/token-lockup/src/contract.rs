@@ -0,0 +1,675 @@
+use atl_snip20_reference_impl::msg::{HandleMsg as Snip20HandleMsg, ResponseStatus};
+use atl_snip20_reference_impl::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
+use cosmwasm_std::{
+    from_binary, log, to_binary, Api, Binary, CosmosMsg, Decimal, Env, Extern, HandleResponse,
+    HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use lock_utils::lists::{page_ids, push_id};
+use lock_utils::registry::{register_token, registered_code_hash};
+
+use crate::msg::{
+    BeneficiaryDetails, BeneficiaryShare, HandleAnswer, HandleMsg, InitMsg, LockupSummary,
+    QueryAnswer, QueryMsg, ReceiveMsg, Tranche,
+};
+use crate::state::{
+    lockup_count, lockup_count_read, lockups, lockups_read, viewing_keys, viewing_keys_read,
+    Beneficiary, Lockup, BENEFICIARY_LOCKUPS_KEY, TOKEN_LOCKUPS_KEY,
+};
+
+/// Precision of `Decimal`, which shares are counted in.
+const SHARE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+pub fn init<S: Storage, A: Api, Q: Querier>(
+    _deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    _msg: InitMsg,
+) -> StdResult<InitResponse> {
+    Ok(InitResponse::default())
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        HandleMsg::RegisterToken {
+            address, code_hash, ..
+        } => try_register_token(deps, env, address, code_hash),
+        HandleMsg::Receive {
+            from, amount, msg, ..
+        } => try_receive(deps, env, from, amount, msg),
+        HandleMsg::Claim { lockup_id, .. } => try_claim(deps, env, lockup_id),
+        HandleMsg::SetViewingKey { key, .. } => try_set_viewing_key(deps, env, key),
+    }
+}
+
+/// Anyone can register a token, once.
+pub fn try_register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    let register_receive = register_token(deps, &env, &address, code_hash)?;
+
+    Ok(HandleResponse {
+        messages: vec![register_receive],
+        log: vec![log("token", address)],
+        data: Some(to_binary(&HandleAnswer::RegisterToken {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> StdResult<HandleResponse> {
+    let token = deps.api.canonical_address(&env.message.sender)?;
+    registered_code_hash(&deps.storage, &token)?;
+
+    let msg = msg.ok_or_else(|| StdError::generic_err("Missing lockup message"))?;
+    let (beneficiaries, mut tranches) = match from_binary(&msg)? {
+        ReceiveMsg::CreateLockup {
+            beneficiaries,
+            tranches,
+        } => (beneficiaries, tranches),
+    };
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot lock up zero tokens"));
+    }
+    tranches.sort_by_key(|tranche| tranche.unlock_height);
+    validate_tranches(&env, &tranches)?;
+    let beneficiaries = allocate(deps, &beneficiaries, amount)?;
+
+    let lockup_id = lockup_count_read(&deps.storage)
+        .may_load()?
+        .unwrap_or_default()
+        + 1;
+    lockup_count(&mut deps.storage).save(&lockup_id)?;
+    push_id(
+        &mut deps.storage,
+        TOKEN_LOCKUPS_KEY,
+        token.as_slice(),
+        lockup_id,
+    )?;
+    for beneficiary in &beneficiaries {
+        push_id(
+            &mut deps.storage,
+            BENEFICIARY_LOCKUPS_KEY,
+            beneficiary.address.as_slice(),
+            lockup_id,
+        )?;
+    }
+    lockups(&mut deps.storage).save(
+        &lockup_id.to_be_bytes(),
+        &Lockup {
+            token,
+            creator: deps.api.canonical_address(&from)?,
+            amount,
+            created_at: env.block.height,
+            tranches,
+            beneficiaries,
+        },
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("lockup_id", lockup_id),
+            log("token", env.message.sender),
+            log("creator", from),
+            log("amount", amount),
+        ],
+        data: Some(to_binary(&HandleAnswer::Receive { lockup_id })?),
+    })
+}
+
+pub fn try_claim<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    lockup_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut lockup = load_lockup(&deps.storage, lockup_id)?;
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let unlocked = unlocked_share(&lockup.tranches, env.block.height);
+    let beneficiary = lockup
+        .beneficiaries
+        .iter_mut()
+        .find(|beneficiary| beneficiary.address == sender)
+        .ok_or_else(|| StdError::generic_err("Only beneficiaries of a lockup can claim from it"))?;
+
+    let vested = beneficiary
+        .allocation
+        .multiply_ratio(unlocked, SHARE_PRECISION);
+    let amount = (vested - beneficiary.claimed)?;
+    if amount.is_zero() {
+        return Err(StdError::generic_err(
+            "Nothing to claim until the next tranche unlocks",
+        ));
+    }
+    beneficiary.claimed = Uint128(beneficiary.claimed.u128() + amount.u128());
+    let token = deps.api.human_address(&lockup.token)?;
+    let code_hash = registered_code_hash(&deps.storage, &lockup.token)?;
+    lockups(&mut deps.storage).save(&lockup_id.to_be_bytes(), &lockup)?;
+
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token,
+            callback_code_hash: code_hash,
+            msg: to_binary(&Snip20HandleMsg::Transfer {
+                recipient: env.message.sender,
+                amount,
+                memo: None,
+                padding: None,
+            })?,
+            send: vec![],
+        })],
+        log: vec![log("lockup_id", lockup_id), log("amount", amount)],
+        data: Some(to_binary(&HandleAnswer::Claim { amount })?),
+    })
+}
+
+pub fn try_set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let address = deps.api.canonical_address(&env.message.sender)?;
+    viewing_keys(&mut deps.storage)
+        .save(address.as_slice(), &ViewingKey(key).to_hashed().to_vec())?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey {
+            status: ResponseStatus::Success,
+        })?),
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Lockup { lockup_id } => query_lockup(deps, lockup_id),
+        QueryMsg::TokenLockups {
+            token,
+            page,
+            page_size,
+        } => query_token_lockups(deps, &token, page.unwrap_or(0), page_size),
+        QueryMsg::BeneficiaryLockups {
+            address,
+            key,
+            page,
+            page_size,
+        } => {
+            if is_valid_viewing_key(deps, &address, &key)? {
+                query_beneficiary_lockups(deps, &address, page.unwrap_or(0), page_size)
+            } else {
+                to_binary(&QueryAnswer::ViewingKeyError {
+                    msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+                })
+            }
+        }
+    }
+}
+
+fn query_lockup<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    lockup_id: u64,
+) -> StdResult<Binary> {
+    let lockup = load_lockup(&deps.storage, lockup_id)?;
+
+    to_binary(&QueryAnswer::Lockup {
+        lockup: lockup_summary(deps, lockup_id, lockup)?,
+    })
+}
+
+fn query_token_lockups<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let token = deps.api.canonical_address(token)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        TOKEN_LOCKUPS_KEY,
+        token.as_slice(),
+        page,
+        page_size,
+    )?;
+    let lockups = ids
+        .into_iter()
+        .map(|lockup_id| lockup_summary(deps, lockup_id, load_lockup(&deps.storage, lockup_id)?))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_binary(&QueryAnswer::TokenLockups { lockups, total })
+}
+
+fn query_beneficiary_lockups<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(address)?;
+    let (ids, total) = page_ids(
+        &deps.storage,
+        BENEFICIARY_LOCKUPS_KEY,
+        address.as_slice(),
+        page,
+        page_size,
+    )?;
+
+    let mut lockups = vec![];
+    for lockup_id in ids {
+        let lockup = load_lockup(&deps.storage, lockup_id)?;
+        let beneficiary = lockup
+            .beneficiaries
+            .iter()
+            .find(|beneficiary| beneficiary.address == address)
+            .ok_or_else(|| StdError::generic_err("Beneficiary not found in lockup"))?;
+        lockups.push(BeneficiaryDetails {
+            lockup_id,
+            token: deps.api.human_address(&lockup.token)?,
+            share: beneficiary.share,
+            allocation: beneficiary.allocation,
+            claimed: beneficiary.claimed,
+            tranches: lockup.tranches.clone(),
+        });
+    }
+
+    to_binary(&QueryAnswer::BeneficiaryLockups { lockups, total })
+}
+
+fn lockup_summary<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    lockup_id: u64,
+    lockup: Lockup,
+) -> StdResult<LockupSummary> {
+    Ok(LockupSummary {
+        id: lockup_id,
+        token: deps.api.human_address(&lockup.token)?,
+        creator: deps.api.human_address(&lockup.creator)?,
+        amount: lockup.amount,
+        claimed: Uint128(
+            lockup
+                .beneficiaries
+                .iter()
+                .map(|beneficiary| beneficiary.claimed.u128())
+                .sum(),
+        ),
+        created_at: lockup.created_at,
+        tranches: lockup.tranches,
+        beneficiary_count: lockup.beneficiaries.len() as u32,
+    })
+}
+
+/// Checks `key` against the viewing key of `address`, taking the same time whether a key is set or not.
+fn is_valid_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    key: &str,
+) -> StdResult<bool> {
+    let address = deps.api.canonical_address(address)?;
+    let key = ViewingKey(key.to_string());
+
+    Ok(
+        match viewing_keys_read(&deps.storage).may_load(address.as_slice())? {
+            Some(expected_key) => key.check_viewing_key(expected_key.as_slice()),
+            None => {
+                key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+                false
+            }
+        },
+    )
+}
+
+fn validate_tranches(env: &Env, tranches: &[Tranche]) -> StdResult<()> {
+    if tranches.is_empty() {
+        return Err(StdError::generic_err("A lockup needs at least one tranche"));
+    }
+    if tranches[0].unlock_height <= env.block.height {
+        return Err(StdError::generic_err(
+            "Tranches must unlock at a future height",
+        ));
+    }
+    if tranches
+        .windows(2)
+        .any(|pair| pair[0].unlock_height == pair[1].unlock_height)
+    {
+        return Err(StdError::generic_err(
+            "Tranches must unlock at different heights",
+        ));
+    }
+    check_shares(tranches.iter().map(|tranche| tranche.share), "Tranche")
+}
+
+/// Splits `amount` between `beneficiaries` by share. The last one gets what rounding leaves over,
+/// so that the whole amount can be claimed.
+fn allocate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    beneficiaries: &[BeneficiaryShare],
+    amount: Uint128,
+) -> StdResult<Vec<Beneficiary>> {
+    if beneficiaries.is_empty() {
+        return Err(StdError::generic_err(
+            "A lockup needs at least one beneficiary",
+        ));
+    }
+    check_shares(
+        beneficiaries.iter().map(|beneficiary| beneficiary.share),
+        "Beneficiary",
+    )?;
+
+    let mut allocated = Uint128::zero();
+    let mut allocations: Vec<Beneficiary> = vec![];
+    for (index, beneficiary) in beneficiaries.iter().enumerate() {
+        let address = deps.api.canonical_address(&beneficiary.address)?;
+        if allocations.iter().any(|other| other.address == address) {
+            return Err(StdError::generic_err(format!(
+                "{} is listed as a beneficiary more than once",
+                beneficiary.address
+            )));
+        }
+        let allocation = if index + 1 == beneficiaries.len() {
+            (amount - allocated)?
+        } else {
+            amount * beneficiary.share
+        };
+        allocated = Uint128(allocated.u128() + allocation.u128());
+        allocations.push(Beneficiary {
+            address,
+            share: beneficiary.share,
+            allocation,
+            claimed: Uint128::zero(),
+        });
+    }
+
+    Ok(allocations)
+}
+
+fn check_shares(shares: impl Iterator<Item = Decimal>, kind: &str) -> StdResult<()> {
+    let mut total = 0u128;
+    for share in shares {
+        if share.is_zero() {
+            return Err(StdError::generic_err(format!(
+                "{} shares must be above 0%",
+                kind
+            )));
+        }
+        // Also keeps `share_units` from overflowing.
+        if share > Decimal::one() {
+            return Err(StdError::generic_err(format!(
+                "{} shares cannot exceed 100%",
+                kind
+            )));
+        }
+        total = share_units(share)
+            .checked_add(total)
+            .ok_or_else(|| StdError::generic_err(format!("{} shares overflow", kind)))?;
+    }
+    if total != SHARE_PRECISION {
+        return Err(StdError::generic_err(format!(
+            "{} shares must add up to 100%",
+            kind
+        )));
+    }
+
+    Ok(())
+}
+
+/// Share of a lockup unlocked at `height`, in units of `SHARE_PRECISION`.
+fn unlocked_share(tranches: &[Tranche], height: u64) -> u128 {
+    tranches
+        .iter()
+        .filter(|tranche| tranche.unlock_height <= height)
+        .map(|tranche| share_units(tranche.share))
+        .sum()
+}
+
+/// Converts a share to an integer, so that shares add up exactly.
+fn share_units(share: Decimal) -> u128 {
+    (Uint128(SHARE_PRECISION) * share).u128()
+}
+
+fn load_lockup<S: Storage>(storage: &S, lockup_id: u64) -> StdResult<Lockup> {
+    lockups_read(storage)
+        .may_load(&lockup_id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("Lockup {} not found", lockup_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+
+    fn init_lockup() -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, &[]);
+        init(&mut deps, mock_env("admin", &[]), InitMsg {}).unwrap();
+        handle(
+            &mut deps,
+            mock_env("anyone", &[]),
+            HandleMsg::RegisterToken {
+                address: HumanAddr("snipix".to_string()),
+                code_hash: "snipix_hash".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+
+        deps
+    }
+
+    fn env_at(sender: &str, height: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.height = height;
+        env
+    }
+
+    fn create_lockup_msg(
+        amount: u128,
+        beneficiaries: &[(&str, u64)],
+        tranches: &[(u64, u64)],
+    ) -> HandleMsg {
+        HandleMsg::Receive {
+            sender: HumanAddr("team".to_string()),
+            from: HumanAddr("team".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            msg: Some(
+                to_binary(&ReceiveMsg::CreateLockup {
+                    beneficiaries: beneficiaries
+                        .iter()
+                        .map(|(address, percent)| BeneficiaryShare {
+                            address: HumanAddr(address.to_string()),
+                            share: Decimal::percent(*percent),
+                        })
+                        .collect(),
+                    tranches: tranches
+                        .iter()
+                        .map(|(unlock_height, percent)| Tranche {
+                            unlock_height: *unlock_height,
+                            share: Decimal::percent(*percent),
+                        })
+                        .collect(),
+                })
+                .unwrap(),
+            ),
+        }
+    }
+
+    fn claim(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        beneficiary: &str,
+        height: u64,
+    ) -> StdResult<Uint128> {
+        let response = handle(
+            deps,
+            env_at(beneficiary, height),
+            HandleMsg::Claim {
+                lockup_id: 1,
+                padding: None,
+            },
+        )?;
+        match from_binary(&response.data.unwrap())? {
+            HandleAnswer::Claim { amount } => Ok(amount),
+            _ => panic!("unexpected answer"),
+        }
+    }
+
+    fn summary(deps: &Extern<MockStorage, MockApi, MockQuerier>) -> LockupSummary {
+        match from_binary(&query(deps, QueryMsg::Lockup { lockup_id: 1 }).unwrap()).unwrap() {
+            QueryAnswer::Lockup { lockup } => lockup,
+            _ => panic!("unexpected answer"),
+        }
+    }
+
+    fn beneficiary_lockups(
+        deps: &Extern<MockStorage, MockApi, MockQuerier>,
+        beneficiary: &str,
+        key: &str,
+    ) -> QueryAnswer {
+        let msg = QueryMsg::BeneficiaryLockups {
+            address: HumanAddr(beneficiary.to_string()),
+            key: key.to_string(),
+            page: None,
+            page_size: 10,
+        };
+        from_binary(&query(deps, msg).unwrap()).unwrap()
+    }
+
+    fn assert_generic_err<T: std::fmt::Debug>(result: StdResult<T>, expected: &str) {
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert!(msg.contains(expected), "{}", msg),
+            other => panic!("expected error {:?}, got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn test_create_lockup_validation() {
+        let mut deps = init_lockup();
+
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("other_token", 10),
+                create_lockup_msg(1000, &[("alice", 100)], &[(20, 100)]),
+            ),
+            "Token is not registered",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("snipix", 10),
+                create_lockup_msg(1000, &[("alice", 60), ("bob", 30)], &[(20, 100)]),
+            ),
+            "Beneficiary shares must add up to 100%",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("snipix", 10),
+                create_lockup_msg(1000, &[("alice", u64::MAX)], &[(20, 100)]),
+            ),
+            "Beneficiary shares cannot exceed 100%",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("snipix", 10),
+                create_lockup_msg(1000, &[("alice", 50), ("alice", 50)], &[(20, 100)]),
+            ),
+            "alice is listed as a beneficiary more than once",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("snipix", 10),
+                create_lockup_msg(1000, &[("alice", 100)], &[(20, 50), (10, 50)]),
+            ),
+            "Tranches must unlock at a future height",
+        );
+        assert_generic_err(
+            handle(
+                &mut deps,
+                env_at("snipix", 10),
+                create_lockup_msg(1000, &[("alice", 100)], &[(20, 50), (30, 40)]),
+            ),
+            "Tranche shares must add up to 100%",
+        );
+    }
+
+    #[test]
+    fn test_tranches_release_to_beneficiaries() {
+        let mut deps = init_lockup();
+        handle(
+            &mut deps,
+            env_at("snipix", 10),
+            create_lockup_msg(1001, &[("alice", 60), ("bob", 40)], &[(30, 75), (20, 25)]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(&deps),
+            LockupSummary {
+                id: 1,
+                token: HumanAddr("snipix".to_string()),
+                creator: HumanAddr("team".to_string()),
+                amount: Uint128(1001),
+                claimed: Uint128::zero(),
+                created_at: 10,
+                tranches: vec![
+                    Tranche {
+                        unlock_height: 20,
+                        share: Decimal::percent(25),
+                    },
+                    Tranche {
+                        unlock_height: 30,
+                        share: Decimal::percent(75),
+                    },
+                ],
+                beneficiary_count: 2,
+            }
+        );
+
+        assert_generic_err(claim(&mut deps, "alice", 15), "Nothing to claim");
+        assert_generic_err(claim(&mut deps, "team", 20), "Only beneficiaries");
+        assert_eq!(claim(&mut deps, "alice", 20).unwrap(), Uint128(150));
+        assert_generic_err(claim(&mut deps, "alice", 25), "Nothing to claim");
+        assert_eq!(claim(&mut deps, "alice", 30).unwrap(), Uint128(450));
+        // bob gets what rounding left over, so the whole lockup is claimed.
+        assert_eq!(claim(&mut deps, "bob", 40).unwrap(), Uint128(401));
+        assert_eq!(summary(&deps).claimed, Uint128(1001));
+
+        match beneficiary_lockups(&deps, "bob", "bob_key") {
+            QueryAnswer::ViewingKeyError { .. } => {}
+            _ => panic!("expected a viewing key error"),
+        }
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::SetViewingKey {
+                key: "bob_key".to_string(),
+                padding: None,
+            },
+        )
+        .unwrap();
+        match beneficiary_lockups(&deps, "bob", "bob_key") {
+            QueryAnswer::BeneficiaryLockups { lockups, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(lockups[0].share, Decimal::percent(40));
+                assert_eq!(lockups[0].allocation, Uint128(401));
+                assert_eq!(lockups[0].claimed, Uint128(401));
+            }
+            _ => panic!("unexpected answer"),
+        }
+    }
+}
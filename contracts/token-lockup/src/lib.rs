@@ -0,0 +1,5 @@
+pub mod contract;
+pub mod msg;
+pub mod state;
+
+lock_utils::entry_points!(contract);
@@ -0,0 +1,128 @@
+use atl_snip20_reference_impl::msg::ResponseStatus;
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct InitMsg {}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    /// Has a snipix token notify the contract of what is sent to it, so it can be locked up.
+    RegisterToken {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    /// Sent by a registered token when tokens are sent to the contract, with a `ReceiveMsg`.
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        memo: Option<String>,
+        msg: Option<Binary>,
+    },
+    /// Sends the sender their share of the tranches of a lockup that unlocked so far.
+    Claim {
+        lockup_id: u64,
+        padding: Option<String>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Locks up the received tokens. Each tranche releases its share of them to every
+    /// beneficiary, in proportion to their share. Both kinds of shares must add up to 100%.
+    CreateLockup {
+        beneficiaries: Vec<BeneficiaryShare>,
+        tranches: Vec<Tranche>,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct BeneficiaryShare {
+    pub address: HumanAddr,
+    pub share: Decimal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct Tranche {
+    pub unlock_height: u64,
+    pub share: Decimal,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    RegisterToken { status: ResponseStatus },
+    Receive { lockup_id: u64 },
+    Claim { amount: Uint128 },
+    SetViewingKey { status: ResponseStatus },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Public summary of a lockup, which leaves out who its beneficiaries are.
+    Lockup { lockup_id: u64 },
+    TokenLockups {
+        token: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    /// Lockups `address` is a beneficiary of, most recent first, with their share of each.
+    BeneficiaryLockups {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct LockupSummary {
+    pub id: u64,
+    pub token: HumanAddr,
+    pub creator: HumanAddr,
+    pub amount: Uint128,
+    pub claimed: Uint128,
+    pub created_at: u64,
+    pub tranches: Vec<Tranche>,
+    pub beneficiary_count: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct BeneficiaryDetails {
+    pub lockup_id: u64,
+    pub token: HumanAddr,
+    pub share: Decimal,
+    /// Amount the beneficiary gets once every tranche unlocked.
+    pub allocation: Uint128,
+    pub claimed: Uint128,
+    pub tranches: Vec<Tranche>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    Lockup {
+        lockup: LockupSummary,
+    },
+    TokenLockups {
+        lockups: Vec<LockupSummary>,
+        total: u64,
+    },
+    BeneficiaryLockups {
+        lockups: Vec<BeneficiaryDetails>,
+        total: u64,
+    },
+    ViewingKeyError {
+        msg: String,
+    },
+}
@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{CanonicalAddr, Decimal, Storage, Uint128};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, Bucket, ReadonlyBucket, ReadonlySingleton,
+    Singleton,
+};
+
+use crate::msg::Tranche;
+
+pub static LOCKUP_COUNT_KEY: &[u8] = b"lockup_count";
+pub static LOCKUPS_KEY: &[u8] = b"lockups";
+pub static TOKEN_LOCKUPS_KEY: &[u8] = b"token_lockups";
+pub static BENEFICIARY_LOCKUPS_KEY: &[u8] = b"beneficiary_lockups";
+pub static VIEWING_KEYS_KEY: &[u8] = b"viewing_keys";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Lockup {
+    pub token: CanonicalAddr,
+    pub creator: CanonicalAddr,
+    pub amount: Uint128,
+    /// Block height at which the tokens were locked up.
+    pub created_at: u64,
+    /// Sorted by unlock height.
+    pub tranches: Vec<Tranche>,
+    pub beneficiaries: Vec<Beneficiary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Beneficiary {
+    pub address: CanonicalAddr,
+    pub share: Decimal,
+    /// Share of the lockup amount. The last beneficiary also gets what rounding leaves over.
+    pub allocation: Uint128,
+    pub claimed: Uint128,
+}
+
+pub fn lockup_count<S: Storage>(storage: &mut S) -> Singleton<S, u64> {
+    singleton(storage, LOCKUP_COUNT_KEY)
+}
+
+pub fn lockup_count_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, u64> {
+    singleton_read(storage, LOCKUP_COUNT_KEY)
+}
+
+/// Lockups, by id.
+pub fn lockups<S: Storage>(storage: &mut S) -> Bucket<S, Lockup> {
+    bucket(LOCKUPS_KEY, storage)
+}
+
+pub fn lockups_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Lockup> {
+    bucket_read(LOCKUPS_KEY, storage)
+}
+
+/// Hashed viewing keys, by address.
+pub fn viewing_keys<S: Storage>(storage: &mut S) -> Bucket<S, Vec<u8>> {
+    bucket(VIEWING_KEYS_KEY, storage)
+}
+
+pub fn viewing_keys_read<S: Storage>(storage: &S) -> ReadonlyBucket<S, Vec<u8>> {
+    bucket_read(VIEWING_KEYS_KEY, storage)
+}
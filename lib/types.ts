@@ -4,6 +4,8 @@ export type Uint128 = string
 
 export type Binary = string
 
+export type Decimal = string
+
 export type Logo = { url: string } | { embedded: EmbeddedLogo }
 
 export type EmbeddedLogo = { svg: Binary } | { png: Binary }
@@ -28,6 +30,19 @@ export interface Snip20InitMsg {
 
 export interface SnipixInitMsg extends Snip20InitMsg {
   marketing_info?: MarketingInfo | null
+  governance?: GovernanceConfig | null
+  reward_tokens?: Array<RewardToken> | null
+}
+
+export interface RewardToken {
+  address: HumanAddr
+  code_hash: string
+}
+
+export interface GovernanceConfig {
+  quorum: Decimal
+  threshold: Decimal
+  voting_period: number
 }
 
 export interface MarketingInfoResponse {
@@ -0,0 +1,40 @@
+use cosmwasm_std::{ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use secret_toolkit::storage::{AppendStore, AppendStoreMut};
+
+/// Appends `id` to the list stored under `namespace` and `key`, such as the locks of a token.
+/// Lists let records be enumerated without iterating over storage.
+pub fn push_id<S: Storage>(
+    storage: &mut S,
+    namespace: &[u8],
+    key: &[u8],
+    id: u64,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[namespace, key], storage);
+    let mut store = AppendStoreMut::<u64, _, _>::attach_or_create(&mut store)?;
+    store.push(&id)
+}
+
+/// Returns a page of the ids in a list, most recent first, along with the length of the list.
+pub fn page_ids<S: ReadonlyStorage>(
+    storage: &S,
+    namespace: &[u8],
+    key: &[u8],
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<u64>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[namespace, key], storage);
+    let store = match AppendStore::<u64, _, _>::attach(&store) {
+        Some(store) => store?,
+        None => return Ok((vec![], 0)),
+    };
+
+    let ids = store
+        .iter()
+        .rev()
+        .skip(page as usize * page_size as usize)
+        .take(page_size as usize)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok((ids, store.len() as u64))
+}
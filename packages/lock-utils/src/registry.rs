@@ -0,0 +1,58 @@
+use atl_snip20_reference_impl::msg::HandleMsg as Snip20HandleMsg;
+use cosmwasm_std::{
+    to_binary, Api, CanonicalAddr, CosmosMsg, Env, Extern, HumanAddr, Querier, ReadonlyStorage,
+    StdError, StdResult, Storage, WasmMsg,
+};
+use cosmwasm_storage::{bucket, bucket_read, Bucket, ReadonlyBucket};
+
+pub static TOKEN_CODE_HASHES_KEY: &[u8] = b"token_code_hashes";
+
+/// Records the code hash of the token at `address`, and returns the message having the token
+/// notify this contract of what it receives.
+///
+/// Tokens can only be registered once. That first registration cannot carry a wrong code hash:
+/// the `RegisterReceive` call would then fail, and revert the registration along with it.
+pub fn register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    address: &HumanAddr,
+    code_hash: String,
+) -> StdResult<CosmosMsg> {
+    let token = deps.api.canonical_address(address)?;
+    if token_code_hashes_read(&deps.storage)
+        .may_load(token.as_slice())?
+        .is_some()
+    {
+        return Err(StdError::generic_err("Token is already registered"));
+    }
+    token_code_hashes(&mut deps.storage).save(token.as_slice(), &code_hash)?;
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: address.clone(),
+        callback_code_hash: code_hash,
+        msg: to_binary(&Snip20HandleMsg::RegisterReceive {
+            code_hash: env.contract_code_hash.clone(),
+            padding: None,
+        })?,
+        send: vec![],
+    }))
+}
+
+/// Code hash of a registered token.
+pub fn registered_code_hash<S: ReadonlyStorage>(
+    storage: &S,
+    token: &CanonicalAddr,
+) -> StdResult<String> {
+    token_code_hashes_read(storage)
+        .may_load(token.as_slice())?
+        .ok_or_else(|| StdError::generic_err("Token is not registered"))
+}
+
+/// Code hashes of the registered tokens, by token address.
+pub fn token_code_hashes<S: Storage>(storage: &mut S) -> Bucket<S, String> {
+    bucket(TOKEN_CODE_HASHES_KEY, storage)
+}
+
+pub fn token_code_hashes_read<S: ReadonlyStorage>(storage: &S) -> ReadonlyBucket<S, String> {
+    bucket_read(TOKEN_CODE_HASHES_KEY, storage)
+}
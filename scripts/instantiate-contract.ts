@@ -48,26 +48,138 @@ async function main() {
     restUrl, 
   })
 
-  const initMsg = {
-    name: 'Test token',
-    symbol: 'TTX',
-    decimals: 6,
-    prng_seed: Buffer.from(randomUUID()).toString('base64'),
-    marketing_info: {
-      project: `Atomik Labs: Token #${randomUUID()}`
+  const initMsg = contractName === 'snipix_factory'
+    ? await factoryInitMsg(envName)
+    : contractName === 'snipix_pool'
+    ? await poolInitMsg(envName)
+    : contractName in lockLabels
+    ? {}
+    : {
+      name: 'Test token',
+      symbol: 'TTX',
+      decimals: 6,
+      prng_seed: Buffer.from(randomUUID()).toString('base64'),
+      marketing_info: {
+        project: `Atomik Labs: Token #${randomUUID()}`
+      }
     }
-  }
 
   const contractInstantiateResult = await client.instantiateContract({
     codeId: typeof codeId === 'string' ? parseInt(codeId, 10) : codeId,
     codeHash,
-    label: 'Snip-20 from Atomiklabs.io',
+    label: contractName === 'snipix_factory'
+      ? `Snipix factory from Atomiklabs.io #${randomUUID()}`
+      : contractName === 'snipix_pool'
+      ? `Snipix pool from Atomiklabs.io #${randomUUID()}`
+      : contractName in lockLabels
+      ? `${lockLabels[contractName]} from Atomiklabs.io #${randomUUID()}`
+      : 'Snip-20 from Atomiklabs.io',
     initMsg
   })
 
   await writeContractManifestFile(contractManifestFilePath, Object.assign({}, snipixManifest, { ...contractInstantiateResult }))
 }
 
+// Lock contracts take no init params.
+const lockLabels: Record<string, string> = {
+  liquidity_lock: 'Liquidity lock',
+  token_lockup: 'Token lockup',
+}
+
+// The factory instantiates tokens from the snipix code uploaded with `storeCode/snipix`.
+async function factoryInitMsg(envName: string) {
+  const snipixManifest = (await readContractManifestFile(`snipix.${envName}`)) as ContractManifest
+
+  if (!snipixManifest.codeId || !snipixManifest.codeHash) {
+    throw new Error('Missing snipix `codeId` or `codeHash`, upload snipix first')
+  }
+
+  return {
+    snipix_code_id: snipixManifest.codeId,
+    snipix_code_hash: snipixManifest.codeHash,
+    prng_seed: Buffer.from(randomUUID()).toString('base64'),
+    templates: launchTemplates,
+  }
+}
+
+// The pool trades the token at `TOKEN_ADDRESS` against SCRT, and issues LP shares as a token
+// instantiated from the snipix code uploaded with `storeCode/snipix`.
+async function poolInitMsg(envName: string) {
+  const snipixManifest = (await readContractManifestFile(`snipix.${envName}`)) as ContractManifest
+  const tokenAddress = process.env.TOKEN_ADDRESS
+  const tokenCodeHash = process.env.TOKEN_CODE_HASH
+
+  if (!snipixManifest.codeId || !snipixManifest.codeHash) {
+    throw new Error('Missing snipix `codeId` or `codeHash`, upload snipix first')
+  }
+
+  if (!tokenAddress || !tokenCodeHash) {
+    throw new Error('Missing env vars. Ensure providing both: `TOKEN_ADDRESS` and `TOKEN_CODE_HASH`')
+  }
+
+  return {
+    token: { address: tokenAddress, code_hash: tokenCodeHash },
+    lp_token_code_id: snipixManifest.codeId,
+    lp_token_code_hash: snipixManifest.codeHash,
+    lp_token_name: process.env.LP_TOKEN_NAME || 'Snipix pool liquidity',
+    lp_token_symbol: process.env.LP_TOKEN_SYMBOL || 'SNIPLP',
+    prng_seed: Buffer.from(randomUUID()).toString('base64'),
+    swap_fee: process.env.SWAP_FEE || '0.003',
+  }
+}
+
+const fixedRules = {
+  decimals_overridable: false,
+  config_overridable: false,
+  extensions_overridable: false,
+}
+
+const noExtensions = {
+  supply_cap: null,
+  vesting_period: null,
+  governance: null,
+}
+
+// Presets `CreateToken` launches from, so creators only pick a name, a symbol and a template.
+const launchTemplates = [
+  {
+    name: 'meme token',
+    description: 'Fixed supply, public and burnable',
+    decimals: 6,
+    config: { public_total_supply: true, enable_burn: true },
+    extensions: noExtensions,
+    rules: fixedRules,
+  },
+  {
+    name: 'stablecoin',
+    description: 'Minted and burned by its issuer',
+    decimals: 6,
+    config: { public_total_supply: true, enable_mint: true, enable_burn: true },
+    extensions: noExtensions,
+    rules: { ...fixedRules, extensions_overridable: true },
+  },
+  {
+    name: 'governance token',
+    description: 'Capped supply, vesting over about a week, governed by its holders',
+    decimals: 6,
+    config: { public_total_supply: true },
+    extensions: {
+      supply_cap: '1000000000000000',
+      vesting_period: 100800,
+      governance: { quorum: '0.1', threshold: '0.5', voting_period: 14400 },
+    },
+    rules: { ...fixedRules, extensions_overridable: true },
+  },
+  {
+    name: 'loyalty points',
+    description: 'Private balances, minted by the issuer and redeemed by burning',
+    decimals: 0,
+    config: { public_total_supply: false, enable_mint: true, enable_burn: true },
+    extensions: noExtensions,
+    rules: { ...fixedRules, config_overridable: true },
+  },
+]
+
 // Makes the script crash on unhandled rejections instead of silently
 // ignoring them. In the future, promise rejections that are not handled will
 // terminate the Node.js process with a non-zero exit code.